@@ -0,0 +1,7 @@
+-- Add migration script here
+ALTER TABLE subscriptions
+    ADD COLUMN confirmed_at            timestamptz NULL,
+    ADD COLUMN confirmation_ip         text        NULL,
+    ADD COLUMN confirmation_user_agent text        NULL,
+    ADD COLUMN source                  text        NOT NULL DEFAULT 'Form',
+    ADD COLUMN consent_version         text        NULL;
@@ -0,0 +1,36 @@
+-- Add migration script here
+CREATE TABLE lists
+(
+    id         uuid        NOT NULL PRIMARY KEY,
+    slug       text        NOT NULL UNIQUE,
+    name       text        NOT NULL,
+    created_at timestamptz NOT NULL
+);
+
+CREATE TABLE list_subscriptions
+(
+    list_id       uuid        NOT NULL REFERENCES lists (id),
+    subscriber_id uuid        NOT NULL REFERENCES subscriptions (id),
+    status        text        NOT NULL,
+    subscribed_at timestamptz NOT NULL,
+    confirmed_at  timestamptz NULL,
+    PRIMARY KEY (list_id, subscriber_id)
+);
+
+INSERT INTO lists (id, slug, name, created_at)
+VALUES (gen_random_uuid(), 'default', 'Newsletter', now());
+
+INSERT INTO list_subscriptions (list_id, subscriber_id, status, subscribed_at, confirmed_at)
+SELECT l.id, s.id, s.status, s.subscribed_at, s.confirmed_at
+FROM subscriptions s
+         CROSS JOIN lists l
+WHERE l.slug = 'default';
+
+ALTER TABLE subscription_tokens
+    ADD COLUMN list_id uuid NULL REFERENCES lists (id);
+
+UPDATE subscription_tokens
+SET list_id = (SELECT id FROM lists WHERE slug = 'default');
+
+ALTER TABLE subscription_tokens
+    ALTER COLUMN list_id SET NOT NULL;
@@ -0,0 +1,11 @@
+-- Add migration script here
+ALTER TABLE subscriptions
+    ADD COLUMN attributes jsonb NOT NULL DEFAULT '{}'::jsonb;
+
+CREATE TABLE segments
+(
+    id         uuid        NOT NULL PRIMARY KEY,
+    name       text        NOT NULL UNIQUE,
+    filter     text        NOT NULL,
+    created_at timestamptz NOT NULL
+);
@@ -0,0 +1,20 @@
+-- Add migration script here
+CREATE TABLE subscriber_tags
+(
+    subscriber_id uuid        NOT NULL REFERENCES subscriptions (id),
+    tag           text        NOT NULL,
+    created_at    timestamptz NOT NULL,
+    PRIMARY KEY (subscriber_id, tag)
+);
+
+CREATE INDEX subscriber_tags_tag_idx ON subscriber_tags (tag);
+
+INSERT INTO subscriber_tags (subscriber_id, tag, created_at)
+SELECT s.id, t.tag, now()
+FROM subscriptions s
+         CROSS JOIN LATERAL jsonb_array_elements_text(s.attributes -> 'tags') AS t(tag)
+WHERE jsonb_typeof(s.attributes -> 'tags') = 'array'
+ON CONFLICT DO NOTHING;
+
+UPDATE subscriptions
+SET attributes = attributes - 'tags';
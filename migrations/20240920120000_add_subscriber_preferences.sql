@@ -0,0 +1,14 @@
+-- Add migration script here
+ALTER TABLE subscriptions
+    ADD COLUMN content_format text NOT NULL DEFAULT 'Html';
+
+CREATE TABLE preference_tokens
+(
+    preference_token text        NOT NULL PRIMARY KEY,
+    subscriber_id    uuid        NOT NULL UNIQUE REFERENCES subscriptions (id),
+    created_at       timestamptz NOT NULL
+);
+
+INSERT INTO preference_tokens (preference_token, subscriber_id, created_at)
+SELECT replace(gen_random_uuid()::text, '-', ''), id, now()
+FROM subscriptions;
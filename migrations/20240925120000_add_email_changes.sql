@@ -0,0 +1,13 @@
+-- Add migration script here
+CREATE TABLE email_changes
+(
+    id            uuid        NOT NULL PRIMARY KEY,
+    subscriber_id uuid        NOT NULL REFERENCES subscriptions (id),
+    old_email     text        NOT NULL,
+    new_email     text        NOT NULL,
+    change_token  text        NOT NULL UNIQUE,
+    requested_at  timestamptz NOT NULL,
+    confirmed_at  timestamptz NULL
+);
+
+CREATE INDEX email_changes_subscriber_id_idx ON email_changes (subscriber_id);
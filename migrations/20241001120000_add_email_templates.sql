@@ -0,0 +1,42 @@
+-- Add migration script here
+CREATE TABLE email_templates
+(
+    name       text        NOT NULL PRIMARY KEY,
+    kind       text        NOT NULL,
+    subject    text        NOT NULL,
+    html_body  text        NOT NULL,
+    text_body  text        NOT NULL,
+    updated_at timestamptz NOT NULL
+);
+
+INSERT INTO email_templates (name, kind, subject, html_body, text_body, updated_at)
+VALUES ('layout', 'Layout', '',
+        '<!doctype html>
+<html>
+<body>
+{% block content %}{% endblock %}
+{% include "footer" %}
+</body>
+</html>',
+        '{% block content %}{% endblock %}
+{% include "footer" %}',
+        now()),
+       ('footer', 'Partial', '',
+        '<p><a href="{{ unsubscribe_link }}">Manage preferences or unsubscribe</a></p>',
+        '--
+Manage preferences or unsubscribe: {{ unsubscribe_link }}',
+        now()),
+       ('confirmation', 'Email', 'Welcome!',
+        '{% extends "layout" %}{% block content %}Welcome to our newsletter, {{ name }}!<br />
+Click <a href="{{ confirmation_link }}">here</a> to confirm your subscription.{% endblock %}',
+        '{% extends "layout" %}{% block content %}Welcome to our newsletter, {{ name }}!
+Visit {{ confirmation_link }} to confirm your subscription{% endblock %}',
+        now()),
+       ('email_change', 'Email', 'Confirm your new email',
+        '{% extends "layout" %}{% block content %}Click <a href="{{ confirmation_link }}">here</a> to start receiving our newsletter at this address.{% endblock %}',
+        '{% extends "layout" %}{% block content %}Visit {{ confirmation_link }} to start receiving our newsletter at this address{% endblock %}',
+        now()),
+       ('newsletter', 'Email', '{{ title }}',
+        '{% extends "layout" %}{% block content %}{{ content|safe }}{% endblock %}',
+        '{% extends "layout" %}{% block content %}{{ content }}{% endblock %}',
+        now());
@@ -0,0 +1,10 @@
+-- Add migration script here
+CREATE TABLE newsletter_issues
+(
+    id           uuid        NOT NULL PRIMARY KEY,
+    title        text        NOT NULL,
+    html_content text        NOT NULL,
+    text_content text        NOT NULL,
+    created_at   timestamptz NOT NULL,
+    published_at timestamptz NULL
+);
@@ -0,0 +1,7 @@
+-- Add migration script here
+ALTER TABLE subscriptions ADD COLUMN locale text NULL;
+
+ALTER TABLE email_templates ADD COLUMN locale text NOT NULL DEFAULT 'en';
+ALTER TABLE email_templates ALTER COLUMN locale DROP DEFAULT;
+ALTER TABLE email_templates DROP CONSTRAINT email_templates_pkey;
+ALTER TABLE email_templates ADD PRIMARY KEY (name, locale);
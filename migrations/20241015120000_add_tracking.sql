@@ -0,0 +1,15 @@
+-- Add migration script here
+ALTER TABLE newsletter_issues ADD COLUMN tracking_enabled boolean NOT NULL DEFAULT false;
+ALTER TABLE subscriptions ADD COLUMN tracking_opt_out boolean NOT NULL DEFAULT false;
+
+CREATE TABLE tracking_events
+(
+    id            uuid        NOT NULL PRIMARY KEY,
+    issue_id      uuid        NOT NULL REFERENCES newsletter_issues (id),
+    subscriber_id uuid        NOT NULL REFERENCES subscriptions (id),
+    kind          text        NOT NULL,
+    url           text        NULL,
+    occurred_at   timestamptz NOT NULL
+);
+
+CREATE INDEX tracking_events_issue_id_idx ON tracking_events (issue_id);
@@ -0,0 +1,2 @@
+-- Add migration script here
+ALTER TABLE subscriptions ADD COLUMN soft_bounce_count integer NOT NULL DEFAULT 0;
@@ -0,0 +1,12 @@
+-- Add migration script here
+CREATE TABLE suppressions
+(
+    value      text        NOT NULL PRIMARY KEY,
+    reason     text        NOT NULL,
+    created_at timestamptz NOT NULL
+);
+
+INSERT INTO suppressions (value, reason, created_at)
+SELECT DISTINCT ON (lower(email)) lower(email), status, now()
+FROM subscriptions
+WHERE status IN ('Unsubscribed', 'Bounced', 'Complained');
@@ -0,0 +1,16 @@
+-- Add migration script here
+CREATE TABLE deliveries
+(
+    issue_id            uuid        NOT NULL REFERENCES newsletter_issues (id),
+    subscriber_id       uuid        NOT NULL REFERENCES subscriptions (id),
+    status              text        NOT NULL,
+    provider_message_id text        NULL,
+    error               text        NULL,
+    attempts            integer     NOT NULL,
+    queued_at           timestamptz NOT NULL,
+    sent_at             timestamptz NULL,
+    updated_at          timestamptz NOT NULL,
+    PRIMARY KEY (issue_id, subscriber_id)
+);
+
+CREATE INDEX deliveries_provider_message_id_idx ON deliveries (provider_message_id);
@@ -0,0 +1,17 @@
+-- Add migration script here
+CREATE TABLE assets
+(
+    id           uuid        NOT NULL PRIMARY KEY,
+    filename     text        NOT NULL,
+    content_type text        NOT NULL,
+    content      bytea       NOT NULL,
+    created_at   timestamptz NOT NULL
+);
+
+CREATE TABLE issue_assets
+(
+    issue_id uuid    NOT NULL REFERENCES newsletter_issues (id),
+    asset_id uuid    NOT NULL REFERENCES assets (id),
+    inline   boolean NOT NULL,
+    PRIMARY KEY (issue_id, asset_id)
+);
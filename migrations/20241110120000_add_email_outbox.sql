@@ -0,0 +1,17 @@
+-- Add migration script here
+CREATE TABLE email_outbox
+(
+    id              uuid        NOT NULL PRIMARY KEY,
+    recipient       text        NOT NULL,
+    subject         text        NOT NULL,
+    html_content    text        NOT NULL,
+    text_content    text        NOT NULL,
+    tag             text,
+    attempts        integer     NOT NULL DEFAULT 0,
+    last_error      text,
+    created_at      timestamptz NOT NULL,
+    next_attempt_at timestamptz NOT NULL,
+    sent_at         timestamptz
+);
+
+CREATE INDEX email_outbox_pending_idx ON email_outbox (next_attempt_at) WHERE sent_at IS NULL;
@@ -2,10 +2,13 @@ use crate::app_config::AppConfig;
 use crate::email_client::EmailClient;
 use crate::infrastructure::sqlx_postgres_repository::SqlxPostgresRepository;
 use std::fmt::Debug;
+use tokio::sync::Notify;
 
 #[derive(Debug)]
 pub struct AppState {
     pub config: AppConfig,
     pub repository: SqlxPostgresRepository,
     pub email_client: EmailClient,
+    /// Wakes the outbox relay after emails were written to the outbox
+    pub outbox_notify: Notify,
 }
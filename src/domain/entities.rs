@@ -1 +1,10 @@
+pub mod asset;
+pub mod delivery;
+pub mod email_template;
+pub mod mailing_list;
+pub mod newsletter_issue;
+pub mod outbox_email;
+pub mod recipient;
+pub mod segment;
 pub mod subscriber;
+pub mod suppression;
@@ -0,0 +1,9 @@
+use crate::domain::value_objects::AssetId;
+
+/// Uploaded file issues can attach or show as inline image via `cid:<id>`.
+pub struct Asset {
+    pub id: AssetId,
+    pub filename: String,
+    pub content_type: String,
+    pub content: Vec<u8>,
+}
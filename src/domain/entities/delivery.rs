@@ -0,0 +1,33 @@
+use crate::domain::value_objects::{DeliveryStatus, IssueId, SubscriberEmail, SubscriberId};
+use chrono::{DateTime, Utc};
+
+/// Latest delivery attempt of an issue to a subscriber.
+pub struct Delivery {
+    pub issue_id: IssueId,
+    pub subscriber_id: SubscriberId,
+    pub email: SubscriberEmail,
+    pub status: DeliveryStatus,
+    pub provider_message_id: Option<String>,
+    pub error: Option<String>,
+    pub attempts: i32,
+    pub queued_at: DateTime<Utc>,
+    pub sent_at: Option<DateTime<Utc>>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Result of a finished attempt.
+pub struct DeliveryUpdate {
+    pub subscriber_id: SubscriberId,
+    pub status: DeliveryStatus,
+    pub provider_message_id: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Deliveries of an issue by status.
+pub struct DeliverySummary {
+    pub queued: i64,
+    pub sent: i64,
+    pub failed: i64,
+    pub bounced: i64,
+    pub suppressed: i64,
+}
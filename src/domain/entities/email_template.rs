@@ -0,0 +1,11 @@
+use crate::domain::value_objects::{Locale, TemplateKind, TemplateName};
+
+#[derive(Clone)]
+pub struct EmailTemplate {
+    pub name: TemplateName,
+    pub locale: Locale,
+    pub kind: TemplateKind,
+    pub subject: String,
+    pub html_body: String,
+    pub text_body: String,
+}
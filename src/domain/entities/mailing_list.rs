@@ -0,0 +1,13 @@
+use crate::domain::value_objects::{ConfirmationStatus, ListId, ListSlug};
+
+pub struct MailingList {
+    pub id: ListId,
+    pub slug: ListSlug,
+    pub name: String,
+}
+
+/// A list as seen by one subscriber, `status` is `None` for non-members.
+pub struct ListMembership {
+    pub list: MailingList,
+    pub status: Option<ConfirmationStatus>,
+}
@@ -0,0 +1,21 @@
+use crate::domain::value_objects::{AssetId, IssueId};
+
+/// Issue content as authored, after HTML processing and before per recipient rendering.
+pub struct NewsletterIssue {
+    pub id: IssueId,
+    pub title: String,
+    pub html_content: String,
+    pub text_content: String,
+    pub tracking_enabled: bool,
+    pub attachments: Vec<AssetId>,
+    /// Assets shown in the HTML as `<img src="cid:<asset id>">`
+    pub inline_images: Vec<AssetId>,
+}
+
+/// Tracked opens and clicks of an issue.
+pub struct IssueStats {
+    pub opens: i64,
+    pub unique_opens: i64,
+    pub clicks: i64,
+    pub unique_clicks: i64,
+}
@@ -0,0 +1,14 @@
+use crate::domain::value_objects::{OutboxEmailId, SubscriberEmail};
+
+/// Email written in the same transaction as the change it belongs to, sent afterwards by the
+/// outbox relay.
+pub struct OutboxEmail {
+    pub id: OutboxEmailId,
+    pub recipient: SubscriberEmail,
+    pub subject: String,
+    pub html_content: String,
+    pub text_content: String,
+    pub tag: Option<String>,
+    /// Send attempts so far, including the current one for claimed emails
+    pub attempts: i32,
+}
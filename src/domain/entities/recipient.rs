@@ -0,0 +1,15 @@
+use crate::domain::value_objects::{
+    ContentFormat, Locale, SubscriberEmail, SubscriberId, SubscriberName,
+};
+
+/// Confirmed subscriber selected for a send.
+pub struct Recipient {
+    pub id: SubscriberId,
+    pub email: SubscriberEmail,
+    pub name: SubscriberName,
+    pub content_format: ContentFormat,
+    pub preference_token: String,
+    pub attributes: serde_json::Value,
+    pub locale: Option<Locale>,
+    pub tracking_opt_out: bool,
+}
@@ -0,0 +1,7 @@
+use crate::domain::value_objects::{SegmentFilter, SegmentId};
+
+pub struct Segment {
+    pub id: SegmentId,
+    pub name: String,
+    pub filter: SegmentFilter,
+}
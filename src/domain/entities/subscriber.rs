@@ -1,7 +1,21 @@
-use crate::domain::value_objects::{SubscriberEmail, SubscriberId, SubscriberName};
+use crate::domain::value_objects::{
+    ConfirmationStatus, ConsentConfirmation, ContentFormat, Locale, SubscriberEmail, SubscriberId,
+    SubscriberName, SubscriptionSource, Tag,
+};
+use chrono::{DateTime, Utc};
 
 pub struct Subscriber {
     pub id: SubscriberId,
     pub email: SubscriberEmail,
     pub name: SubscriberName,
+    pub status: ConfirmationStatus,
+    pub subscribed_at: DateTime<Utc>,
+    pub source: SubscriptionSource,
+    pub consent_version: Option<String>,
+    pub confirmation: Option<ConsentConfirmation>,
+    pub attributes: serde_json::Value,
+    pub content_format: ContentFormat,
+    pub locale: Option<Locale>,
+    pub tracking_opt_out: bool,
+    pub tags: Vec<Tag>,
 }
@@ -0,0 +1,8 @@
+use crate::domain::value_objects::{SuppressionReason, SuppressionTarget};
+use chrono::{DateTime, Utc};
+
+pub struct Suppression {
+    pub target: SuppressionTarget,
+    pub reason: SuppressionReason,
+    pub created_at: DateTime<Utc>,
+}
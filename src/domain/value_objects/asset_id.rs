@@ -0,0 +1,28 @@
+use uuid::Uuid;
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub struct AssetId(Uuid);
+
+impl AssetId {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Default for AssetId {
+    fn default() -> Self {
+        Self(Uuid::now_v7())
+    }
+}
+
+impl From<Uuid> for AssetId {
+    fn from(value: Uuid) -> Self {
+        Self(value)
+    }
+}
+
+impl AsRef<Uuid> for AssetId {
+    fn as_ref(&self) -> &Uuid {
+        &self.0
+    }
+}
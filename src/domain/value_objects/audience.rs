@@ -0,0 +1,13 @@
+use crate::domain::value_objects::{ListId, SegmentFilter, Tag};
+
+/// Recipients of a send: confirmed members of any of the lists, narrowed down by the segment
+/// and tags.
+#[derive(Debug, Default)]
+pub struct Audience {
+    pub list_ids: Vec<ListId>,
+    pub segment: Option<SegmentFilter>,
+    /// Subscriber must have all of these tags, ignored when empty
+    pub include_tags: Vec<Tag>,
+    /// Subscriber must have none of these tags
+    pub exclude_tags: Vec<Tag>,
+}
@@ -0,0 +1,9 @@
+use chrono::{DateTime, Utc};
+
+/// Evidence of when and from where a subscriber confirmed their subscription.
+#[derive(Debug, Eq, PartialEq)]
+pub struct ConsentConfirmation {
+    pub confirmed_at: DateTime<Utc>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
@@ -0,0 +1,10 @@
+use strum_macros::{AsRefStr, EnumString};
+
+/// Which part of a multipart email the subscriber wants to receive.
+#[derive(Debug, Default, AsRefStr, EnumString, Eq, PartialEq, Clone, Copy)]
+#[strum(ascii_case_insensitive)]
+pub enum ContentFormat {
+    #[default]
+    Html,
+    PlainText,
+}
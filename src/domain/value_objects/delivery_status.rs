@@ -0,0 +1,13 @@
+use strum_macros::{AsRefStr, EnumString};
+
+#[derive(Debug, AsRefStr, EnumString, Eq, PartialEq, Clone, Copy)]
+#[strum(ascii_case_insensitive)]
+pub enum DeliveryStatus {
+    Queued,
+    Sent,
+    Failed,
+    /// Sent, then reported as bounced by the provider.
+    Bounced,
+    /// Skipped because the address or its domain is suppressed.
+    Suppressed,
+}
@@ -1,7 +1,13 @@
-use strum_macros::AsRefStr;
+use strum_macros::{AsRefStr, EnumString};
 
-#[derive(Debug, AsRefStr, Eq, PartialEq)]
+#[derive(Debug, AsRefStr, EnumString, Eq, PartialEq)]
+#[strum(ascii_case_insensitive)]
 pub enum ConfirmationStatus {
     PendingConfirmation,
     Confirmed,
+    Unsubscribed,
+    /// The address hard bounced or soft bounced too many times.
+    Bounced,
+    /// The subscriber reported an email as spam.
+    Complained,
 }
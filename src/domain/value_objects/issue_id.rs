@@ -0,0 +1,28 @@
+use uuid::Uuid;
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub struct IssueId(Uuid);
+
+impl IssueId {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Default for IssueId {
+    fn default() -> Self {
+        Self(Uuid::now_v7())
+    }
+}
+
+impl From<Uuid> for IssueId {
+    fn from(value: Uuid) -> Self {
+        Self(value)
+    }
+}
+
+impl AsRef<Uuid> for IssueId {
+    fn as_ref(&self) -> &Uuid {
+        &self.0
+    }
+}
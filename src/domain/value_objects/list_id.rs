@@ -0,0 +1,28 @@
+use uuid::Uuid;
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub struct ListId(Uuid);
+
+impl ListId {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Default for ListId {
+    fn default() -> Self {
+        Self(Uuid::now_v7())
+    }
+}
+
+impl From<Uuid> for ListId {
+    fn from(value: Uuid) -> Self {
+        Self(value)
+    }
+}
+
+impl AsRef<Uuid> for ListId {
+    fn as_ref(&self) -> &Uuid {
+        &self.0
+    }
+}
@@ -0,0 +1,58 @@
+use crate::error::DomainError;
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct ListSlug(String);
+
+impl ListSlug {
+    pub const DEFAULT: &'static str = "default";
+
+    pub fn parse(s: String) -> Result<Self, DomainError> {
+        let is_valid_length = !s.is_empty() && s.len() <= 64;
+        let has_valid_chars = s
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+        let has_valid_edges = !s.starts_with('-') && !s.ends_with('-');
+
+        if !(is_valid_length && has_valid_chars && has_valid_edges) {
+            return Err(format!("{} is not valid list slug", s).into());
+        }
+        Ok(Self(s))
+    }
+}
+
+impl Default for ListSlug {
+    fn default() -> Self {
+        Self(Self::DEFAULT.to_owned())
+    }
+}
+
+impl AsRef<str> for ListSlug {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lowercase_slug_with_dashes_is_accepted() {
+        assert!(ListSlug::parse("rust-weekly-2024".to_string()).is_ok());
+    }
+
+    #[test]
+    fn empty_slug_is_rejected() {
+        assert!(ListSlug::parse("".to_string()).is_err());
+    }
+
+    #[test]
+    fn slug_with_uppercase_or_spaces_is_rejected() {
+        assert!(ListSlug::parse("Rust Weekly".to_string()).is_err());
+    }
+
+    #[test]
+    fn slug_with_leading_dash_is_rejected() {
+        assert!(ListSlug::parse("-weekly".to_string()).is_err());
+    }
+}
@@ -0,0 +1,95 @@
+use crate::error::DomainError;
+
+/// Language tag with an optional region, normalized to `de` or `pt-BR` form.
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+pub struct Locale(String);
+
+impl Locale {
+    pub fn parse(s: String) -> Result<Self, DomainError> {
+        let mut parts = s.trim().split(['-', '_']);
+        let language = parts.next().unwrap_or_default();
+        let region = parts.next();
+
+        let is_valid_language =
+            (2..=3).contains(&language.len()) && language.chars().all(|c| c.is_ascii_alphabetic());
+        let is_valid_region =
+            region.is_none_or(|r| r.len() == 2 && r.chars().all(|c| c.is_ascii_alphabetic()));
+        if !(is_valid_language && is_valid_region && parts.next().is_none()) {
+            return Err(format!("{} is not valid locale", s).into());
+        }
+
+        let mut locale = language.to_ascii_lowercase();
+        if let Some(region) = region {
+            locale.push('-');
+            locale.push_str(&region.to_ascii_uppercase());
+        }
+        Ok(Self(locale))
+    }
+
+    /// Picks the most preferred valid locale of an `Accept-Language` header.
+    pub fn from_accept_language(header: &str) -> Option<Self> {
+        header
+            .split(',')
+            .filter_map(|entry| {
+                let mut params = entry.split(';');
+                let locale = Self::parse(params.next()?.to_owned()).ok()?;
+                let quality = params
+                    .find_map(|p| p.trim().strip_prefix("q="))
+                    .map_or(Some(1.0), |q| q.parse::<f32>().ok())?;
+                (quality > 0.0).then_some((locale, quality))
+            })
+            .fold(
+                None,
+                |best: Option<(Self, f32)>, (locale, quality)| match best {
+                    Some((_, best_quality)) if best_quality >= quality => best,
+                    _ => Some((locale, quality)),
+                },
+            )
+            .map(|(locale, _)| locale)
+    }
+
+    /// The locale without region, e.g. `pt` for `pt-BR`.
+    pub fn language(&self) -> Self {
+        Self(self.0.split('-').next().unwrap_or_default().to_owned())
+    }
+}
+
+impl AsRef<str> for Locale {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Result<Locale, DomainError> {
+        Locale::parse(s.to_string())
+    }
+
+    #[test]
+    fn locale_is_normalized() {
+        assert_eq!(parse("pt_br").unwrap().as_ref(), "pt-BR");
+        assert_eq!(parse("DE").unwrap().as_ref(), "de");
+    }
+
+    #[test]
+    fn invalid_locale_is_rejected() {
+        assert!(parse("").is_err());
+        assert!(parse("english").is_err());
+        assert!(parse("en-US-x").is_err());
+    }
+
+    #[test]
+    fn most_preferred_accept_language_is_picked() {
+        let locale = Locale::from_accept_language("en;q=0.5, de-AT;q=0.9, *;q=0.1, fr;q=0");
+
+        assert_eq!(locale.unwrap().as_ref(), "de-AT");
+    }
+
+    #[test]
+    fn language_drops_region() {
+        assert_eq!(parse("pt-BR").unwrap().language().as_ref(), "pt");
+    }
+}
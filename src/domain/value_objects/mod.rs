@@ -1,11 +1,49 @@
+mod asset_id;
+mod audience;
+mod consent_confirmation;
+mod content_format;
+mod delivery_status;
 mod email_status;
+mod issue_id;
+mod list_id;
+mod list_slug;
+mod locale;
+mod outbox_email_id;
 mod password_hash;
+mod segment_filter;
+mod segment_id;
 mod subscriber_email;
 mod subscriber_id;
 mod subscriber_name;
+mod subscription_source;
+mod suppression_reason;
+mod suppression_target;
+mod tag;
+mod template_kind;
+mod template_name;
+mod tracking_event_kind;
 
+pub use asset_id::*;
+pub use audience::*;
+pub use consent_confirmation::*;
+pub use content_format::*;
+pub use delivery_status::*;
 pub use email_status::*;
+pub use issue_id::*;
+pub use list_id::*;
+pub use list_slug::*;
+pub use locale::*;
+pub use outbox_email_id::*;
 pub use password_hash::*;
+pub use segment_filter::*;
+pub use segment_id::*;
 pub use subscriber_email::*;
 pub use subscriber_id::*;
 pub use subscriber_name::*;
+pub use subscription_source::*;
+pub use suppression_reason::*;
+pub use suppression_target::*;
+pub use tag::*;
+pub use template_kind::*;
+pub use template_name::*;
+pub use tracking_event_kind::*;
@@ -0,0 +1,28 @@
+use uuid::Uuid;
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub struct OutboxEmailId(Uuid);
+
+impl OutboxEmailId {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Default for OutboxEmailId {
+    fn default() -> Self {
+        Self(Uuid::now_v7())
+    }
+}
+
+impl From<Uuid> for OutboxEmailId {
+    fn from(value: Uuid) -> Self {
+        Self(value)
+    }
+}
+
+impl AsRef<Uuid> for OutboxEmailId {
+    fn as_ref(&self) -> &Uuid {
+        &self.0
+    }
+}
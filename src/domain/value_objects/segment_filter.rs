@@ -0,0 +1,291 @@
+use crate::error::DomainError;
+use chrono::NaiveDate;
+
+/// Parsed segment filter, e.g. `attr.locale = "de" and attr.age >= 18 and joined_after 2024-03-01`.
+///
+/// Conditions are joined with `and`. Supported conditions:
+/// - `attr.<key> <op> <value>` where `<op>` is one of `=`, `!=`, `>`, `>=`, `<`, `<=`;
+///   ranges are only allowed for numbers
+/// - `tag = "<tag>"` and `tag != "<tag>"`
+/// - `joined_before <date>` and `joined_after <date>` with `YYYY-MM-DD` dates
+#[derive(Debug, Clone, PartialEq)]
+pub struct SegmentFilter {
+    source: String,
+    conditions: Vec<SegmentCondition>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SegmentCondition {
+    Attribute {
+        key: String,
+        operator: ComparisonOperator,
+        value: SegmentValue,
+    },
+    HasTag(String),
+    LacksTag(String),
+    JoinedBefore(NaiveDate),
+    JoinedAfter(NaiveDate),
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ComparisonOperator {
+    Eq,
+    NotEq,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SegmentValue {
+    Text(String),
+    Number(f64),
+    Bool(bool),
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Text(String),
+    Operator(ComparisonOperator),
+}
+
+impl SegmentFilter {
+    pub fn parse(s: String) -> Result<Self, DomainError> {
+        let tokens = tokenize(&s)?;
+        let mut tokens = tokens.into_iter().peekable();
+        let mut conditions = vec![];
+
+        loop {
+            conditions.push(parse_condition(&mut tokens)?);
+            match tokens.next() {
+                None => break,
+                Some(Token::Word(w)) if w.eq_ignore_ascii_case("and") => continue,
+                Some(t) => return Err(format!("Expected 'and' but found {:?}", t).into()),
+            }
+        }
+
+        Ok(Self {
+            source: s,
+            conditions,
+        })
+    }
+
+    pub fn conditions(&self) -> &[SegmentCondition] {
+        &self.conditions
+    }
+}
+
+impl AsRef<str> for SegmentFilter {
+    fn as_ref(&self) -> &str {
+        &self.source
+    }
+}
+
+fn parse_condition(
+    tokens: &mut impl Iterator<Item = Token>,
+) -> Result<SegmentCondition, DomainError> {
+    let field = match tokens.next() {
+        Some(Token::Word(w)) => w,
+        Some(t) => return Err(format!("Expected field name but found {:?}", t).into()),
+        None => return Err("Expected condition but filter ended".into()),
+    };
+
+    match field.as_str() {
+        "joined_before" => Ok(SegmentCondition::JoinedBefore(parse_date(tokens.next())?)),
+        "joined_after" => Ok(SegmentCondition::JoinedAfter(parse_date(tokens.next())?)),
+        "tag" => {
+            let operator = parse_operator(tokens.next())?;
+            let tag = match parse_value(tokens.next())? {
+                SegmentValue::Text(tag) => tag,
+                v => return Err(format!("Tag must be a string, got {:?}", v).into()),
+            };
+            match operator {
+                ComparisonOperator::Eq => Ok(SegmentCondition::HasTag(tag)),
+                ComparisonOperator::NotEq => Ok(SegmentCondition::LacksTag(tag)),
+                _ => Err("Tags support only '=' and '!='".into()),
+            }
+        }
+        _ => {
+            let Some(key) = field.strip_prefix("attr.") else {
+                return Err(format!("Unknown field {}", field).into());
+            };
+            let is_valid_key = !key.is_empty()
+                && key
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+            if !is_valid_key {
+                return Err(format!("{} is not valid attribute name", key).into());
+            }
+
+            let operator = parse_operator(tokens.next())?;
+            let value = parse_value(tokens.next())?;
+            let is_equality =
+                matches!(operator, ComparisonOperator::Eq | ComparisonOperator::NotEq);
+            if !is_equality && !matches!(value, SegmentValue::Number(_)) {
+                return Err(format!("Range comparison of {} requires a number", key).into());
+            }
+
+            Ok(SegmentCondition::Attribute {
+                key: key.to_owned(),
+                operator,
+                value,
+            })
+        }
+    }
+}
+
+fn parse_operator(token: Option<Token>) -> Result<ComparisonOperator, DomainError> {
+    match token {
+        Some(Token::Operator(operator)) => Ok(operator),
+        t => Err(format!("Expected comparison operator but found {:?}", t).into()),
+    }
+}
+
+fn parse_value(token: Option<Token>) -> Result<SegmentValue, DomainError> {
+    match token {
+        Some(Token::Text(s)) => Ok(SegmentValue::Text(s)),
+        Some(Token::Word(w)) => match w.as_str() {
+            "true" => Ok(SegmentValue::Bool(true)),
+            "false" => Ok(SegmentValue::Bool(false)),
+            _ => w
+                .parse::<f64>()
+                .ok()
+                .filter(|n| n.is_finite())
+                .map(SegmentValue::Number)
+                .ok_or_else(|| format!("{} must be quoted", w).into()),
+        },
+        t => Err(format!("Expected value but found {:?}", t).into()),
+    }
+}
+
+fn parse_date(token: Option<Token>) -> Result<NaiveDate, DomainError> {
+    let raw = match token {
+        Some(Token::Word(w)) | Some(Token::Text(w)) => w,
+        t => return Err(format!("Expected date but found {:?}", t).into()),
+    };
+    NaiveDate::parse_from_str(&raw, "%Y-%m-%d")
+        .map_err(|_| format!("{} is not valid YYYY-MM-DD date", raw).into())
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, DomainError> {
+    let mut tokens = vec![];
+    let mut chars = s.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '"' => {
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(escaped) => text.push(escaped),
+                            None => return Err("Unterminated string in filter".into()),
+                        },
+                        Some(c) => text.push(c),
+                        None => return Err("Unterminated string in filter".into()),
+                    }
+                }
+                tokens.push(Token::Text(text));
+            }
+            '=' | '!' | '>' | '<' => {
+                chars.next();
+                let has_eq = chars.next_if_eq(&'=').is_some();
+                let operator = match (c, has_eq) {
+                    ('=', _) => ComparisonOperator::Eq,
+                    ('!', true) => ComparisonOperator::NotEq,
+                    ('>', false) => ComparisonOperator::Gt,
+                    ('>', true) => ComparisonOperator::Gte,
+                    ('<', false) => ComparisonOperator::Lt,
+                    ('<', true) => ComparisonOperator::Lte,
+                    _ => return Err("Unexpected '!' in filter".into()),
+                };
+                tokens.push(Token::Operator(operator));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace() && !"\"=!<>".contains(*c))
+                {
+                    word.push(c);
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+
+    if tokens.is_empty() {
+        return Err("Segment filter must not be empty".into());
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Result<SegmentFilter, DomainError> {
+        SegmentFilter::parse(s.to_string())
+    }
+
+    #[test]
+    fn combined_conditions_are_parsed() {
+        let filter = parse(
+            r#"attr.locale = "de" AND attr.age >= 18 and tag != "vip" and joined_after 2024-03-01"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            filter.conditions(),
+            &[
+                SegmentCondition::Attribute {
+                    key: "locale".into(),
+                    operator: ComparisonOperator::Eq,
+                    value: SegmentValue::Text("de".into()),
+                },
+                SegmentCondition::Attribute {
+                    key: "age".into(),
+                    operator: ComparisonOperator::Gte,
+                    value: SegmentValue::Number(18.0),
+                },
+                SegmentCondition::LacksTag("vip".into()),
+                SegmentCondition::JoinedAfter(NaiveDate::from_ymd_opt(2024, 3, 1).unwrap()),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_filter_is_rejected() {
+        assert!(parse("  ").is_err());
+    }
+
+    #[test]
+    fn unknown_field_is_rejected() {
+        assert!(parse(r#"locale = "de""#).is_err());
+    }
+
+    #[test]
+    fn range_on_text_is_rejected() {
+        assert!(parse(r#"attr.locale > "de""#).is_err());
+    }
+
+    #[test]
+    fn unquoted_text_is_rejected() {
+        assert!(parse("attr.locale = de").is_err());
+    }
+
+    #[test]
+    fn invalid_date_is_rejected() {
+        assert!(parse("joined_before 2024-13-01").is_err());
+    }
+
+    #[test]
+    fn missing_conjunction_is_rejected() {
+        assert!(parse(r#"tag = "a" tag = "b""#).is_err());
+    }
+}
@@ -0,0 +1,28 @@
+use uuid::Uuid;
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub struct SegmentId(Uuid);
+
+impl SegmentId {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Default for SegmentId {
+    fn default() -> Self {
+        Self(Uuid::now_v7())
+    }
+}
+
+impl From<Uuid> for SegmentId {
+    fn from(value: Uuid) -> Self {
+        Self(value)
+    }
+}
+
+impl AsRef<Uuid> for SegmentId {
+    fn as_ref(&self) -> &Uuid {
+        &self.0
+    }
+}
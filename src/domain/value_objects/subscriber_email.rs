@@ -1,6 +1,6 @@
 use crate::error::DomainError;
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Eq, PartialEq, Clone)]
 pub struct SubscriberEmail(String);
 
 impl SubscriberEmail {
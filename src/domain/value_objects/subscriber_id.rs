@@ -1,7 +1,7 @@
 use crate::error::DomainError;
 use uuid::Uuid;
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Eq, PartialEq, Clone)]
 pub struct SubscriberId(Uuid);
 
 impl SubscriberId {
@@ -21,6 +21,12 @@ impl Default for SubscriberId {
     }
 }
 
+impl From<Uuid> for SubscriberId {
+    fn from(value: Uuid) -> Self {
+        Self(value)
+    }
+}
+
 impl AsRef<Uuid> for SubscriberId {
     fn as_ref(&self) -> &Uuid {
         &self.0
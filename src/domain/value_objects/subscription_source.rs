@@ -0,0 +1,10 @@
+use strum_macros::{AsRefStr, EnumString};
+
+#[derive(Debug, Default, AsRefStr, EnumString, Eq, PartialEq, Clone, Copy)]
+#[strum(ascii_case_insensitive)]
+pub enum SubscriptionSource {
+    #[default]
+    Form,
+    Import,
+    Api,
+}
@@ -0,0 +1,23 @@
+use crate::domain::value_objects::ConfirmationStatus;
+use strum_macros::{AsRefStr, EnumString};
+
+#[derive(Debug, AsRefStr, EnumString, Eq, PartialEq, Clone, Copy)]
+#[strum(ascii_case_insensitive)]
+pub enum SuppressionReason {
+    Unsubscribed,
+    Bounced,
+    Complained,
+    /// Added by an administrator.
+    Manual,
+}
+
+impl SuppressionReason {
+    /// Status given to the matching subscriber.
+    pub fn subscriber_status(self) -> ConfirmationStatus {
+        match self {
+            Self::Unsubscribed | Self::Manual => ConfirmationStatus::Unsubscribed,
+            Self::Bounced => ConfirmationStatus::Bounced,
+            Self::Complained => ConfirmationStatus::Complained,
+        }
+    }
+}
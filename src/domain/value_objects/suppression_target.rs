@@ -0,0 +1,80 @@
+use crate::domain::value_objects::SubscriberEmail;
+use crate::error::DomainError;
+
+/// Suppressed email address or whole domain, stored lowercase.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct SuppressionTarget(String);
+
+impl SuppressionTarget {
+    /// Values containing `@` are addresses, anything else is a domain such as `example.com`.
+    pub fn parse(s: String) -> Result<Self, DomainError> {
+        let s = s.trim().to_lowercase();
+        if s.contains('@') {
+            return Ok(Self::from(&SubscriberEmail::parse(s)?));
+        }
+
+        let is_valid_domain = s.len() <= 253
+            && s.contains('.')
+            && s.split('.').all(|label| {
+                !label.is_empty()
+                    && label.len() <= 63
+                    && !label.starts_with('-')
+                    && !label.ends_with('-')
+                    && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+            });
+        if !is_valid_domain {
+            return Err(format!("{} is not valid email address or domain", s).into());
+        }
+        Ok(Self(s))
+    }
+
+    pub fn is_domain(&self) -> bool {
+        !self.0.contains('@')
+    }
+}
+
+impl From<&SubscriberEmail> for SuppressionTarget {
+    fn from(email: &SubscriberEmail) -> Self {
+        Self(email.as_ref().to_lowercase())
+    }
+}
+
+impl AsRef<str> for SuppressionTarget {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addresses_and_domains_are_normalized() {
+        let address = SuppressionTarget::parse("Ursula@Earthsea.com".to_string()).unwrap();
+        let domain = SuppressionTarget::parse(" Mail.Earthsea.com ".to_string()).unwrap();
+
+        assert_eq!(address.as_ref(), "ursula@earthsea.com");
+        assert!(!address.is_domain());
+        assert_eq!(domain.as_ref(), "mail.earthsea.com");
+        assert!(domain.is_domain());
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        for value in [
+            "",
+            "localhost",
+            "-bad.com",
+            "bad..com",
+            "ursula@",
+            "a b.com",
+        ] {
+            assert!(
+                SuppressionTarget::parse(value.to_string()).is_err(),
+                "{} was accepted",
+                value
+            );
+        }
+    }
+}
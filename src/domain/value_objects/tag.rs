@@ -0,0 +1,49 @@
+use crate::error::DomainError;
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Tag(String);
+
+impl Tag {
+    pub fn parse(s: String) -> Result<Self, DomainError> {
+        let is_valid_length = !s.is_empty() && s.len() <= 64;
+        let has_valid_chars = s
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_' || c == ':');
+
+        if !(is_valid_length && has_valid_chars) {
+            return Err(format!("{} is not valid tag", s).into());
+        }
+        Ok(Self(s))
+    }
+}
+
+impl AsRef<str> for Tag {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tag_with_namespace_is_accepted() {
+        assert!(Tag::parse("source:conference-2024".to_string()).is_ok());
+    }
+
+    #[test]
+    fn empty_tag_is_rejected() {
+        assert!(Tag::parse("".to_string()).is_err());
+    }
+
+    #[test]
+    fn tag_with_whitespace_is_rejected() {
+        assert!(Tag::parse("early adopter".to_string()).is_err());
+    }
+
+    #[test]
+    fn too_long_tag_is_rejected() {
+        assert!(Tag::parse("a".repeat(65)).is_err());
+    }
+}
@@ -0,0 +1,12 @@
+use strum_macros::{AsRefStr, EnumString};
+
+#[derive(Debug, AsRefStr, EnumString, Eq, PartialEq, Clone, Copy)]
+#[strum(ascii_case_insensitive)]
+pub enum TemplateKind {
+    /// Wraps emails through `{% extends %}`
+    Layout,
+    /// Reused through `{% include %}`
+    Partial,
+    /// Sendable email with a subject
+    Email,
+}
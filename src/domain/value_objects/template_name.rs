@@ -0,0 +1,24 @@
+use crate::error::DomainError;
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct TemplateName(String);
+
+impl TemplateName {
+    pub fn parse(s: String) -> Result<Self, DomainError> {
+        let is_valid_length = !s.is_empty() && s.len() <= 64;
+        let has_valid_chars = s
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-');
+
+        if !(is_valid_length && has_valid_chars) {
+            return Err(format!("{} is not valid template name", s).into());
+        }
+        Ok(Self(s))
+    }
+}
+
+impl AsRef<str> for TemplateName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
@@ -0,0 +1,8 @@
+use strum_macros::{AsRefStr, EnumString};
+
+#[derive(Debug, AsRefStr, EnumString, Eq, PartialEq, Clone, Copy)]
+#[strum(ascii_case_insensitive)]
+pub enum TrackingEventKind {
+    Open,
+    Click,
+}
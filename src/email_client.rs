@@ -1,60 +1,736 @@
+use crate::app_config::DkimConfig;
 use crate::domain::value_objects::SubscriberEmail;
-use serde::Serialize;
+use crate::email_providers::{EmailProviders, MimeEmail, ProviderHealth};
+use crate::infrastructure::sqlx_postgres_repository::SqlxPostgresRepository;
+use crate::rate_limiter::SendRateLimiter;
+use anyhow::{anyhow, bail, Context};
+use data_encoding::BASE64;
+use mail_auth::common::crypto::{RsaKey, Sha256};
+use mail_auth::common::headers::HeaderWriter;
+use mail_auth::dkim::{Canonicalization, Done};
+use mail_builder::headers::address::Address;
+use mail_builder::headers::raw::Raw;
+use mail_builder::mime::MimePart;
+use mail_builder::MessageBuilder;
+use reqwest::header::{HeaderName, HeaderValue};
+use rustls_pki_types::pem::PemObject;
+use rustls_pki_types::PrivateKeyDer;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
 use tracing::info;
+use uuid::Uuid;
+
+/// Provider limit for the attachments of one email.
+pub const MAX_ATTACHMENTS_SIZE: usize = 10 * 1024 * 1024;
+/// Headers the provider sets from the message fields, they can't be added as custom headers.
+const RESERVED_HEADERS: [&str; 9] = [
+    "from",
+    "to",
+    "cc",
+    "bcc",
+    "reply-to",
+    "subject",
+    "content-type",
+    "content-transfer-encoding",
+    "mime-version",
+];
+/// Headers signed when present, so they can't be altered in transit.
+const DKIM_SIGNED_HEADERS: [&str; 9] = [
+    "from",
+    "to",
+    "reply-to",
+    "subject",
+    "date",
+    "message-id",
+    "mime-version",
+    "content-type",
+    "list-id",
+];
 
 #[derive(Debug)]
 pub struct EmailClient {
-    http_client: reqwest::Client,
-    url: String,
-    authorization_token: String,
+    providers: EmailProviders,
     sender_email: SubscriberEmail,
+    sender_name: String,
+    repository: SqlxPostgresRepository,
+    rate_limiter: SendRateLimiter,
+    dkim: Option<DkimSigner>,
 }
 
 impl EmailClient {
     pub fn new(
-        base_address: String,
-        authorization_token: String,
+        providers: EmailProviders,
         sender_email: SubscriberEmail,
+        sender_name: String,
+        repository: SqlxPostgresRepository,
+        rate_limiter: SendRateLimiter,
+        dkim: Option<DkimSigner>,
     ) -> Self {
-        let url = format!("{}/email", base_address);
         Self {
-            http_client: reqwest::Client::new(),
-            url,
-            authorization_token,
+            providers,
             sender_email,
+            sender_name,
+            repository,
+            rate_limiter,
+            dkim,
         }
     }
-    pub async fn send(
+
+    /// Largest batch the provider accepts in one request.
+    pub const MAX_BATCH_SIZE: usize = 500;
+
+    /// Sends the email unless the recipient address or domain is suppressed, suppressed
+    /// recipients are skipped silently. Waits for the send rate limit first, then fails over
+    /// across the configured providers.
+    pub async fn send(&self, message: &EmailMessage) -> Result<SendOutcome, anyhow::Error> {
+        let request_body = self.request_body(message)?;
+        if !self.prepare(&message.recipient).await? {
+            return Ok(SendOutcome::Suppressed);
+        }
+
+        let response = self
+            .providers
+            .send("/email", &request_body, || {
+                Ok(vec![self.mime_email(message)?])
+            })
+            .await?;
+        Ok(SendOutcome::Sent {
+            message_id: response
+                .and_then(|x| serde_json::from_str::<SendResponse>(&x).ok())
+                .and_then(|x| x.message_id),
+        })
+    }
+
+    /// Sends up to [`Self::MAX_BATCH_SIZE`] emails in one request, suppressed and rate limited
+    /// like [`Self::send`]. Returns one result per email in the given order, emails the
+    /// provider rejected or didn't report on are failed.
+    pub async fn send_batch(
         &self,
-        recipient: &SubscriberEmail,
-        subject: &str,
-        html_content: &str,
-        text_content: &str,
-    ) -> Result<(), anyhow::Error> {
-        let request_body = RequestBody {
-            to: recipient.as_ref().to_owned(),
-            from: self.sender_email.as_ref().to_owned(),
-            text_content: text_content.to_string(),
-            html_content: html_content.to_string(),
-            subject: subject.to_string(),
+        messages: &[EmailMessage],
+    ) -> Vec<Result<SendOutcome, anyhow::Error>> {
+        let mut results = Vec::with_capacity(messages.len());
+        let mut request_body = vec![];
+        let mut pending = vec![];
+        for (i, message) in messages.iter().enumerate() {
+            let outcome = match self.request_body(message) {
+                Ok(body) => match self.prepare(&message.recipient).await {
+                    Ok(true) => {
+                        request_body.push(body);
+                        pending.push(i);
+                        Ok(SendOutcome::Sent { message_id: None })
+                    }
+                    Ok(false) => Ok(SendOutcome::Suppressed),
+                    Err(error) => Err(error),
+                },
+                Err(error) => Err(error),
+            };
+            results.push(outcome);
+        }
+        if request_body.is_empty() {
+            return results;
+        }
+
+        let mime = || {
+            pending
+                .iter()
+                .map(|&i| self.mime_email(&messages[i]))
+                .collect::<Result<Vec<_>, _>>()
         };
+        match self
+            .providers
+            .send("/email/batch", &request_body, mime)
+            .await
+            .and_then(|response| batch_outcomes(response.as_deref(), pending.len()))
+        {
+            Ok(outcomes) => {
+                for (outcome, i) in outcomes.into_iter().zip(pending) {
+                    results[i] = outcome;
+                }
+            }
+            Err(error) => {
+                let error = format!("{:#}", error);
+                for i in pending {
+                    results[i] = Err(anyhow!(error.clone()));
+                }
+            }
+        }
+        results
+    }
+
+    /// Circuit breaker state of every provider, in priority order.
+    pub fn provider_health(&self) -> Vec<ProviderHealth> {
+        self.providers.health()
+    }
+
+    /// Renders the message as MIME for SMTP providers, DKIM signed when a signer is configured.
+    fn mime_email(&self, message: &EmailMessage) -> Result<MimeEmail, anyhow::Error> {
+        let mime = mime_message(message, &self.sender_name, &self.sender_email)?;
+        Ok(MimeEmail {
+            sender: self.sender_email.as_ref().to_owned(),
+            recipient: message.recipient.as_ref().to_owned(),
+            message: match &self.dkim {
+                Some(signer) => signer.sign(&mime)?,
+                None => mime,
+            },
+        })
+    }
+
+    /// Returns `false` for suppressed recipients, otherwise waits for the send rate limit and
+    /// fails when the wait is too long.
+    async fn prepare(&self, recipient: &SubscriberEmail) -> Result<bool, anyhow::Error> {
+        if self
+            .repository
+            .is_suppressed(recipient)
+            .await
+            .map_err(|e| anyhow!(e))?
+        {
+            info!("Email to suppressed recipient skipped");
+            return Ok(false);
+        }
+        self.rate_limiter.acquire(recipient).await?;
+        Ok(true)
+    }
+
+    fn request_body(&self, message: &EmailMessage) -> Result<RequestBody, anyhow::Error> {
+        let size = message
+            .attachments
+            .iter()
+            .map(|x| x.content.len())
+            .sum::<usize>();
+        if size > MAX_ATTACHMENTS_SIZE {
+            bail!(
+                "Attachments of {} bytes exceed the limit of {} bytes",
+                size,
+                MAX_ATTACHMENTS_SIZE
+            );
+        }
+
+        check_headers(&message.headers)?;
+
+        Ok(RequestBody {
+            to: message.recipient.as_ref().to_owned(),
+            from: mailbox(&self.sender_name, &self.sender_email),
+            reply_to: message.reply_to.as_ref().map(|x| x.as_ref().to_owned()),
+            text_content: message.text_content.clone(),
+            html_content: message.html_content.clone(),
+            subject: message.subject.clone(),
+            headers: message
+                .headers
+                .iter()
+                .map(|(name, value)| HeaderBody {
+                    name: name.clone(),
+                    value: value.clone(),
+                })
+                .collect(),
+            tag: message.tag.clone(),
+            metadata: message.metadata.clone(),
+            attachments: message
+                .attachments
+                .iter()
+                .map(|x| AttachmentBody {
+                    name: x.filename.clone(),
+                    content: BASE64.encode(&x.content),
+                    content_type: x.content_type.clone(),
+                    content_id: x.content_id.as_ref().map(|id| format!("cid:{}", id)),
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Outcome of an email the provider didn't reject.
+#[derive(Debug)]
+pub enum SendOutcome {
+    /// Accepted by the provider, with its message id when one was returned
+    Sent { message_id: Option<String> },
+    /// Skipped because the address or its domain is suppressed
+    Suppressed,
+}
+
+/// Email to one recipient, e.g.
+/// `EmailMessage::new(recipient, subject, text).html(html).tag("confirmation")`.
+#[derive(Clone)]
+pub struct EmailMessage {
+    recipient: SubscriberEmail,
+    subject: String,
+    text_content: String,
+    html_content: String,
+    reply_to: Option<SubscriberEmail>,
+    headers: Vec<(String, String)>,
+    tag: Option<String>,
+    metadata: BTreeMap<String, String>,
+    attachments: Vec<Attachment>,
+}
 
-        info!("Email sent: {:?}", request_body);
-        // self.http_client
-        //     .post(&self.url)
-        //     .header("X-Postmark-Server-Token", &self.authorization_token)
-        //     .json(&request_body)
-        //     .send()
-        //     .await?;
-        Ok(())
+impl EmailMessage {
+    /// Plain-text email, unless an HTML part is added.
+    pub fn new(
+        recipient: SubscriberEmail,
+        subject: impl Into<String>,
+        text_content: impl Into<String>,
+    ) -> Self {
+        Self {
+            recipient,
+            subject: subject.into(),
+            text_content: text_content.into(),
+            html_content: String::new(),
+            reply_to: None,
+            headers: vec![],
+            tag: None,
+            metadata: BTreeMap::new(),
+            attachments: vec![],
+        }
+    }
+
+    /// Empty HTML keeps the email plain-text only.
+    pub fn html(mut self, html_content: impl Into<String>) -> Self {
+        self.html_content = html_content.into();
+        self
+    }
+
+    pub fn reply_to(mut self, reply_to: SubscriberEmail) -> Self {
+        self.reply_to = Some(reply_to);
+        self
+    }
+
+    /// Custom header such as `List-Id`. Invalid and reserved headers fail the send.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Provider tag to group emails in its statistics, e.g. `newsletter`.
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tag = Some(tag.into());
+        self
+    }
+
+    /// Provider metadata, returned with the events of the email.
+    pub fn metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn attachments(mut self, attachments: impl IntoIterator<Item = Attachment>) -> Self {
+        self.attachments.extend(attachments);
+        self
+    }
+
+    pub fn recipient(&self) -> &SubscriberEmail {
+        &self.recipient
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct Attachment {
+    pub filename: String,
+    pub content_type: String,
+    /// Shared, as the same attachment is usually sent to many recipients
+    pub content: Arc<[u8]>,
+    /// Set for inline images, which the HTML shows as `<img src="cid:<content id>">`
+    pub content_id: Option<String>,
+}
+
 #[derive(Serialize, Debug)]
 struct RequestBody {
     from: String,
     to: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reply_to: Option<String>,
     subject: String,
+    /// Empty for plain-text only emails
+    #[serde(skip_serializing_if = "String::is_empty")]
     html_content: String,
     text_content: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    headers: Vec<HeaderBody>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tag: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    metadata: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    attachments: Vec<AttachmentBody>,
+}
+
+#[derive(Deserialize)]
+struct SendResponse {
+    #[serde(rename = "MessageID")]
+    message_id: Option<String>,
+    /// Non-zero when the provider rejected the email
+    #[serde(rename = "ErrorCode", default)]
+    error_code: i64,
+    #[serde(rename = "Message", default)]
+    message: String,
+}
+
+/// Matches the batch response entries to the emails by position, emails without an entry
+/// are failed. No response means the provider accepted every email.
+fn batch_outcomes(
+    response: Option<&str>,
+    count: usize,
+) -> Result<Vec<Result<SendOutcome, anyhow::Error>>, anyhow::Error> {
+    let Some(response) = response else {
+        return Ok((0..count)
+            .map(|_| Ok(SendOutcome::Sent { message_id: None }))
+            .collect());
+    };
+    let mut responses = serde_json::from_str::<Vec<SendResponse>>(response)
+        .context("Batch response can't be parsed")?
+        .into_iter();
+
+    Ok((0..count)
+        .map(|_| match responses.next() {
+            Some(response) if response.error_code == 0 => Ok(SendOutcome::Sent {
+                message_id: response.message_id,
+            }),
+            Some(response) => Err(anyhow!(
+                "Rejected with error code {}: {}",
+                response.error_code,
+                response.message
+            )),
+            None => Err(anyhow!("Batch response has no entry for the email")),
+        })
+        .collect())
+}
+
+#[derive(Serialize, Debug)]
+struct HeaderBody {
+    name: String,
+    value: String,
+}
+
+#[derive(Serialize)]
+struct AttachmentBody {
+    name: String,
+    /// Base64 encoded
+    content: String,
+    content_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_id: Option<String>,
+}
+
+/// Leaves out the content, which would flood the logs.
+impl Debug for AttachmentBody {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AttachmentBody")
+            .field("name", &self.name)
+            .field("content_type", &self.content_type)
+            .field("content_id", &self.content_id)
+            .field("encoded_size", &self.content.len())
+            .finish()
+    }
+}
+
+/// Signs MIME messages with `rsa-sha256` and `relaxed/relaxed` canonicalization (RFC 6376).
+pub struct DkimSigner(mail_auth::dkim::DkimSigner<RsaKey<Sha256>, Done>);
+
+impl DkimSigner {
+    pub fn new(
+        selector: &str,
+        domain: &str,
+        key: PrivateKeyDer<'_>,
+    ) -> Result<Self, anyhow::Error> {
+        let key = RsaKey::<Sha256>::from_key_der(key)
+            .map_err(|e| anyhow!("DKIM key is not valid RSA key: {}", e))?;
+        Ok(Self(
+            mail_auth::dkim::DkimSigner::from_key(key)
+                .domain(domain)
+                .selector(selector)
+                .headers(DKIM_SIGNED_HEADERS)
+                .header_canonicalization(Canonicalization::Relaxed)
+                .body_canonicalization(Canonicalization::Relaxed),
+        ))
+    }
+
+    pub fn load(config: &DkimConfig) -> Result<Self, anyhow::Error> {
+        let key = PrivateKeyDer::from_pem_file(&config.private_key_path)
+            .with_context(|| format!("DKIM key {} can't be read", config.private_key_path))?;
+        Self::new(&config.selector, &config.domain, key)
+            .with_context(|| format!("DKIM key {} can't be loaded", config.private_key_path))
+    }
+
+    /// Returns the message with a `DKIM-Signature` header prepended.
+    pub fn sign(&self, message: &[u8]) -> Result<Vec<u8>, anyhow::Error> {
+        let signature = self
+            .0
+            .sign(message)
+            .map_err(|e| anyhow!("Message can't be DKIM signed: {}", e))?;
+        Ok([signature.to_header().as_bytes(), message].concat())
+    }
+}
+
+/// Leaves out the private key.
+impl Debug for DkimSigner {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DkimSigner")
+            .field("selector", &self.0.template.s)
+            .field("domain", &self.0.template.d)
+            .finish()
+    }
+}
+
+fn mime_message(
+    message: &EmailMessage,
+    sender_name: &str,
+    sender_email: &SubscriberEmail,
+) -> Result<Vec<u8>, anyhow::Error> {
+    check_headers(&message.headers)?;
+    let sender_domain = sender_email.as_ref().rsplit('@').next().unwrap_or_default();
+
+    let mut builder = MessageBuilder::new()
+        .message_id(format!("{}@{}", Uuid::now_v7(), sender_domain))
+        .from(match sender_name.trim() {
+            "" => Address::from(sender_email.as_ref()),
+            name => Address::from((name, sender_email.as_ref())),
+        })
+        .to(message.recipient.as_ref())
+        .subject(message.subject.as_str());
+    if let Some(reply_to) = &message.reply_to {
+        builder = builder.reply_to(reply_to.as_ref());
+    }
+    for (name, value) in &message.headers {
+        builder = builder.header(name.as_str(), Raw::new(value.as_str()));
+    }
+
+    // Inline images go next to the HTML they're shown in: mixed(alternative(text,
+    // related(html, images)), attachments)
+    let (inline, attachments): (Vec<_>, Vec<_>) = message
+        .attachments
+        .iter()
+        .partition(|x| x.content_id.is_some() && !message.html_content.is_empty());
+    let mut body = MimePart::new("text/plain", message.text_content.as_str());
+    if !message.html_content.is_empty() {
+        let mut html = MimePart::new("text/html", message.html_content.as_str());
+        if !inline.is_empty() {
+            let images = inline.iter().map(|x| {
+                MimePart::new(x.content_type.as_str(), &*x.content)
+                    .inline()
+                    .cid(x.content_id.as_deref().unwrap_or_default())
+            });
+            html = MimePart::new(
+                "multipart/related",
+                std::iter::once(html).chain(images).collect::<Vec<_>>(),
+            );
+        }
+        body = MimePart::new("multipart/alternative", vec![body, html]);
+    }
+    if !attachments.is_empty() {
+        let attachments = attachments.iter().map(|x| {
+            let part = MimePart::new(x.content_type.as_str(), &*x.content);
+            match &x.content_id {
+                Some(content_id) => part.inline().cid(content_id.as_str()),
+                None => part.attachment(x.filename.as_str()),
+            }
+        });
+        body = MimePart::new(
+            "multipart/mixed",
+            std::iter::once(body).chain(attachments).collect::<Vec<_>>(),
+        );
+    }
+    builder = builder.body(body);
+
+    Ok(builder.write_to_vec()?)
+}
+
+/// `"Name" <address>`, or the bare address without a name.
+fn mailbox(name: &str, email: &SubscriberEmail) -> String {
+    let name = name
+        .trim()
+        .chars()
+        .filter(|c| !c.is_control())
+        .collect::<String>();
+    if name.is_empty() {
+        return email.as_ref().to_owned();
+    }
+    format!(
+        "\"{}\" <{}>",
+        name.replace('\\', "\\\\").replace('"', "\\\""),
+        email.as_ref()
+    )
+}
+
+fn check_headers(headers: &[(String, String)]) -> Result<(), anyhow::Error> {
+    for (name, value) in headers {
+        let header = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| anyhow!("{} is not valid header name", name))?;
+        if RESERVED_HEADERS.contains(&header.as_str()) {
+            bail!("{} header can't be set directly", name);
+        }
+        HeaderValue::from_str(value)
+            .map_err(|_| anyhow!("{} is not valid value of the {} header", value, name))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email(s: &str) -> SubscriberEmail {
+        SubscriberEmail::parse(s.to_string()).unwrap()
+    }
+
+    fn header(name: &str, value: &str) -> Vec<(String, String)> {
+        vec![(name.to_string(), value.to_string())]
+    }
+
+    #[test]
+    fn sender_display_name_is_quoted() {
+        let sender = email("news@earthsea.com");
+
+        assert_eq!(mailbox("", &sender), "news@earthsea.com");
+        assert_eq!(
+            mailbox("Roke \"School\"", &sender),
+            r#""Roke \"School\"" <news@earthsea.com>"#
+        );
+    }
+
+    #[test]
+    fn batch_outcomes_follow_the_response_entries() {
+        let response = r#"[
+            { "ErrorCode": 0, "Message": "OK", "MessageID": "b7bc2f4a" },
+            { "ErrorCode": 406, "Message": "Inactive recipient" }
+        ]"#;
+
+        let outcomes = batch_outcomes(Some(response), 3).unwrap();
+
+        assert!(matches!(
+            &outcomes[0],
+            Ok(SendOutcome::Sent { message_id: Some(id) }) if id == "b7bc2f4a"
+        ));
+        assert!(outcomes[1]
+            .as_ref()
+            .is_err_and(|e| e.to_string().contains("Inactive recipient")));
+        assert!(outcomes[2].is_err());
+        assert!(batch_outcomes(Some("OK"), 1).is_err());
+        assert!(batch_outcomes(None, 2).unwrap().iter().all(|x| x.is_ok()));
+    }
+
+    fn dkim_key_pair() -> (DkimSigner, rsa::RsaPublicKey) {
+        use rsa::pkcs8::EncodePrivateKey;
+
+        let key = rsa::RsaPrivateKey::new(&mut rand::thread_rng(), 1024).unwrap();
+        let der = key.to_pkcs8_der().unwrap().as_bytes().to_vec();
+        let signer = DkimSigner::new("news", "earthsea.com", PrivateKeyDer::Pkcs8(der.into()));
+        (signer.unwrap(), key.to_public_key())
+    }
+
+    /// Checks the signature like a receiving server would, with the public key instead of the
+    /// one published in DNS.
+    async fn verify_dkim(
+        message: &[u8],
+        public_key: &rsa::RsaPublicKey,
+    ) -> Result<(), anyhow::Error> {
+        use mail_auth::common::parse::TxtRecordParser;
+        use mail_auth::common::verify::DomainKey;
+        use mail_auth::{
+            AuthenticatedMessage, DkimResult, MessageAuthenticator, Parameters, ResolverCache, Txt,
+        };
+        use rsa::pkcs8::EncodePublicKey;
+        use std::borrow::Borrow;
+        use std::hash::Hash;
+
+        /// Answers every DNS lookup with the key record.
+        struct PublishedKey(Txt);
+
+        impl ResolverCache<Box<str>, Txt> for PublishedKey {
+            fn get<Q>(&self, _: &Q) -> Option<Txt>
+            where
+                Box<str>: Borrow<Q>,
+                Q: Hash + Eq + ?Sized,
+            {
+                Some(self.0.clone())
+            }
+
+            fn remove<Q>(&self, _: &Q) -> Option<Txt>
+            where
+                Box<str>: Borrow<Q>,
+                Q: Hash + Eq + ?Sized,
+            {
+                None
+            }
+
+            fn insert(&self, _: Box<str>, _: Txt, _: std::time::Instant) {}
+        }
+
+        let record = format!(
+            "v=DKIM1; k=rsa; p={}",
+            BASE64.encode(public_key.to_public_key_der()?.as_bytes())
+        );
+        let key = PublishedKey(Txt::from(DomainKey::parse(record.as_bytes())));
+        let message = AuthenticatedMessage::parse(message).context("Message can't be parsed")?;
+        let results = MessageAuthenticator::new_cloudflare()?
+            .verify_dkim(Parameters::new(&message).with_txt_cache(&key))
+            .await;
+
+        match results.first().map(|x| x.result()) {
+            Some(DkimResult::Pass) => Ok(()),
+            Some(result) => bail!("DKIM verification failed: {:?}", result),
+            None => bail!("Message isn't signed"),
+        }
+    }
+
+    #[tokio::test]
+    async fn mime_message_is_dkim_signed() {
+        let (signer, public_key) = dkim_key_pair();
+        let message = EmailMessage::new(email("ged@gont.com"), "Maps", "See the map")
+            .html("<p>See the map</p>")
+            .reply_to(email("ogion@gont.com"))
+            .header("List-Id", "<news.earthsea.com>")
+            .attachments([Attachment {
+                filename: "map.pdf".to_string(),
+                content_type: "application/pdf".to_string(),
+                content: Arc::from(b"%PDF-1.7".as_slice()),
+                content_id: None,
+            }]);
+
+        let mime = mime_message(&message, "Roke", &email("news@earthsea.com")).unwrap();
+        let signed = signer.sign(&mime).unwrap();
+
+        let text = String::from_utf8(signed.clone())
+            .unwrap()
+            .replace("\r\n\t", "");
+        let signed_headers = text.split("h=").nth(1).unwrap().split(';').next().unwrap();
+        let signed_headers = signed_headers.to_lowercase();
+        assert!(DKIM_SIGNED_HEADERS
+            .iter()
+            .all(|header| signed_headers.split(':').any(|x| x == *header)));
+        assert!(text.contains("List-Id: <news.earthsea.com>\r\n"));
+        assert!(text.contains("filename=\"map.pdf\""));
+        assert!(verify_dkim(&signed, &public_key).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn altered_dkim_signed_message_fails_verification() {
+        let (signer, public_key) = dkim_key_pair();
+        let (_, other_key) = dkim_key_pair();
+        let message = b"From: news@earthsea.com\r\n\
+            To: ged@gont.com\r\n\
+            Subject: The Archipelago\r\n\
+            \r\n\
+            Hello Ged, the map of \xE9a is enclosed\r\n";
+
+        let signed = signer.sign(message).unwrap();
+        let replace = |from: &[u8], to: &[u8]| {
+            let i = signed.windows(from.len()).position(|x| x == from).unwrap();
+            [&signed[..i], to, &signed[i + from.len()..]].concat()
+        };
+        let subject = replace(b"Archipelago", b"Reach");
+        let body = replace(b"Ged", b"Tenar");
+
+        assert!(verify_dkim(&signed, &public_key).await.is_ok());
+        assert!(verify_dkim(&signed, &other_key).await.is_err());
+        assert!(verify_dkim(&subject, &public_key).await.is_err());
+        assert!(verify_dkim(&body, &public_key).await.is_err());
+    }
+
+    #[test]
+    fn custom_headers_are_checked() {
+        assert!(check_headers(&header("List-Id", "<news.earthsea.com>")).is_ok());
+        assert!(check_headers(&header("Reply-To", "ged@gont.com")).is_err());
+        assert!(check_headers(&header("X Tag", "x")).is_err());
+        assert!(check_headers(&header("X-Tag", "x\r\nBcc: ged@gont.com")).is_err());
+    }
 }
@@ -0,0 +1,135 @@
+use crate::domain::value_objects::SubscriberEmail;
+use crate::error::DomainError;
+use serde::Deserialize;
+
+/// Delivery outcome reported by the email provider.
+#[derive(Debug, Eq, PartialEq)]
+pub struct EmailEvent {
+    pub kind: EmailEventKind,
+    pub email: SubscriberEmail,
+    /// Provider id of the affected message, when reported
+    pub message_id: Option<String>,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum EmailEventKind {
+    Delivered,
+    HardBounce,
+    SoftBounce,
+    Complaint,
+}
+
+/// Postmark delivery, bounce or spam complaint webhook payload, e.g.
+/// `{"RecordType": "Bounce", "Type": "HardBounce", "Email": "ursula@example.com"}`.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct ProviderEvent {
+    record_type: String,
+    #[serde(default)]
+    r#type: String,
+    email: String,
+    #[serde(rename = "MessageID")]
+    message_id: Option<String>,
+}
+
+impl EmailEvent {
+    /// Other record types, such as opens, and informational bounces like auto responders are
+    /// ignored.
+    pub fn parse(payload: ProviderEvent) -> Result<Option<Self>, DomainError> {
+        let kind = match payload.record_type.as_str() {
+            "Delivery" => EmailEventKind::Delivered,
+            "SpamComplaint" => EmailEventKind::Complaint,
+            "Bounce" => match payload.r#type.as_str() {
+                "HardBounce" | "BadEmailAddress" | "ManuallyDeactivated" => {
+                    EmailEventKind::HardBounce
+                }
+                "SoftBounce" | "Transient" | "DnsError" | "Blocked" => EmailEventKind::SoftBounce,
+                _ => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+
+        Ok(Some(Self {
+            kind,
+            email: SubscriberEmail::parse(payload.email)?,
+            message_id: payload.message_id,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn parse(payload: serde_json::Value) -> Result<Option<EmailEvent>, DomainError> {
+        EmailEvent::parse(serde_json::from_value(payload).unwrap())
+    }
+
+    fn event(kind: EmailEventKind, message_id: Option<&str>) -> EmailEvent {
+        EmailEvent {
+            kind,
+            email: SubscriberEmail::parse("ursula@earthsea.com".to_string()).unwrap(),
+            message_id: message_id.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn bounces_and_complaints_are_parsed() {
+        let hard = parse(json!({
+            "RecordType": "Bounce",
+            "Type": "HardBounce",
+            "TypeCode": 1,
+            "MessageID": "883953f4-6105-42a2-a16a-77a8eac79483",
+            "Email": "ursula@earthsea.com",
+        }));
+        let soft = parse(json!({
+            "RecordType": "Bounce",
+            "Type": "Transient",
+            "Email": "ursula@earthsea.com",
+        }));
+        let complaint = parse(json!({
+            "RecordType": "SpamComplaint",
+            "Type": "SpamComplaint",
+            "Email": "ursula@earthsea.com",
+        }));
+        let delivery = parse(json!({ "RecordType": "Delivery", "Email": "ursula@earthsea.com" }));
+
+        assert_eq!(
+            hard.unwrap(),
+            Some(event(
+                EmailEventKind::HardBounce,
+                Some("883953f4-6105-42a2-a16a-77a8eac79483")
+            ))
+        );
+        assert_eq!(soft.unwrap(), Some(event(EmailEventKind::SoftBounce, None)));
+        assert_eq!(
+            complaint.unwrap(),
+            Some(event(EmailEventKind::Complaint, None))
+        );
+        assert_eq!(
+            delivery.unwrap(),
+            Some(event(EmailEventKind::Delivered, None))
+        );
+    }
+
+    #[test]
+    fn unrelated_events_are_ignored() {
+        let open = parse(json!({ "RecordType": "Open", "Email": "ursula@earthsea.com" }));
+        let auto_responder = parse(json!({
+            "RecordType": "Bounce",
+            "Type": "AutoResponder",
+            "Email": "ursula@earthsea.com",
+        }));
+
+        assert_eq!(open.unwrap(), None);
+        assert_eq!(auto_responder.unwrap(), None);
+    }
+
+    #[test]
+    fn invalid_email_is_rejected() {
+        let result = parse(json!({ "RecordType": "SpamComplaint", "Email": "not-an-email" }));
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,404 @@
+use crate::app_config::{CircuitBreakerConfig, EmailProviderConfig, EmailProviderKind};
+use anyhow::{anyhow, bail, Context};
+use chrono::{DateTime, Utc};
+use lettre::address::Envelope;
+use lettre::{Address, AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+use serde::Serialize;
+use std::fmt::Debug;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use strum_macros::AsRefStr;
+use tracing::{info, warn};
+
+/// Slow providers count as unavailable, so sends fail over instead of hanging.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Email providers in priority order. Requests go to the first available provider and fail
+/// over to the next one on transport errors and 5xx responses, other responses are final.
+/// SMTP providers fail over on transient errors of the first message, later errors are final
+/// since the earlier messages were already sent.
+///
+/// Every provider has a circuit breaker: after `failure_threshold` consecutive failures it's
+/// skipped for `open_seconds`, then a single trial request decides whether it's used again.
+#[derive(Debug)]
+pub struct EmailProviders {
+    providers: Vec<EmailProvider>,
+}
+
+#[derive(Debug)]
+struct EmailProvider {
+    name: String,
+    priority: u32,
+    transport: Transport,
+    breaker: Mutex<CircuitBreaker>,
+}
+
+#[derive(Debug)]
+enum Transport {
+    Http {
+        http_client: reqwest::Client,
+        base_url: String,
+        authorization_token: String,
+    },
+    Smtp(AsyncSmtpTransport<Tokio1Executor>),
+    Log,
+}
+
+/// Email as sent over SMTP.
+pub struct MimeEmail {
+    pub sender: String,
+    pub recipient: String,
+    /// Complete MIME message, DKIM signed when configured
+    pub message: Vec<u8>,
+}
+
+enum ProviderError {
+    /// Worth retrying with another provider
+    Unavailable(anyhow::Error),
+    Rejected(anyhow::Error),
+}
+
+#[derive(Debug)]
+struct CircuitBreaker {
+    failure_threshold: u32,
+    open_duration: Duration,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    last_error: Option<String>,
+    last_failure_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, AsRefStr)]
+pub enum CircuitState {
+    /// Used normally
+    Closed,
+    /// Skipped after repeated failures
+    Open,
+    /// Open period is over, the next request is a trial
+    HalfOpen,
+}
+
+/// Circuit breaker state of a provider.
+pub struct ProviderHealth {
+    pub name: String,
+    pub priority: u32,
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+    pub last_failure_at: Option<DateTime<Utc>>,
+}
+
+impl EmailProviders {
+    pub fn new(
+        providers: &[EmailProviderConfig],
+        circuit_breaker: &CircuitBreakerConfig,
+    ) -> Result<Self, anyhow::Error> {
+        if providers.is_empty() {
+            bail!("At least one email provider must be configured");
+        }
+        let http_client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()?;
+
+        let mut providers = providers
+            .iter()
+            .map(|config| {
+                let transport = match config.kind {
+                    EmailProviderKind::Http | EmailProviderKind::Smtp
+                        if config.base_url.is_empty() =>
+                    {
+                        bail!("Email provider {} has no base url", config.name)
+                    }
+                    EmailProviderKind::Http => Transport::Http {
+                        http_client: http_client.clone(),
+                        base_url: config.base_url.clone(),
+                        authorization_token: config.authorization_token.clone(),
+                    },
+                    EmailProviderKind::Smtp => Transport::Smtp(
+                        AsyncSmtpTransport::<Tokio1Executor>::from_url(&config.base_url)
+                            .with_context(|| {
+                                format!("Email provider {} has invalid SMTP url", config.name)
+                            })?
+                            .timeout(Some(REQUEST_TIMEOUT))
+                            .build(),
+                    ),
+                    EmailProviderKind::Log => Transport::Log,
+                };
+                Ok(EmailProvider {
+                    name: config.name.clone(),
+                    priority: config.priority,
+                    transport,
+                    breaker: Mutex::new(CircuitBreaker::new(circuit_breaker)),
+                })
+            })
+            .collect::<Result<Vec<_>, anyhow::Error>>()?;
+        providers.sort_by_key(|x| x.priority);
+
+        Ok(Self { providers })
+    }
+
+    /// Posts `body` as JSON to `path` of the first available HTTP provider, or sends the
+    /// emails rendered by `mime` over SMTP, and returns the response body. SMTP and logging
+    /// providers have no response, they accept every email or fail.
+    pub async fn send<B, M>(
+        &self,
+        path: &str,
+        body: &B,
+        mime: M,
+    ) -> Result<Option<String>, anyhow::Error>
+    where
+        B: Serialize + Debug,
+        M: Fn() -> Result<Vec<MimeEmail>, anyhow::Error> + Sync,
+    {
+        let mut errors = vec![];
+        for provider in &self.providers {
+            if !provider.breaker.lock().unwrap().allow(Instant::now()) {
+                continue;
+            }
+
+            match provider
+                .transport
+                .send(&provider.name, path, body, &mime)
+                .await
+            {
+                Ok(response) => {
+                    provider.breaker.lock().unwrap().record_success();
+                    return Ok(response);
+                }
+                Err(ProviderError::Rejected(error)) => {
+                    provider.breaker.lock().unwrap().record_success();
+                    return Err(error.context(format!("Rejected by {}", provider.name)));
+                }
+                Err(ProviderError::Unavailable(error)) => {
+                    warn!(provider = %provider.name, ?error, "Email provider failed");
+                    provider
+                        .breaker
+                        .lock()
+                        .unwrap()
+                        .record_failure(Instant::now(), &error);
+                    errors.push(format!("{}: {:#}", provider.name, error));
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            bail!("No email provider is available");
+        }
+        bail!("All email providers failed: {}", errors.join("; "))
+    }
+
+    pub fn health(&self) -> Vec<ProviderHealth> {
+        let now = Instant::now();
+        self.providers
+            .iter()
+            .map(|provider| {
+                let breaker = provider.breaker.lock().unwrap();
+                ProviderHealth {
+                    name: provider.name.clone(),
+                    priority: provider.priority,
+                    state: breaker.state(now),
+                    consecutive_failures: breaker.consecutive_failures,
+                    last_error: breaker.last_error.clone(),
+                    last_failure_at: breaker.last_failure_at,
+                }
+            })
+            .collect()
+    }
+}
+
+impl Transport {
+    async fn send<B, M>(
+        &self,
+        provider: &str,
+        path: &str,
+        body: &B,
+        mime: &M,
+    ) -> Result<Option<String>, ProviderError>
+    where
+        B: Serialize + Debug,
+        M: Fn() -> Result<Vec<MimeEmail>, anyhow::Error> + Sync,
+    {
+        let (http_client, base_url, authorization_token) = match self {
+            Transport::Http {
+                http_client,
+                base_url,
+                authorization_token,
+            } => (http_client, base_url, authorization_token),
+            Transport::Smtp(mailer) => {
+                let emails = mime().map_err(ProviderError::Rejected)?;
+                return send_smtp(mailer, &emails).await.map(|_| None);
+            }
+            Transport::Log => {
+                info!(provider, path, "Email request: {:?}", body);
+                return Ok(None);
+            }
+        };
+
+        let response = http_client
+            .post(format!("{}{}", base_url, path))
+            .header("X-Postmark-Server-Token", authorization_token)
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| ProviderError::Unavailable(e.into()))?;
+        let status = response.status();
+        let text = response
+            .text()
+            .await
+            .map_err(|e| ProviderError::Unavailable(e.into()))?;
+
+        if status.is_server_error() {
+            Err(ProviderError::Unavailable(anyhow!("{}: {}", status, text)))
+        } else if !status.is_success() {
+            Err(ProviderError::Rejected(anyhow!("{}: {}", status, text)))
+        } else {
+            Ok(Some(text))
+        }
+    }
+}
+
+impl MimeEmail {
+    fn envelope(&self) -> Result<Envelope, anyhow::Error> {
+        Ok(Envelope::new(
+            Some(self.sender.parse::<Address>()?),
+            vec![self.recipient.parse::<Address>()?],
+        )?)
+    }
+}
+
+async fn send_smtp(
+    mailer: &AsyncSmtpTransport<Tokio1Executor>,
+    emails: &[MimeEmail],
+) -> Result<(), ProviderError> {
+    for (i, email) in emails.iter().enumerate() {
+        let envelope = email.envelope().map_err(ProviderError::Rejected)?;
+
+        match mailer.send_raw(&envelope, &email.message).await {
+            Ok(_) => {}
+            Err(error) if i == 0 && !error.is_permanent() => {
+                return Err(ProviderError::Unavailable(error.into()))
+            }
+            Err(error) => {
+                return Err(ProviderError::Rejected(anyhow!(error).context(format!(
+                    "Sending to {} failed after {} of {} emails were sent",
+                    email.recipient,
+                    i,
+                    emails.len()
+                ))))
+            }
+        }
+    }
+    Ok(())
+}
+
+impl CircuitBreaker {
+    fn new(config: &CircuitBreakerConfig) -> Self {
+        Self {
+            failure_threshold: config.failure_threshold.get(),
+            open_duration: Duration::from_secs(config.open_seconds),
+            consecutive_failures: 0,
+            opened_at: None,
+            last_error: None,
+            last_failure_at: None,
+        }
+    }
+
+    fn state(&self, now: Instant) -> CircuitState {
+        match self.opened_at {
+            None => CircuitState::Closed,
+            Some(opened_at) if now.saturating_duration_since(opened_at) < self.open_duration => {
+                CircuitState::Open
+            }
+            Some(_) => CircuitState::HalfOpen,
+        }
+    }
+
+    /// A trial reopens the circuit, so concurrent requests keep skipping the provider until
+    /// the trial succeeds, and a cancelled trial is retried after the next open period.
+    fn allow(&mut self, now: Instant) -> bool {
+        match self.state(now) {
+            CircuitState::Closed => true,
+            CircuitState::Open => false,
+            CircuitState::HalfOpen => {
+                self.opened_at = Some(now);
+                true
+            }
+        }
+    }
+
+    fn record_success(&mut self) {
+        self.consecutive_failures = 0;
+        self.opened_at = None;
+    }
+
+    fn record_failure(&mut self, now: Instant, error: &anyhow::Error) {
+        self.consecutive_failures += 1;
+        self.last_error = Some(format!("{:#}", error));
+        self.last_failure_at = Some(Utc::now());
+        if self.consecutive_failures >= self.failure_threshold {
+            self.opened_at = Some(now);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::num::NonZeroU32;
+
+    fn breaker(failure_threshold: u32) -> CircuitBreaker {
+        CircuitBreaker::new(&CircuitBreakerConfig {
+            failure_threshold: NonZeroU32::new(failure_threshold).unwrap(),
+            open_seconds: 30,
+        })
+    }
+
+    #[test]
+    fn breaker_opens_after_consecutive_failures() {
+        let mut breaker = breaker(2);
+        let now = Instant::now();
+
+        breaker.record_failure(now, &anyhow!("503"));
+        assert!(breaker.allow(now));
+        breaker.record_success();
+        breaker.record_failure(now, &anyhow!("503"));
+        assert_eq!(breaker.state(now), CircuitState::Closed);
+        breaker.record_failure(now, &anyhow!("timeout"));
+
+        assert_eq!(breaker.state(now), CircuitState::Open);
+        assert!(!breaker.allow(now + Duration::from_secs(29)));
+        assert_eq!(breaker.last_error.as_deref(), Some("timeout"));
+    }
+
+    #[test]
+    fn half_open_breaker_allows_one_trial() {
+        let mut breaker = breaker(1);
+        let now = Instant::now();
+        breaker.record_failure(now, &anyhow!("503"));
+        let later = now + Duration::from_secs(30);
+
+        assert_eq!(breaker.state(later), CircuitState::HalfOpen);
+        assert!(breaker.allow(later));
+        assert!(!breaker.allow(later));
+        breaker.record_success();
+        assert_eq!(breaker.state(later), CircuitState::Closed);
+    }
+
+    #[test]
+    fn failed_trial_reopens_breaker() {
+        let mut breaker = breaker(3);
+        let now = Instant::now();
+        for _ in 0..3 {
+            breaker.record_failure(now, &anyhow!("503"));
+        }
+        let later = now + Duration::from_secs(30);
+
+        assert!(breaker.allow(later));
+        breaker.record_failure(later, &anyhow!("503"));
+
+        assert_eq!(
+            breaker.state(later + Duration::from_secs(29)),
+            CircuitState::Open
+        );
+    }
+}
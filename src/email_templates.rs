@@ -0,0 +1,314 @@
+use crate::domain::entities::email_template::EmailTemplate;
+use crate::domain::value_objects::{Locale, TemplateKind};
+use crate::error::{ApplicationError, DomainError, InternalLogicDomainError};
+use crate::infrastructure::sqlx_postgres_repository::SqlxPostgresRepository;
+use minijinja::value::Value;
+use minijinja::{escape_formatter, AutoEscape, Environment, Output, State};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Compiled set of database stored templates.
+///
+/// Every template has an HTML and a plain-text body. Each variant is rendered in its own
+/// environment, so `{% extends "layout" %}` and `{% include "footer" %}` resolve to the layout
+/// and partial of the same variant. Only the HTML variant is auto-escaped.
+///
+/// Templates are stored per locale. Each locale is compiled on top of the default locale, and
+/// a regional locale such as `pt-BR` also on top of `pt`, so missing translations fall back.
+pub struct EmailTemplates {
+    default_locale: Locale,
+    locales: HashMap<Locale, LocaleTemplates>,
+}
+
+struct LocaleTemplates {
+    html: Environment<'static>,
+    text: Environment<'static>,
+    subjects: HashMap<String, String>,
+}
+
+#[derive(Debug)]
+pub struct RenderedEmail {
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+}
+
+impl EmailTemplates {
+    pub fn new(templates: Vec<EmailTemplate>, default_locale: Locale) -> Result<Self, DomainError> {
+        let mut locales = HashMap::new();
+        for locale in templates
+            .iter()
+            .map(|t| t.locale.clone())
+            .chain([default_locale.clone()])
+        {
+            if locales.contains_key(&locale) {
+                continue;
+            }
+            let mut chain = vec![default_locale.clone(), locale.language(), locale.clone()];
+            chain.dedup();
+            let layers = chain
+                .iter()
+                .flat_map(|l| templates.iter().filter(move |t| &t.locale == l));
+            locales.insert(locale, LocaleTemplates::new(layers)?);
+        }
+
+        Ok(Self {
+            default_locale,
+            locales,
+        })
+    }
+
+    /// Loads the current templates, invalid stored templates are a server error.
+    pub async fn load(
+        repository: &SqlxPostgresRepository,
+        default_locale: &str,
+    ) -> Result<Self, ApplicationError> {
+        let default_locale =
+            Locale::parse(default_locale.to_owned()).map_err(InternalLogicDomainError::from)?;
+        let templates = repository
+            .get_email_templates()
+            .await?
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(InternalLogicDomainError::from)?;
+
+        Ok(Self::new(templates, default_locale).map_err(InternalLogicDomainError::from)?)
+    }
+
+    /// Names of the sendable templates in the default locale.
+    pub fn email_names(&self) -> impl Iterator<Item = &str> {
+        self.locales[&self.default_locale]
+            .subjects
+            .keys()
+            .map(String::as_str)
+    }
+
+    pub fn locales(&self) -> impl Iterator<Item = &Locale> {
+        self.locales.keys()
+    }
+
+    /// Renders the variant closest to `locale`: the exact locale, then its language, then the
+    /// default locale.
+    pub fn render<S: Serialize>(
+        &self,
+        name: &str,
+        locale: Option<&Locale>,
+        context: &S,
+    ) -> Result<RenderedEmail, DomainError> {
+        let templates = locale
+            .and_then(|l| {
+                self.locales
+                    .get(l)
+                    .or_else(|| self.locales.get(&l.language()))
+            })
+            .unwrap_or(&self.locales[&self.default_locale]);
+        templates.render(name, context)
+    }
+}
+
+impl LocaleTemplates {
+    /// Later templates replace earlier ones with the same name.
+    fn new<'a>(templates: impl Iterator<Item = &'a EmailTemplate>) -> Result<Self, DomainError> {
+        let mut html = Environment::new();
+        html.set_auto_escape_callback(|_| AutoEscape::Html);
+        html.set_formatter(html_formatter);
+        let mut text = Environment::new();
+        text.set_auto_escape_callback(|_| AutoEscape::None);
+        let mut subjects = HashMap::new();
+
+        for template in templates {
+            let name = template.name.as_ref().to_owned();
+            html.add_template_owned(name.clone(), template.html_body.clone())
+                .map_err(|e| template_error(&name, e))?;
+            text.add_template_owned(name.clone(), template.text_body.clone())
+                .map_err(|e| template_error(&name, e))?;
+            text.template_from_str(&template.subject)
+                .map_err(|e| template_error(&name, e))?;
+            if template.kind == TemplateKind::Email {
+                subjects.insert(name, template.subject.clone());
+            } else {
+                subjects.remove(&name);
+            }
+        }
+
+        Ok(Self {
+            html,
+            text,
+            subjects,
+        })
+    }
+
+    fn render<S: Serialize>(&self, name: &str, context: &S) -> Result<RenderedEmail, DomainError> {
+        let subject = self
+            .subjects
+            .get(name)
+            .ok_or_else(|| DomainError::from(format!("Email template {} wasn't found", name)))?;
+
+        let render = |env: &Environment<'static>| {
+            env.get_template(name)
+                .and_then(|t| t.render(context))
+                .map_err(|e| template_error(name, e))
+        };
+
+        Ok(RenderedEmail {
+            subject: self
+                .text
+                .render_str(subject, context)
+                .map_err(|e| template_error(name, e))?,
+            html: render(&self.html)?,
+            text: render(&self.text)?,
+        })
+    }
+}
+
+/// Escapes only the characters significant to HTML, so links stay readable in the source.
+pub fn html_formatter(
+    out: &mut Output,
+    state: &State,
+    value: &Value,
+) -> Result<(), minijinja::Error> {
+    match value.as_str() {
+        Some(s) if !value.is_safe() && matches!(state.auto_escape(), AutoEscape::Html) => {
+            Ok(out.write_str(&escape_html(s))?)
+        }
+        _ => escape_formatter(out, state, value),
+    }
+}
+
+pub fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn template_error(name: &str, error: minijinja::Error) -> DomainError {
+    format!("Template {} is invalid: {}", name, error).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::value_objects::TemplateName;
+    use serde_json::json;
+
+    fn locale(s: &str) -> Locale {
+        Locale::parse(s.to_string()).unwrap()
+    }
+
+    fn template(name: &str, kind: TemplateKind, html: &str, text: &str) -> EmailTemplate {
+        EmailTemplate {
+            name: TemplateName::parse(name.to_string()).unwrap(),
+            locale: locale("en"),
+            kind,
+            subject: "Hi {{ name }}".to_string(),
+            html_body: html.to_string(),
+            text_body: text.to_string(),
+        }
+    }
+
+    fn templates() -> EmailTemplates {
+        let german = EmailTemplate {
+            locale: locale("de"),
+            subject: "Hallo {{ name }}".to_string(),
+            ..template(
+                "welcome",
+                TemplateKind::Email,
+                "{% extends \"layout\" %}{% block content %}Hallo {{ name }}{% endblock %}",
+                "Hallo {{ name }}",
+            )
+        };
+        EmailTemplates::new(
+            vec![
+                template(
+                    "layout",
+                    TemplateKind::Layout,
+                    "<body>{% block content %}{% endblock %}{% include \"footer\" %}</body>",
+                    "{% block content %}{% endblock %}\n{% include \"footer\" %}",
+                ),
+                template(
+                    "footer",
+                    TemplateKind::Partial,
+                    "<a href=\"{{ unsubscribe_link }}\">unsubscribe</a>",
+                    "unsubscribe: {{ unsubscribe_link }}",
+                ),
+                template(
+                    "welcome",
+                    TemplateKind::Email,
+                    "{% extends \"layout\" %}{% block content %}Hello {{ name }}{% endblock %}",
+                    "{% extends \"layout\" %}{% block content %}Hello {{ name }}{% endblock %}",
+                ),
+                german,
+            ],
+            locale("en"),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn layout_and_partial_are_applied_to_both_variants() {
+        let context = json!({ "name": "Ursula", "unsubscribe_link": "https://x/u" });
+
+        let email = templates().render("welcome", None, &context).unwrap();
+
+        assert_eq!(email.subject, "Hi Ursula");
+        assert_eq!(
+            email.html,
+            "<body>Hello Ursula<a href=\"https://x/u\">unsubscribe</a></body>"
+        );
+        assert_eq!(email.text, "Hello Ursula\nunsubscribe: https://x/u");
+    }
+
+    #[test]
+    fn html_variant_is_escaped_and_text_variant_is_not() {
+        let context = json!({ "name": "<b>Ursula</b>", "unsubscribe_link": "" });
+
+        let email = templates().render("welcome", None, &context).unwrap();
+
+        assert!(email.html.contains("Hello &lt;b&gt;Ursula&lt;/b&gt;"));
+        assert!(email.text.contains("Hello <b>Ursula</b>"));
+    }
+
+    #[test]
+    fn partials_are_not_sendable() {
+        assert!(templates().render("footer", None, &json!({})).is_err());
+    }
+
+    #[test]
+    fn invalid_syntax_is_rejected() {
+        let result = EmailTemplates::new(
+            vec![template("broken", TemplateKind::Email, "{% if %}", "")],
+            locale("en"),
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn closest_locale_is_used_with_default_fallback() {
+        let context = json!({ "name": "Ursula", "unsubscribe_link": "https://x/u" });
+        let templates = templates();
+
+        let austrian = templates
+            .render("welcome", Some(&locale("de-AT")), &context)
+            .unwrap();
+        let french = templates
+            .render("welcome", Some(&locale("fr")), &context)
+            .unwrap();
+
+        assert_eq!(austrian.subject, "Hallo Ursula");
+        assert_eq!(
+            austrian.html,
+            "<body>Hallo Ursula<a href=\"https://x/u\">unsubscribe</a></body>"
+        );
+        assert_eq!(french.subject, "Hi Ursula");
+    }
+}
@@ -1,10 +1,12 @@
-use axum::http::StatusCode;
+use axum::extract::rejection::{FormRejection, JsonRejection, PathRejection, QueryRejection};
+use axum::http::{header, HeaderValue, StatusCode};
 use axum::response::{IntoResponse, Response};
-use axum::Json;
 use derive_more::{Display, From};
+use serde::Serialize;
 use serde_json::json;
 use std::borrow::Cow;
-use std::fmt::Debug;
+use std::fmt::{Debug, Formatter};
+use strum_macros::AsRefStr;
 use tracing::error;
 
 #[derive(Debug, From, Display)]
@@ -20,17 +22,57 @@ pub enum ApplicationError {
     InternalLogicError(InternalLogicError),
     AuthError(anyhow::Error),
     DomainError(DomainError),
+    NotFoundError(NotFoundError),
+    ValidationError(ValidationError),
 }
 
 #[derive(Debug, From, Display)]
 pub struct DomainError(Cow<'static, str>);
 
+/// The requested resource, or one the request refers to, doesn't exist.
+#[derive(Debug, From, Display)]
+pub struct NotFoundError(Cow<'static, str>);
+
 #[derive(Debug, From, Display)]
 pub struct InternalLogicDomainError(DomainError);
 
 #[derive(Debug, From, Display)]
 pub struct InternalLogicError(anyhow::Error);
 
+/// Invalid request fields, reported together instead of one at a time.
+#[derive(Debug, Default)]
+pub struct ValidationError(Vec<FieldError>);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    pub field: Cow<'static, str>,
+    pub reason: String,
+}
+
+/// Stable error codes clients can rely on, unlike the `detail` text.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum ErrorCode {
+    InvalidRequest,
+    ValidationFailed,
+    Unauthorized,
+    NotFound,
+    InternalError,
+}
+
+/// RFC 7807 problem details of an error response.
+///
+/// Responses carry the problem as an extension, the problem details middleware renders it again
+/// with the request id as `instance` and, in Local and Testing, the internal error as `debug`.
+#[derive(Debug, Clone)]
+pub struct Problem {
+    pub code: ErrorCode,
+    pub detail: String,
+    pub errors: Vec<FieldError>,
+    /// Debug output of the underlying error, hidden from clients in other environments
+    pub internal: Option<String>,
+}
+
 impl From<String> for DomainError {
     fn from(value: String) -> Self {
         DomainError::from(Cow::Owned(value))
@@ -43,32 +85,220 @@ impl From<&'static str> for DomainError {
     }
 }
 
+impl From<String> for NotFoundError {
+    fn from(value: String) -> Self {
+        NotFoundError::from(Cow::Owned(value))
+    }
+}
+
+impl From<&'static str> for NotFoundError {
+    fn from(value: &'static str) -> Self {
+        NotFoundError::from(Cow::Borrowed(value))
+    }
+}
+
+/// Malformed bodies are reported as invalid requests, unlike axum's 422 for bodies that
+/// don't match the expected fields.
+impl From<JsonRejection> for ApplicationError {
+    fn from(value: JsonRejection) -> Self {
+        DomainError::from(value.body_text()).into()
+    }
+}
+
+impl From<FormRejection> for ApplicationError {
+    fn from(value: FormRejection) -> Self {
+        DomainError::from(value.body_text()).into()
+    }
+}
+
+impl From<PathRejection> for ApplicationError {
+    fn from(value: PathRejection) -> Self {
+        DomainError::from(value.body_text()).into()
+    }
+}
+
+impl From<QueryRejection> for ApplicationError {
+    fn from(value: QueryRejection) -> Self {
+        DomainError::from(value.body_text()).into()
+    }
+}
+
+impl ValidationError {
+    pub fn add(&mut self, field: impl Into<Cow<'static, str>>, reason: impl ToString) {
+        self.0.push(FieldError {
+            field: field.into(),
+            reason: reason.to_string(),
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn errors(&self) -> &[FieldError] {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let errors = self
+            .0
+            .iter()
+            .map(|x| format!("{}: {}", x.field, x.reason))
+            .collect::<Vec<_>>();
+        write!(f, "{}", errors.join("; "))
+    }
+}
+
+impl ErrorCode {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ErrorCode::InvalidRequest | ErrorCode::ValidationFailed => StatusCode::BAD_REQUEST,
+            ErrorCode::Unauthorized => StatusCode::UNAUTHORIZED,
+            ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            ErrorCode::InvalidRequest => "Invalid request",
+            ErrorCode::ValidationFailed => "Validation failed",
+            ErrorCode::Unauthorized => "Unauthorized",
+            ErrorCode::NotFound => "Not found",
+            ErrorCode::InternalError => "Internal server error",
+        }
+    }
+}
+
+impl Problem {
+    pub fn new(code: ErrorCode, detail: impl Into<String>) -> Self {
+        Self {
+            code,
+            detail: detail.into(),
+            errors: vec![],
+            internal: None,
+        }
+    }
+
+    pub fn to_response(&self, instance: Option<&str>, show_internal: bool) -> Response {
+        let mut body = json!({
+            "type": format!("urn:zero2prod:problem:{}", self.code.as_ref()),
+            "title": self.code.title(),
+            "status": self.code.status().as_u16(),
+            "detail": self.detail,
+            "code": self.code.as_ref(),
+        });
+        if let Some(instance) = instance {
+            body["instance"] = json!(instance);
+        }
+        if !self.errors.is_empty() {
+            body["errors"] = json!(self.errors);
+        }
+        if let Some(internal) = self.internal.as_ref().filter(|_| show_internal) {
+            body["debug"] = json!(internal);
+        }
+
+        let mut response = (
+            self.code.status(),
+            [(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/problem+json"),
+            )],
+            body.to_string(),
+        )
+            .into_response();
+        response.extensions_mut().insert(self.clone());
+        response
+    }
+}
+
+impl IntoResponse for Problem {
+    fn into_response(self) -> Response {
+        self.to_response(None, false)
+    }
+}
+
 impl IntoResponse for ApplicationError {
     fn into_response(self) -> Response {
-        match self {
-            e @ ApplicationError::RepositoryError(..) => {
-                error!("Processing error!\n{:#?}", e);
-                (StatusCode::INTERNAL_SERVER_ERROR, to_json_error(e))
+        let problem = match &self {
+            ApplicationError::DomainError(e) => {
+                Problem::new(ErrorCode::InvalidRequest, e.to_string())
             }
-            e @ ApplicationError::DomainError(..) => (StatusCode::BAD_REQUEST, to_json_error(e)),
-            e @ ApplicationError::InternalLogicDomainError(..) => {
-                (StatusCode::INTERNAL_SERVER_ERROR, to_json_error(e))
+            ApplicationError::NotFoundError(e) => Problem::new(ErrorCode::NotFound, e.to_string()),
+            ApplicationError::ValidationError(e) => Problem {
+                errors: e.0.clone(),
+                ..Problem::new(ErrorCode::ValidationFailed, "Request has invalid fields")
+            },
+            ApplicationError::AuthError(e) => Problem::new(ErrorCode::Unauthorized, e.to_string()),
+            ApplicationError::RepositoryError(..)
+            | ApplicationError::InternalLogicDomainError(..)
+            | ApplicationError::InternalLogicError(..) => {
+                error!("Processing error!\n{:#?}", self);
+                Problem {
+                    internal: Some(format!("{:?}", self)),
+                    ..Problem::new(
+                        ErrorCode::InternalError,
+                        "The request couldn't be processed",
+                    )
+                }
             }
-            e @ ApplicationError::InternalLogicError(..) => {
-                (StatusCode::INTERNAL_SERVER_ERROR, to_json_error(e))
-            }
-            e @ ApplicationError::AuthError(..) => (StatusCode::UNAUTHORIZED, to_json_error(e)),
-        }
-        .into_response()
+        };
+        problem.into_response()
     }
 }
 
-fn to_json_error<T: Display + Debug>(error: T) -> Response {
-    let message = format!("{}", error);
-    let details = format!("{:?}", error);
-    let json = json!({
-        "message": message,
-        "details": details
-    });
-    Json(json).into_response()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::to_bytes;
+
+    async fn body(response: Response) -> serde_json::Value {
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn internal_errors_are_hidden() {
+        let error = ApplicationError::from(InternalLogicError::from(anyhow::anyhow!(
+            "connection refused"
+        )));
+        let response = error.into_response();
+        let problem = response.extensions().get::<Problem>().unwrap().clone();
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "application/problem+json"
+        );
+        let hidden = body(response).await;
+        assert_eq!(hidden["code"], "internal_error");
+        assert_eq!(hidden["detail"], "The request couldn't be processed");
+        assert!(hidden.get("debug").is_none());
+        let shown = body(problem.to_response(Some("request-id"), true)).await;
+        assert_eq!(shown["instance"], "request-id");
+        assert!(shown["debug"]
+            .as_str()
+            .unwrap()
+            .contains("connection refused"));
+    }
+
+    #[tokio::test]
+    async fn validation_errors_list_fields() {
+        let mut errors = ValidationError::default();
+        errors.add("name", "Name can't be empty");
+        errors.add("email", "Email is missing");
+
+        let response = ApplicationError::from(errors).into_response();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body(response).await["errors"],
+            json!([
+                { "field": "name", "reason": "Name can't be empty" },
+                { "field": "email", "reason": "Email is missing" },
+            ])
+        );
+    }
 }
@@ -0,0 +1,22 @@
+use crate::error::ApplicationError;
+use axum::extract::{FromRequest, FromRequestParts};
+
+/// `axum::Json` whose rejections are returned as `ApplicationError` problem details.
+#[derive(FromRequest)]
+#[from_request(via(axum::Json), rejection(ApplicationError))]
+pub struct JsonBody<T>(pub T);
+
+/// `axum::Form` whose rejections are returned as `ApplicationError` problem details.
+#[derive(FromRequest)]
+#[from_request(via(axum::Form), rejection(ApplicationError))]
+pub struct FormBody<T>(pub T);
+
+/// `axum::extract::Path` whose rejections are returned as `ApplicationError` problem details.
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(ApplicationError))]
+pub struct PathParams<T>(pub T);
+
+/// `axum::extract::Query` whose rejections are returned as `ApplicationError` problem details.
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(ApplicationError))]
+pub struct QueryParams<T>(pub T);
@@ -0,0 +1,199 @@
+use crate::error::DomainError;
+use crate::merge_fields::{protect_tags, restore_tags, starts_with_tag};
+use ammonia::{Builder, Url, UrlRelative, UrlRelativeEvaluate};
+use css_inline::CSSInliner;
+use maplit::{hashmap, hashset};
+use std::borrow::Cow;
+
+/// Gmail clips messages with more than 102KB of HTML.
+pub const CLIPPING_SIZE: usize = 102 * 1024;
+
+const ALLOWED_TAGS: [&str; 36] = [
+    "a",
+    "b",
+    "blockquote",
+    "br",
+    "center",
+    "code",
+    "div",
+    "em",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "hr",
+    "i",
+    "img",
+    "li",
+    "ol",
+    "p",
+    "pre",
+    "s",
+    "small",
+    "span",
+    "strong",
+    "sub",
+    "sup",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "tr",
+    "u",
+    "ul",
+];
+const CELL_ATTRIBUTES: [&str; 6] = ["width", "height", "colspan", "rowspan", "valign", "bgcolor"];
+
+/// Prepares issue HTML for mail clients: `<style>` rules are inlined into `style` attributes,
+/// the markup is sanitized against an allowlist and relative URLs are resolved against
+/// `base_url`. Merge field tags are left for per recipient rendering.
+pub fn process_html(html: &str, base_url: &str) -> Result<String, DomainError> {
+    let base_url = Url::parse(base_url)
+        .map_err(|e| DomainError::from(format!("{} is not valid base url: {}", base_url, e)))?;
+    let (html, tags) = protect_tags(html);
+
+    let inliner = CSSInliner::options()
+        .keep_style_tags(false)
+        .keep_link_tags(false)
+        .load_remote_stylesheets(false)
+        .build();
+    let inlined = inliner
+        .inline(&html)
+        .map_err(|e| DomainError::from(format!("Styles can't be inlined: {}", e)))?;
+
+    let sanitized = sanitizer()
+        .url_relative(UrlRelative::Custom(Box::new(RelativeUrls(base_url))))
+        .clean(&inlined)
+        .to_string();
+
+    Ok(restore_tags(sanitized, &tags))
+}
+
+/// Sanitizes HTML rendered from processed content again, merge field values and `default`
+/// fallbacks could add URLs with disallowed schemes, e.g. `href="{{ attributes.site }}"`.
+pub fn sanitize_html(html: &str) -> String {
+    sanitizer().clean(html).to_string()
+}
+
+/// Rewrites absolute `http(s)` link targets of already processed HTML.
+pub fn rewrite_links<F>(html: &str, rewrite: F) -> String
+where
+    F: Fn(&str) -> String + Send + Sync + 'static,
+{
+    sanitizer()
+        .attribute_filter(move |element, attribute, value| {
+            let is_web_link = element == "a"
+                && attribute == "href"
+                && (value.starts_with("https://") || value.starts_with("http://"));
+            if is_web_link {
+                Some(Cow::Owned(rewrite(value)))
+            } else {
+                Some(Cow::Borrowed(value))
+            }
+        })
+        .clean(html)
+        .to_string()
+}
+
+/// Content ids referenced by inline images, `<img src="cid:logo">`, of already processed HTML.
+pub fn inline_image_ids(html: &str) -> Vec<&str> {
+    let mut ids = html
+        .match_indices(r#"src="cid:"#)
+        .filter_map(|(start, prefix)| {
+            let rest = &html[start + prefix.len()..];
+            rest.find('"').map(|end| &rest[..end])
+        })
+        .collect::<Vec<_>>();
+    ids.sort_unstable();
+    ids.dedup();
+    ids
+}
+
+fn sanitizer() -> Builder<'static> {
+    let mut builder = Builder::empty();
+    builder
+        .tags(ALLOWED_TAGS.into_iter().collect())
+        .generic_attributes(hashset!["style", "align", "dir", "lang", "title"])
+        .tag_attributes(hashmap![
+            "a" => hashset!["href", "target"],
+            "img" => hashset!["src", "alt", "width", "height"],
+            "table" => hashset!["width", "border", "cellpadding", "cellspacing", "bgcolor"],
+            "td" => CELL_ATTRIBUTES.into_iter().collect(),
+            "th" => CELL_ATTRIBUTES.into_iter().collect(),
+        ])
+        .url_schemes(hashset!["http", "https", "mailto", "cid"]);
+    builder
+}
+
+struct RelativeUrls(Url);
+
+impl<'a> UrlRelativeEvaluate<'a> for RelativeUrls {
+    fn evaluate<'url>(&self, url: &'url str) -> Option<Cow<'url, str>> {
+        if starts_with_tag(url) {
+            return Some(Cow::Borrowed(url));
+        }
+        self.0.join(url).ok().map(|url| Cow::Owned(url.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(html: &str) -> String {
+        process_html(html, "https://news.example.com/issues/").unwrap()
+    }
+
+    #[test]
+    fn disallowed_markup_is_removed() {
+        assert_eq!(
+            process(r#"<p onclick="x()">Hi<script>alert(1)</script><iframe src="x"></iframe></p>"#),
+            "<p>Hi</p>"
+        );
+    }
+
+    #[test]
+    fn style_rules_are_inlined() {
+        assert_eq!(
+            process("<style>p { color: red }</style><p>Hi</p>"),
+            r#"<p style="color: red;">Hi</p>"#
+        );
+    }
+
+    #[test]
+    fn relative_urls_are_resolved() {
+        assert_eq!(
+            process(r#"<a href="/archive">x</a><img src="logo.png">"#),
+            r#"<a href="https://news.example.com/archive" rel="noopener noreferrer">x</a><img src="https://news.example.com/issues/logo.png">"#
+        );
+    }
+
+    #[test]
+    fn merge_fields_are_kept() {
+        assert_eq!(
+            process(r#"<a href="{{ unsubscribe_link }}">{{ name | default("friend") }}</a>"#),
+            r#"<a href="{{ unsubscribe_link }}" rel="noopener noreferrer">{{ name | default("friend") }}</a>"#
+        );
+    }
+
+    #[test]
+    fn rendered_urls_are_sanitized_again() {
+        assert_eq!(
+            sanitize_html(r#"<a href="javascript:alert(1)">x</a><img src="cid:logo">"#),
+            r#"<a rel="noopener noreferrer">x</a><img src="cid:logo">"#
+        );
+    }
+
+    #[test]
+    fn inline_image_ids_are_collected() {
+        let html = process(
+            r#"<img src="cid:logo"><p>Hi</p><img src="cid:chart"><img src="cid:logo"><img src="/x.png">"#,
+        );
+
+        assert_eq!(inline_image_ids(&html), vec!["chart", "logo"]);
+    }
+}
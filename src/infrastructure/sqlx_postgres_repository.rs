@@ -1,14 +1,30 @@
 use std::fmt::Debug;
 
+use crate::domain::entities::asset::Asset;
+use crate::domain::entities::delivery::{Delivery, DeliverySummary, DeliveryUpdate};
+use crate::domain::entities::email_template::EmailTemplate;
+use crate::domain::entities::mailing_list::{ListMembership, MailingList};
+use crate::domain::entities::newsletter_issue::{IssueStats, NewsletterIssue};
+use crate::domain::entities::outbox_email::OutboxEmail;
+use crate::domain::entities::recipient::Recipient;
+use crate::domain::entities::segment::Segment;
 use crate::domain::entities::subscriber::Subscriber;
-use crate::domain::value_objects::{ConfirmationStatus, SubscriberEmail};
+use crate::domain::entities::suppression::Suppression;
+use crate::domain::value_objects::{
+    AssetId, Audience, ComparisonOperator, ConfirmationStatus, ConsentConfirmation, ContentFormat,
+    DeliveryStatus, IssueId, ListId, ListSlug, Locale, OutboxEmailId, SegmentCondition,
+    SegmentFilter, SegmentId, SegmentValue, SubscriberEmail, SubscriberName, SubscriptionSource,
+    SuppressionReason, SuppressionTarget, Tag, TemplateKind, TemplateName, TrackingEventKind,
+};
 use crate::domain::value_objects::{PasswordHash, SubscriberId};
 use crate::error::{DomainError, RepositoryError};
-use chrono::Utc;
+use chrono::{DateTime, Duration, NaiveTime, Utc};
 use sqlx::PgPool;
 use sqlx::Postgres;
+use sqlx::QueryBuilder;
 use sqlx::Transaction;
 use tracing::error;
+use uuid::Uuid;
 
 #[derive(Debug, Clone)]
 pub struct SqlxPostgresRepository(PgPool);
@@ -25,18 +41,18 @@ impl SqlxPostgresRepository {
 
 impl SqlxPostgresRepository {
     #[tracing::instrument(skip_all)]
-    pub async fn begin_transaction(&self) -> Result<Transaction<Postgres>, RepositoryError> {
+    pub async fn begin_transaction(&self) -> Result<Transaction<'_, Postgres>, RepositoryError> {
         let transaction = self.0.begin().await.unwrap();
         Ok(transaction)
     }
 
     #[tracing::instrument(skip_all)]
-    pub async fn get_subscriber_id_by_token(
+    pub async fn get_subscription_by_token(
         &self,
         token: &str,
-    ) -> Result<Option<SubscriberId>, RepositoryError> {
-        let id = sqlx::query!(
-            "SELECT subscriber_id FROM subscription_tokens WHERE subscription_token=$1",
+    ) -> Result<Option<(SubscriberId, ListId)>, RepositoryError> {
+        let row = sqlx::query!(
+            "SELECT subscriber_id, list_id FROM subscription_tokens WHERE subscription_token=$1",
             token
         )
         .fetch_optional(&self.0)
@@ -44,71 +60,294 @@ impl SqlxPostgresRepository {
         .map_err(|e| {
             error!("Failed to execute query: {:?}", e);
             e
-        })?
-        .map(|x| x.subscriber_id);
-        //
-        // let id: Option<(String,)> = sqlx::query_as(
-        //     "SELECT subscriber_id,a FROM subscription_tokens WHERE subscription_token=$1",
-        // )
-        // .bind(token)
-        // .fetch_optional(&self.0)
-        // .await?;
-
-        let id = id
-            .map(|id| SubscriberId::parse(&id).map_err(RepositoryError::Domain))
+        })?;
+
+        let subscription = row
+            .map(|x| {
+                SubscriberId::parse(&x.subscriber_id)
+                    .map(|id| (id, ListId::from(x.list_id)))
+                    .map_err(RepositoryError::Domain)
+            })
             .transpose()?;
 
-        Ok(id)
+        Ok(subscription)
     }
 
-    pub async fn get_confirmed_emails(
+    /// Returns every subscriber of the audience, once per subscriber.
+    #[tracing::instrument(skip_all)]
+    pub async fn get_recipients(
         &self,
-    ) -> Result<Vec<Result<SubscriberEmail, DomainError>>, RepositoryError> {
-        let emails = sqlx::query!(
-            "SELECT email FROM subscriptions WHERE status=$1",
-            ConfirmationStatus::Confirmed.as_ref()
+        audience: &Audience,
+    ) -> Result<Vec<Result<Recipient, DomainError>>, RepositoryError> {
+        let mut query = QueryBuilder::new(
+            r#"
+        SELECT DISTINCT s.id, s.email, s.name, s.content_format, s.attributes, s.locale,
+               s.tracking_opt_out, pt.preference_token
+        FROM subscriptions s
+        JOIN preference_tokens pt ON pt.subscriber_id = s.id
+        "#,
+        );
+        push_confirmed_audience(&mut query, audience);
+
+        let recipients = query
+            .build_query_as::<RecipientRow>()
+            .fetch_all(&self.0)
+            .await?
+            .into_iter()
+            .map(Recipient::try_from)
+            .collect::<Vec<_>>();
+
+        Ok(recipients)
+    }
+
+    /// Returns the subscriber as a recipient regardless of the confirmation status.
+    #[tracing::instrument(skip_all)]
+    pub async fn get_recipient(
+        &self,
+        subscriber_id: &SubscriberId,
+    ) -> Result<Option<Result<Recipient, DomainError>>, RepositoryError> {
+        let recipient = sqlx::query_as!(
+            RecipientRow,
+            r#"
+        SELECT s.id, s.email, s.name, s.content_format, s.attributes, s.locale,
+               s.tracking_opt_out, pt.preference_token
+        FROM subscriptions s
+        JOIN preference_tokens pt ON pt.subscriber_id = s.id
+        WHERE s.id = $1
+        "#,
+            subscriber_id.as_ref()
+        )
+        .fetch_optional(&self.0)
+        .await?
+        .map(Recipient::try_from);
+
+        Ok(recipient)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn count_confirmed_subscribers(
+        &self,
+        audience: &Audience,
+    ) -> Result<i64, RepositoryError> {
+        let mut query = QueryBuilder::new("SELECT COUNT(DISTINCT s.id) FROM subscriptions s ");
+        push_confirmed_audience(&mut query, audience);
+
+        let count = query.build_query_scalar::<i64>().fetch_one(&self.0).await?;
+
+        Ok(count)
+    }
+
+    /// Returns subscribers having all of `with_tags` and none of `without_tags`.
+    #[tracing::instrument(skip_all)]
+    pub async fn get_subscribers(
+        &self,
+        with_tags: &[Tag],
+        without_tags: &[Tag],
+    ) -> Result<Vec<Result<Subscriber, DomainError>>, RepositoryError> {
+        let mut with_tags = with_tags
+            .iter()
+            .map(|x| x.as_ref().to_owned())
+            .collect::<Vec<_>>();
+        with_tags.sort();
+        with_tags.dedup();
+        let without_tags = without_tags
+            .iter()
+            .map(|x| x.as_ref().to_owned())
+            .collect::<Vec<_>>();
+
+        let subscribers = sqlx::query_as!(
+            SubscriberRow,
+            r#"
+        SELECT s.id, s.email, s.name, s.status, s.subscribed_at, s.source, s.consent_version,
+               s.confirmed_at, s.confirmation_ip, s.confirmation_user_agent, s.attributes,
+               s.content_format, s.locale, s.tracking_opt_out,
+               COALESCE(array_agg(st.tag ORDER BY st.tag) FILTER (WHERE st.tag IS NOT NULL), '{}') AS "tags!"
+        FROM subscriptions s
+        LEFT JOIN subscriber_tags st ON st.subscriber_id = s.id
+        WHERE (cardinality($1::text[]) = 0 OR s.id IN (
+                SELECT subscriber_id FROM subscriber_tags WHERE tag = ANY($1)
+                GROUP BY subscriber_id HAVING COUNT(*) = cardinality($1)))
+          AND NOT EXISTS (
+                SELECT 1 FROM subscriber_tags x WHERE x.subscriber_id = s.id AND x.tag = ANY($2))
+        GROUP BY s.id
+        ORDER BY s.subscribed_at
+        "#,
+            &with_tags,
+            &without_tags
         )
         .fetch_all(&self.0)
         .await?
         .into_iter()
-        .map(|x| SubscriberEmail::parse(x.email))
+        .map(Subscriber::try_from)
         .collect::<Vec<_>>();
 
-        Ok(emails)
+        Ok(subscribers)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn get_subscriber(
+        &self,
+        subscriber_id: &SubscriberId,
+    ) -> Result<Option<Subscriber>, RepositoryError> {
+        let subscriber = sqlx::query_as!(
+            SubscriberRow,
+            r#"
+        SELECT s.id, s.email, s.name, s.status, s.subscribed_at, s.source, s.consent_version,
+               s.confirmed_at, s.confirmation_ip, s.confirmation_user_agent, s.attributes,
+               s.content_format, s.locale, s.tracking_opt_out,
+               COALESCE(array_agg(st.tag ORDER BY st.tag) FILTER (WHERE st.tag IS NOT NULL), '{}') AS "tags!"
+        FROM subscriptions s
+        LEFT JOIN subscriber_tags st ON st.subscriber_id = s.id
+        WHERE s.id = $1
+        GROUP BY s.id
+        "#,
+            subscriber_id.as_ref()
+        )
+        .fetch_optional(&self.0)
+        .await?
+        .map(Subscriber::try_from)
+        .transpose()?;
+
+        Ok(subscriber)
     }
 
+    /// Confirms the subscriber, recording the consent evidence only on the first confirmation.
     #[tracing::instrument(skip_all)]
-    pub async fn update_subscriber_confirmation_status(
+    pub async fn update_subscriber_confirmation_status_tx(
         &self,
+        transaction: &mut Transaction<'_, Postgres>,
         subscriber_id: &SubscriberId,
+        confirmation: &ConsentConfirmation,
     ) -> Result<(), RepositoryError> {
         sqlx::query!(
-            "UPDATE subscriptions SET status=$1 WHERE id=$2",
+            r#"
+        UPDATE subscriptions
+        SET status=$1,
+            confirmed_at=COALESCE(confirmed_at, $2),
+            confirmation_ip=CASE WHEN confirmed_at IS NULL THEN $3 ELSE confirmation_ip END,
+            confirmation_user_agent=CASE WHEN confirmed_at IS NULL THEN $4 ELSE confirmation_user_agent END
+        WHERE id=$5
+        "#,
             ConfirmationStatus::Confirmed.as_ref(),
+            confirmation.confirmed_at,
+            confirmation.ip,
+            confirmation.user_agent,
             subscriber_id.as_ref()
         )
-        .execute(&self.0)
+        .execute(&mut **transaction)
         .await?;
 
         Ok(())
     }
 
     #[tracing::instrument(skip_all)]
-    pub async fn insert_subscriber_tx(
+    pub async fn confirm_list_subscription_tx(
         &self,
         transaction: &mut Transaction<'_, Postgres>,
-        subscriber: &Subscriber,
+        subscriber_id: &SubscriberId,
+        list_id: &ListId,
     ) -> Result<(), RepositoryError> {
         sqlx::query!(
             r#"
-        INSERT INTO subscriptions (id, name, email, subscribed_at, status)
-        VALUES ($1,$2,$3,$4,$5)
+        UPDATE list_subscriptions
+        SET status=$1, confirmed_at=COALESCE(confirmed_at, now())
+        WHERE subscriber_id=$2 AND list_id=$3
+        "#,
+            ConfirmationStatus::Confirmed.as_ref(),
+            subscriber_id.as_ref(),
+            list_id.as_ref()
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn get_subscriber_by_email_tx(
+        &self,
+        transaction: &mut Transaction<'_, Postgres>,
+        email: &SubscriberEmail,
+    ) -> Result<Option<Subscriber>, RepositoryError> {
+        let subscriber = sqlx::query_as!(
+            SubscriberRow,
+            r#"
+        SELECT s.id, s.email, s.name, s.status, s.subscribed_at, s.source, s.consent_version,
+               s.confirmed_at, s.confirmation_ip, s.confirmation_user_agent, s.attributes,
+               s.content_format, s.locale, s.tracking_opt_out,
+               COALESCE(array_agg(st.tag ORDER BY st.tag) FILTER (WHERE st.tag IS NOT NULL), '{}') AS "tags!"
+        FROM subscriptions s
+        LEFT JOIN subscriber_tags st ON st.subscriber_id = s.id
+        WHERE s.email = $1
+        GROUP BY s.id
+        "#,
+            email.as_ref()
+        )
+        .fetch_optional(&mut **transaction)
+        .await?
+        .map(Subscriber::try_from)
+        .transpose()?;
+
+        Ok(subscriber)
+    }
+
+    /// Adds the subscriber to the list unless already a member and returns the membership status.
+    /// Unsubscribed members have to confirm again.
+    #[tracing::instrument(skip_all)]
+    pub async fn insert_list_subscription_tx(
+        &self,
+        transaction: &mut Transaction<'_, Postgres>,
+        subscriber_id: &SubscriberId,
+        list_id: &ListId,
+    ) -> Result<ConfirmationStatus, RepositoryError> {
+        let status = sqlx::query!(
+            r#"
+        INSERT INTO list_subscriptions (list_id, subscriber_id, status, subscribed_at)
+        VALUES ($1, $2, $3, now())
+        ON CONFLICT (list_id, subscriber_id) DO UPDATE
+        SET status = CASE WHEN list_subscriptions.status = $4 THEN $3 ELSE list_subscriptions.status END
+        RETURNING status
+        "#,
+            list_id.as_ref(),
+            subscriber_id.as_ref(),
+            ConfirmationStatus::PendingConfirmation.as_ref(),
+            ConfirmationStatus::Unsubscribed.as_ref()
+        )
+        .fetch_one(&mut **transaction)
+        .await?
+        .status;
+
+        let status = status
+            .parse::<ConfirmationStatus>()
+            .map_err(|_| DomainError::from(format!("Unknown confirmation status {}", status)))?;
+
+        Ok(status)
+    }
+
+    /// Returns `false` without inserting when the email is already taken, also by a concurrent
+    /// transaction once it commits.
+    #[tracing::instrument(skip_all)]
+    pub async fn insert_subscriber_tx(
+        &self,
+        transaction: &mut Transaction<'_, Postgres>,
+        subscriber: &Subscriber,
+    ) -> Result<bool, RepositoryError> {
+        let result = sqlx::query!(
+            r#"
+        INSERT INTO subscriptions (id, name, email, subscribed_at, status, source, consent_version, attributes, content_format, locale)
+        VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9,$10)
+        ON CONFLICT (email) DO NOTHING
         "#,
             subscriber.id.as_ref(),
             subscriber.name.as_ref(),
             subscriber.email.as_ref(),
-            Utc::now(),
-            ConfirmationStatus::PendingConfirmation.as_ref()
+            subscriber.subscribed_at,
+            subscriber.status.as_ref(),
+            subscriber.source.as_ref(),
+            subscriber.consent_version,
+            subscriber.attributes,
+            subscriber.content_format.as_ref(),
+            subscriber.locale.as_ref().map(|x| x.as_ref())
         )
         .execute(&mut **transaction)
         .await
@@ -117,22 +356,24 @@ impl SqlxPostgresRepository {
             e
         })?;
 
-        Ok(())
+        Ok(result.rows_affected() == 1)
     }
 
     #[tracing::instrument(skip_all)]
     pub async fn store_token_tx(
         &self,
         transaction: &mut Transaction<'_, Postgres>,
-        subscriber: &Subscriber,
+        subscriber_id: &SubscriberId,
+        list_id: &ListId,
         token: &str,
     ) -> Result<(), RepositoryError> {
         sqlx::query!(
             r#"
-        INSERT INTO subscription_tokens (subscriber_id, subscription_token)
-        VALUES ($1, $2)
+        INSERT INTO subscription_tokens (subscriber_id, list_id, subscription_token)
+        VALUES ($1, $2, $3)
         "#,
-            &subscriber.id.as_ref().to_string(),
+            &subscriber_id.as_ref().to_string(),
+            list_id.as_ref(),
             token
         )
         .execute(&mut **transaction)
@@ -141,22 +382,1367 @@ impl SqlxPostgresRepository {
         Ok(())
     }
 
-    pub async fn user_by_credentials_exists(
+    #[tracing::instrument(skip_all)]
+    pub async fn get_lists(
         &self,
-        username: &str,
-        password_hash: &PasswordHash,
-    ) -> Result<bool, RepositoryError> {
+    ) -> Result<Vec<Result<MailingList, DomainError>>, RepositoryError> {
+        let lists = sqlx::query!("SELECT id, slug, name FROM lists ORDER BY created_at")
+            .fetch_all(&self.0)
+            .await?
+            .into_iter()
+            .map(|x| {
+                Ok(MailingList {
+                    id: ListId::from(x.id),
+                    slug: ListSlug::parse(x.slug)?,
+                    name: x.name,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        Ok(lists)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn get_list_id_by_slug(
+        &self,
+        slug: &ListSlug,
+    ) -> Result<Option<ListId>, RepositoryError> {
+        let id = sqlx::query!("SELECT id FROM lists WHERE slug=$1", slug.as_ref())
+            .fetch_optional(&self.0)
+            .await?
+            .map(|x| ListId::from(x.id));
+
+        Ok(id)
+    }
+
+    /// Returns `false` when a list with the same slug already exists.
+    #[tracing::instrument(skip_all)]
+    pub async fn insert_list(&self, list: &MailingList) -> Result<bool, RepositoryError> {
+        let result = sqlx::query!(
+            r#"
+        INSERT INTO lists (id, slug, name, created_at)
+        VALUES ($1, $2, $3, now())
+        ON CONFLICT (slug) DO NOTHING
+        "#,
+            list.id.as_ref(),
+            list.slug.as_ref(),
+            list.name
+        )
+        .execute(&self.0)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn store_preference_token_tx(
+        &self,
+        transaction: &mut Transaction<'_, Postgres>,
+        subscriber_id: &SubscriberId,
+        token: &str,
+    ) -> Result<(), RepositoryError> {
         sqlx::query!(
             r#"
-        SELECT * FROM users
-        WHERE username=$1 AND password_hash=$2
+        INSERT INTO preference_tokens (preference_token, subscriber_id, created_at)
+        VALUES ($1, $2, now())
         "#,
-            username,
-            password_hash.as_ref()
+            token,
+            subscriber_id.as_ref()
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn get_preference_token_tx(
+        &self,
+        transaction: &mut Transaction<'_, Postgres>,
+        subscriber_id: &SubscriberId,
+    ) -> Result<Option<String>, RepositoryError> {
+        let token = sqlx::query!(
+            "SELECT preference_token FROM preference_tokens WHERE subscriber_id=$1",
+            subscriber_id.as_ref()
+        )
+        .fetch_optional(&mut **transaction)
+        .await?
+        .map(|x| x.preference_token);
+
+        Ok(token)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn get_subscriber_id_by_preference_token(
+        &self,
+        token: &str,
+    ) -> Result<Option<SubscriberId>, RepositoryError> {
+        let id = sqlx::query!(
+            "SELECT subscriber_id FROM preference_tokens WHERE preference_token=$1",
+            token
         )
         .fetch_optional(&self.0)
-        .await
-        .map(|r| r.is_some())
-        .map_err(RepositoryError::Database)
+        .await?
+        .map(|x| SubscriberId::from(x.subscriber_id));
+
+        Ok(id)
+    }
+
+    /// Returns every list with the subscriber membership status.
+    #[tracing::instrument(skip_all)]
+    pub async fn get_subscriber_memberships(
+        &self,
+        subscriber_id: &SubscriberId,
+    ) -> Result<Vec<Result<ListMembership, DomainError>>, RepositoryError> {
+        let memberships = sqlx::query!(
+            r#"
+        SELECT l.id, l.slug, l.name, ls.status AS "status?"
+        FROM lists l
+        LEFT JOIN list_subscriptions ls ON ls.list_id = l.id AND ls.subscriber_id = $1
+        ORDER BY l.created_at
+        "#,
+            subscriber_id.as_ref()
+        )
+        .fetch_all(&self.0)
+        .await?
+        .into_iter()
+        .map(|x| {
+            let list = MailingList {
+                id: ListId::from(x.id),
+                slug: ListSlug::parse(x.slug)?,
+                name: x.name,
+            };
+            let status = x
+                .status
+                .map(|status| {
+                    status
+                        .parse::<ConfirmationStatus>()
+                        .map_err(|_| format!("Unknown confirmation status {}", status))
+                })
+                .transpose()?;
+            Ok(ListMembership { list, status })
+        })
+        .collect::<Vec<_>>();
+
+        Ok(memberships)
+    }
+
+    /// Applies preference center changes: the subscriber stays confirmed only in `list_ids`.
+    #[tracing::instrument(skip_all)]
+    pub async fn update_subscriber_preferences(
+        &self,
+        subscriber_id: &SubscriberId,
+        name: &SubscriberName,
+        content_format: ContentFormat,
+        tracking_opt_out: bool,
+        list_ids: &[ListId],
+    ) -> Result<(), RepositoryError> {
+        let list_ids = list_ids.iter().map(|x| *x.as_ref()).collect::<Vec<_>>();
+        let mut transaction = self.begin_transaction().await?;
+
+        sqlx::query!(
+            "UPDATE subscriptions SET name=$1, content_format=$2, tracking_opt_out=$3 WHERE id=$4",
+            name.as_ref(),
+            content_format.as_ref(),
+            tracking_opt_out,
+            subscriber_id.as_ref()
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        sqlx::query!(
+            r#"
+        UPDATE list_subscriptions SET status=$1
+        WHERE subscriber_id=$2 AND NOT (list_id = ANY($3))
+        "#,
+            ConfirmationStatus::Unsubscribed.as_ref(),
+            subscriber_id.as_ref(),
+            &list_ids
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        sqlx::query!(
+            r#"
+        INSERT INTO list_subscriptions (list_id, subscriber_id, status, subscribed_at, confirmed_at)
+        SELECT l.id, $2, $3, now(), now() FROM UNNEST($1::uuid[]) AS l(id)
+        ON CONFLICT (list_id, subscriber_id) DO UPDATE
+        SET status = $3, confirmed_at = COALESCE(list_subscriptions.confirmed_at, now())
+        "#,
+            &list_ids,
+            subscriber_id.as_ref(),
+            ConfirmationStatus::Confirmed.as_ref()
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn unsubscribe_subscriber(
+        &self,
+        subscriber_id: &SubscriberId,
+    ) -> Result<(), RepositoryError> {
+        let mut transaction = self.begin_transaction().await?;
+
+        sqlx::query!(
+            "UPDATE list_subscriptions SET status=$1 WHERE subscriber_id=$2",
+            ConfirmationStatus::Unsubscribed.as_ref(),
+            subscriber_id.as_ref()
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        let email = sqlx::query!(
+            "UPDATE subscriptions SET status=$1 WHERE id=$2 RETURNING email",
+            ConfirmationStatus::Unsubscribed.as_ref(),
+            subscriber_id.as_ref()
+        )
+        .fetch_one(&mut *transaction)
+        .await?
+        .email;
+
+        let email = SubscriberEmail::parse(email)?;
+        insert_suppression_tx(
+            &mut transaction,
+            &SuppressionTarget::from(&email),
+            SuppressionReason::Unsubscribed,
+        )
+        .await?;
+
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn email_exists(&self, email: &SubscriberEmail) -> Result<bool, RepositoryError> {
+        let exists = sqlx::query!(
+            "SELECT id FROM subscriptions WHERE email=$1",
+            email.as_ref()
+        )
+        .fetch_optional(&self.0)
+        .await?
+        .is_some();
+
+        Ok(exists)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn insert_email_change_tx(
+        &self,
+        transaction: &mut Transaction<'_, Postgres>,
+        subscriber_id: &SubscriberId,
+        old_email: &SubscriberEmail,
+        new_email: &SubscriberEmail,
+        token: &str,
+    ) -> Result<(), RepositoryError> {
+        sqlx::query!(
+            r#"
+        INSERT INTO email_changes (id, subscriber_id, old_email, new_email, change_token, requested_at)
+        VALUES ($1, $2, $3, $4, $5, now())
+        "#,
+            Uuid::now_v7(),
+            subscriber_id.as_ref(),
+            old_email.as_ref(),
+            new_email.as_ref(),
+            token
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Switches the subscriber to the requested address. Returns `false` for unknown or already
+    /// used tokens and a domain error when the address has been taken in the meantime.
+    #[tracing::instrument(skip_all)]
+    pub async fn apply_email_change(&self, token: &str) -> Result<bool, RepositoryError> {
+        let mut transaction = self.begin_transaction().await?;
+
+        let change = sqlx::query!(
+            r#"
+        SELECT id, subscriber_id, new_email FROM email_changes
+        WHERE change_token=$1 AND confirmed_at IS NULL
+        FOR UPDATE
+        "#,
+            token
+        )
+        .fetch_optional(&mut *transaction)
+        .await?;
+
+        let Some(change) = change else {
+            return Ok(false);
+        };
+
+        let updated = sqlx::query!(
+            "UPDATE subscriptions SET email=$1 WHERE id=$2",
+            change.new_email,
+            change.subscriber_id
+        )
+        .execute(&mut *transaction)
+        .await;
+
+        match updated {
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+                return Err(DomainError::from(format!(
+                    "{} is already used by another subscriber",
+                    change.new_email
+                ))
+                .into());
+            }
+            updated => updated?,
+        };
+
+        sqlx::query!(
+            "UPDATE email_changes SET confirmed_at=now() WHERE id=$1",
+            change.id
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        transaction.commit().await?;
+        Ok(true)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn subscriber_exists(
+        &self,
+        subscriber_id: &SubscriberId,
+    ) -> Result<bool, RepositoryError> {
+        let exists = sqlx::query!(
+            "SELECT id FROM subscriptions WHERE id=$1",
+            subscriber_id.as_ref()
+        )
+        .fetch_optional(&self.0)
+        .await?
+        .is_some();
+
+        Ok(exists)
+    }
+
+    /// Tags every existing subscriber of `subscriber_ids` with every tag, returns the number of
+    /// new subscriber-tag pairs.
+    #[tracing::instrument(skip_all)]
+    pub async fn add_tags(
+        &self,
+        subscriber_ids: &[SubscriberId],
+        tags: &[Tag],
+    ) -> Result<u64, RepositoryError> {
+        let subscriber_ids = subscriber_ids
+            .iter()
+            .map(|x| *x.as_ref())
+            .collect::<Vec<_>>();
+        let result = sqlx::query!(
+            r#"
+        INSERT INTO subscriber_tags (subscriber_id, tag, created_at)
+        SELECT s.id, t.tag, now()
+        FROM subscriptions s CROSS JOIN UNNEST($2::text[]) AS t(tag)
+        WHERE s.id = ANY($1)
+        ON CONFLICT DO NOTHING
+        "#,
+            &subscriber_ids,
+            &tags_to_strings(tags)
+        )
+        .execute(&self.0)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Returns the number of removed subscriber-tag pairs.
+    #[tracing::instrument(skip_all)]
+    pub async fn remove_tags(
+        &self,
+        subscriber_ids: &[SubscriberId],
+        tags: &[Tag],
+    ) -> Result<u64, RepositoryError> {
+        let subscriber_ids = subscriber_ids
+            .iter()
+            .map(|x| *x.as_ref())
+            .collect::<Vec<_>>();
+        let result = sqlx::query!(
+            "DELETE FROM subscriber_tags WHERE subscriber_id = ANY($1) AND tag = ANY($2)",
+            &subscriber_ids,
+            &tags_to_strings(tags)
+        )
+        .execute(&self.0)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Merges the given object into the subscriber attributes, `null` values remove keys.
+    /// Returns `false` when the subscriber doesn't exist.
+    #[tracing::instrument(skip_all)]
+    pub async fn merge_subscriber_attributes(
+        &self,
+        subscriber_id: &SubscriberId,
+        attributes: &serde_json::Value,
+    ) -> Result<bool, RepositoryError> {
+        let result = sqlx::query!(
+            "UPDATE subscriptions SET attributes = jsonb_strip_nulls(attributes || $1) WHERE id=$2",
+            attributes,
+            subscriber_id.as_ref()
+        )
+        .execute(&self.0)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Returns `false` when a segment with the same name already exists.
+    #[tracing::instrument(skip_all)]
+    pub async fn insert_segment(&self, segment: &Segment) -> Result<bool, RepositoryError> {
+        let result = sqlx::query!(
+            r#"
+        INSERT INTO segments (id, name, filter, created_at)
+        VALUES ($1, $2, $3, now())
+        ON CONFLICT (name) DO NOTHING
+        "#,
+            segment.id.as_ref(),
+            segment.name,
+            segment.filter.as_ref()
+        )
+        .execute(&self.0)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn get_segments(&self) -> Result<Vec<Result<Segment, DomainError>>, RepositoryError> {
+        let segments = sqlx::query!("SELECT id, name, filter FROM segments ORDER BY created_at")
+            .fetch_all(&self.0)
+            .await?
+            .into_iter()
+            .map(|x| {
+                Ok(Segment {
+                    id: SegmentId::from(x.id),
+                    name: x.name,
+                    filter: SegmentFilter::parse(x.filter)?,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        Ok(segments)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn get_segment(&self, id: &SegmentId) -> Result<Option<Segment>, RepositoryError> {
+        let segment = sqlx::query!(
+            "SELECT id, name, filter FROM segments WHERE id=$1",
+            id.as_ref()
+        )
+        .fetch_optional(&self.0)
+        .await?
+        .map(|x| {
+            Ok::<_, DomainError>(Segment {
+                id: SegmentId::from(x.id),
+                name: x.name,
+                filter: SegmentFilter::parse(x.filter)?,
+            })
+        })
+        .transpose()?;
+
+        Ok(segment)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn get_email_templates(
+        &self,
+    ) -> Result<Vec<Result<EmailTemplate, DomainError>>, RepositoryError> {
+        let templates = sqlx::query!(
+            "SELECT name, locale, kind, subject, html_body, text_body FROM email_templates ORDER BY name, locale"
+        )
+        .fetch_all(&self.0)
+        .await?
+        .into_iter()
+        .map(|x| {
+            Ok(EmailTemplate {
+                name: TemplateName::parse(x.name)?,
+                locale: Locale::parse(x.locale)?,
+                kind: x
+                    .kind
+                    .parse::<TemplateKind>()
+                    .map_err(|_| format!("Unknown template kind {}", x.kind))?,
+                subject: x.subject,
+                html_body: x.html_body,
+                text_body: x.text_body,
+            })
+        })
+        .collect::<Vec<_>>();
+
+        Ok(templates)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn insert_issue(&self, issue: &NewsletterIssue) -> Result<(), RepositoryError> {
+        let mut transaction = self.begin_transaction().await?;
+
+        sqlx::query!(
+            r#"
+        INSERT INTO newsletter_issues (id, title, html_content, text_content, tracking_enabled, created_at)
+        VALUES ($1, $2, $3, $4, $5, now())
+        "#,
+            issue.id.as_ref(),
+            issue.title,
+            issue.html_content,
+            issue.text_content,
+            issue.tracking_enabled
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        for (asset_ids, inline) in [(&issue.attachments, false), (&issue.inline_images, true)] {
+            let asset_ids = asset_ids.iter().map(|x| *x.as_ref()).collect::<Vec<_>>();
+            sqlx::query!(
+                r#"
+        INSERT INTO issue_assets (issue_id, asset_id, inline)
+        SELECT $1, a.id, $3 FROM UNNEST($2::uuid[]) AS a(id)
+        "#,
+                issue.id.as_ref(),
+                &asset_ids,
+                inline
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn get_issue(
+        &self,
+        issue_id: &IssueId,
+    ) -> Result<Option<NewsletterIssue>, RepositoryError> {
+        let issue = sqlx::query!(
+            r#"
+        SELECT i.id, i.title, i.html_content, i.text_content, i.tracking_enabled,
+               COALESCE(array_agg(ia.asset_id ORDER BY ia.asset_id) FILTER (WHERE NOT ia.inline), '{}') AS "attachments!",
+               COALESCE(array_agg(ia.asset_id ORDER BY ia.asset_id) FILTER (WHERE ia.inline), '{}') AS "inline_images!"
+        FROM newsletter_issues i
+        LEFT JOIN issue_assets ia ON ia.issue_id = i.id
+        WHERE i.id = $1
+        GROUP BY i.id
+        "#,
+            issue_id.as_ref()
+        )
+        .fetch_optional(&self.0)
+        .await?
+        .map(|x| NewsletterIssue {
+            id: IssueId::from(x.id),
+            title: x.title,
+            html_content: x.html_content,
+            text_content: x.text_content,
+            tracking_enabled: x.tracking_enabled,
+            attachments: x.attachments.into_iter().map(AssetId::from).collect(),
+            inline_images: x.inline_images.into_iter().map(AssetId::from).collect(),
+        });
+
+        Ok(issue)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn insert_asset(&self, asset: &Asset) -> Result<(), RepositoryError> {
+        sqlx::query!(
+            r#"
+        INSERT INTO assets (id, filename, content_type, content, created_at)
+        VALUES ($1, $2, $3, $4, now())
+        "#,
+            asset.id.as_ref(),
+            asset.filename,
+            asset.content_type,
+            asset.content
+        )
+        .execute(&self.0)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns the existing assets among `asset_ids`.
+    #[tracing::instrument(skip_all)]
+    pub async fn get_assets(&self, asset_ids: &[AssetId]) -> Result<Vec<Asset>, RepositoryError> {
+        let asset_ids = asset_ids.iter().map(|x| *x.as_ref()).collect::<Vec<_>>();
+
+        let assets = sqlx::query!(
+            r#"
+        SELECT id, filename, content_type, content FROM assets
+        WHERE id = ANY($1)
+        ORDER BY id
+        "#,
+            &asset_ids
+        )
+        .fetch_all(&self.0)
+        .await?
+        .into_iter()
+        .map(|x| Asset {
+            id: AssetId::from(x.id),
+            filename: x.filename,
+            content_type: x.content_type,
+            content: x.content,
+        })
+        .collect();
+
+        Ok(assets)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn mark_issue_published(&self, issue_id: &IssueId) -> Result<(), RepositoryError> {
+        sqlx::query!(
+            "UPDATE newsletter_issues SET published_at = COALESCE(published_at, now()) WHERE id = $1",
+            issue_id.as_ref()
+        )
+        .execute(&self.0)
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn upsert_email_template(
+        &self,
+        template: &EmailTemplate,
+    ) -> Result<(), RepositoryError> {
+        sqlx::query!(
+            r#"
+        INSERT INTO email_templates (name, locale, kind, subject, html_body, text_body, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $6, now())
+        ON CONFLICT (name, locale) DO UPDATE
+        SET kind = $3, subject = $4, html_body = $5, text_body = $6, updated_at = now()
+        "#,
+            template.name.as_ref(),
+            template.locale.as_ref(),
+            template.kind.as_ref(),
+            template.subject,
+            template.html_body,
+            template.text_body
+        )
+        .execute(&self.0)
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn insert_tracking_event(
+        &self,
+        issue_id: &IssueId,
+        subscriber_id: &SubscriberId,
+        kind: TrackingEventKind,
+        url: Option<&str>,
+    ) -> Result<(), RepositoryError> {
+        sqlx::query!(
+            r#"
+        INSERT INTO tracking_events (id, issue_id, subscriber_id, kind, url, occurred_at)
+        VALUES ($1, $2, $3, $4, $5, now())
+        "#,
+            Uuid::now_v7(),
+            issue_id.as_ref(),
+            subscriber_id.as_ref(),
+            kind.as_ref(),
+            url
+        )
+        .execute(&self.0)
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn get_issue_stats(&self, issue_id: &IssueId) -> Result<IssueStats, RepositoryError> {
+        let stats = sqlx::query_as!(
+            IssueStats,
+            r#"
+        SELECT COUNT(*) FILTER (WHERE kind = $2) AS "opens!",
+               COUNT(DISTINCT subscriber_id) FILTER (WHERE kind = $2) AS "unique_opens!",
+               COUNT(*) FILTER (WHERE kind = $3) AS "clicks!",
+               COUNT(DISTINCT subscriber_id) FILTER (WHERE kind = $3) AS "unique_clicks!"
+        FROM tracking_events
+        WHERE issue_id = $1
+        "#,
+            issue_id.as_ref(),
+            TrackingEventKind::Open.as_ref(),
+            TrackingEventKind::Click.as_ref()
+        )
+        .fetch_one(&self.0)
+        .await?;
+
+        Ok(stats)
+    }
+
+    /// Queues a delivery per subscriber and returns the queued subscribers. Failed deliveries
+    /// are queued again as another attempt, all others are left alone, so publishing an issue
+    /// again only retries the failed ones.
+    #[tracing::instrument(skip_all)]
+    pub async fn queue_deliveries(
+        &self,
+        issue_id: &IssueId,
+        subscriber_ids: &[SubscriberId],
+    ) -> Result<Vec<SubscriberId>, RepositoryError> {
+        let subscriber_ids = subscriber_ids
+            .iter()
+            .map(|x| *x.as_ref())
+            .collect::<Vec<_>>();
+
+        let queued = sqlx::query!(
+            r#"
+        INSERT INTO deliveries (issue_id, subscriber_id, status, attempts, queued_at, updated_at)
+        SELECT $1, s.id, $3, 1, now(), now() FROM UNNEST($2::uuid[]) AS s(id)
+        ON CONFLICT (issue_id, subscriber_id) DO UPDATE
+        SET status = $3, provider_message_id = NULL, error = NULL,
+            attempts = deliveries.attempts + 1, queued_at = now(), updated_at = now()
+        WHERE deliveries.status = $4
+        RETURNING subscriber_id
+        "#,
+            issue_id.as_ref(),
+            &subscriber_ids,
+            DeliveryStatus::Queued.as_ref(),
+            DeliveryStatus::Failed.as_ref()
+        )
+        .fetch_all(&self.0)
+        .await?
+        .into_iter()
+        .map(|x| SubscriberId::from(x.subscriber_id))
+        .collect();
+
+        Ok(queued)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn record_deliveries(
+        &self,
+        issue_id: &IssueId,
+        updates: &[DeliveryUpdate],
+    ) -> Result<(), RepositoryError> {
+        let subscriber_ids = updates
+            .iter()
+            .map(|x| *x.subscriber_id.as_ref())
+            .collect::<Vec<_>>();
+        let statuses = updates
+            .iter()
+            .map(|x| x.status.as_ref().to_owned())
+            .collect::<Vec<_>>();
+        let message_ids = updates
+            .iter()
+            .map(|x| x.provider_message_id.clone())
+            .collect::<Vec<_>>();
+        let errors = updates.iter().map(|x| x.error.clone()).collect::<Vec<_>>();
+
+        sqlx::query!(
+            r#"
+        UPDATE deliveries d
+        SET status = u.status, provider_message_id = u.provider_message_id, error = u.error,
+            sent_at = CASE WHEN u.status = $6 THEN now() ELSE d.sent_at END, updated_at = now()
+        FROM UNNEST($2::uuid[], $3::text[], $4::text[], $5::text[])
+            AS u(subscriber_id, status, provider_message_id, error)
+        WHERE d.issue_id = $1 AND d.subscriber_id = u.subscriber_id
+        "#,
+            issue_id.as_ref(),
+            &subscriber_ids,
+            &statuses,
+            &message_ids as &[Option<String>],
+            &errors as &[Option<String>],
+            DeliveryStatus::Sent.as_ref()
+        )
+        .execute(&self.0)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns the deliveries of the issue, optionally only those with `status`.
+    #[tracing::instrument(skip_all)]
+    pub async fn get_deliveries(
+        &self,
+        issue_id: &IssueId,
+        status: Option<DeliveryStatus>,
+    ) -> Result<Vec<Result<Delivery, DomainError>>, RepositoryError> {
+        let deliveries = sqlx::query!(
+            r#"
+        SELECT d.issue_id, d.subscriber_id, s.email, d.status, d.provider_message_id, d.error,
+               d.attempts, d.queued_at, d.sent_at, d.updated_at
+        FROM deliveries d
+        JOIN subscriptions s ON s.id = d.subscriber_id
+        WHERE d.issue_id = $1 AND ($2::text IS NULL OR d.status = $2)
+        ORDER BY d.queued_at, s.email
+        "#,
+            issue_id.as_ref(),
+            status.as_ref().map(|x| x.as_ref())
+        )
+        .fetch_all(&self.0)
+        .await?
+        .into_iter()
+        .map(|x| {
+            Ok(Delivery {
+                issue_id: IssueId::from(x.issue_id),
+                subscriber_id: SubscriberId::from(x.subscriber_id),
+                email: SubscriberEmail::parse(x.email)?,
+                status: x.status.parse::<DeliveryStatus>().map_err(|_| {
+                    DomainError::from(format!("Unknown delivery status {}", x.status))
+                })?,
+                provider_message_id: x.provider_message_id,
+                error: x.error,
+                attempts: x.attempts,
+                queued_at: x.queued_at,
+                sent_at: x.sent_at,
+                updated_at: x.updated_at,
+            })
+        })
+        .collect::<Vec<_>>();
+
+        Ok(deliveries)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn get_delivery_summary(
+        &self,
+        issue_id: &IssueId,
+    ) -> Result<DeliverySummary, RepositoryError> {
+        let summary = sqlx::query_as!(
+            DeliverySummary,
+            r#"
+        SELECT COUNT(*) FILTER (WHERE status = $2) AS "queued!",
+               COUNT(*) FILTER (WHERE status = $3) AS "sent!",
+               COUNT(*) FILTER (WHERE status = $4) AS "failed!",
+               COUNT(*) FILTER (WHERE status = $5) AS "bounced!",
+               COUNT(*) FILTER (WHERE status = $6) AS "suppressed!"
+        FROM deliveries
+        WHERE issue_id = $1
+        "#,
+            issue_id.as_ref(),
+            DeliveryStatus::Queued.as_ref(),
+            DeliveryStatus::Sent.as_ref(),
+            DeliveryStatus::Failed.as_ref(),
+            DeliveryStatus::Bounced.as_ref(),
+            DeliveryStatus::Suppressed.as_ref()
+        )
+        .fetch_one(&self.0)
+        .await?;
+
+        Ok(summary)
+    }
+
+    /// Marks the delivery with the provider message id as bounced, or without one the latest
+    /// sent delivery to the address.
+    #[tracing::instrument(skip_all)]
+    pub async fn mark_delivery_bounced(
+        &self,
+        email: &SubscriberEmail,
+        provider_message_id: Option<&str>,
+    ) -> Result<(), RepositoryError> {
+        sqlx::query!(
+            r#"
+        UPDATE deliveries SET status = $3, updated_at = now()
+        WHERE (issue_id, subscriber_id) = (
+            SELECT d.issue_id, d.subscriber_id FROM deliveries d
+            JOIN subscriptions s ON s.id = d.subscriber_id
+            WHERE d.status = $4 AND CASE WHEN $2::text IS NULL
+                THEN lower(s.email) = lower($1)
+                ELSE d.provider_message_id = $2 END
+            ORDER BY d.sent_at DESC
+            LIMIT 1
+        )
+        "#,
+            email.as_ref(),
+            provider_message_id,
+            DeliveryStatus::Bounced.as_ref(),
+            DeliveryStatus::Sent.as_ref()
+        )
+        .execute(&self.0)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Suppresses the address and updates the status of the matching subscriber, if any.
+    #[tracing::instrument(skip_all)]
+    pub async fn suppress_address(
+        &self,
+        email: &SubscriberEmail,
+        reason: SuppressionReason,
+    ) -> Result<(), RepositoryError> {
+        let mut transaction = self.begin_transaction().await?;
+        let status = reason.subscriber_status();
+
+        insert_suppression_tx(&mut transaction, &SuppressionTarget::from(email), reason).await?;
+
+        let subscriber_id = sqlx::query!(
+            "UPDATE subscriptions SET status=$1 WHERE lower(email)=lower($2) RETURNING id",
+            status.as_ref(),
+            email.as_ref()
+        )
+        .fetch_optional(&mut *transaction)
+        .await?
+        .map(|x| x.id);
+
+        if let Some(subscriber_id) = subscriber_id {
+            sqlx::query!(
+                "UPDATE list_subscriptions SET status=$1 WHERE subscriber_id=$2",
+                status.as_ref(),
+                subscriber_id
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    /// Counts a soft bounce of the address and returns the new count.
+    #[tracing::instrument(skip_all)]
+    pub async fn increment_soft_bounces(
+        &self,
+        email: &SubscriberEmail,
+    ) -> Result<Option<i32>, RepositoryError> {
+        let count = sqlx::query!(
+            r#"
+        UPDATE subscriptions SET soft_bounce_count = soft_bounce_count + 1
+        WHERE lower(email)=lower($1)
+        RETURNING soft_bounce_count
+        "#,
+            email.as_ref()
+        )
+        .fetch_optional(&self.0)
+        .await?
+        .map(|x| x.soft_bounce_count);
+
+        Ok(count)
+    }
+
+    /// Delivered mail clears earlier soft bounces, only consecutive ones count toward suppression.
+    #[tracing::instrument(skip_all)]
+    pub async fn reset_soft_bounces(&self, email: &SubscriberEmail) -> Result<(), RepositoryError> {
+        sqlx::query!(
+            "UPDATE subscriptions SET soft_bounce_count = 0 WHERE lower(email)=lower($1)",
+            email.as_ref()
+        )
+        .execute(&self.0)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Whether the address or its domain is suppressed.
+    #[tracing::instrument(skip_all)]
+    pub async fn is_suppressed(&self, email: &SubscriberEmail) -> Result<bool, RepositoryError> {
+        let address = SuppressionTarget::from(email);
+        let domain = address.as_ref().rsplit('@').next().unwrap_or_default();
+
+        let suppressed = sqlx::query!(
+            r#"SELECT EXISTS (SELECT 1 FROM suppressions WHERE value = $1 OR value = $2) AS "suppressed!""#,
+            address.as_ref(),
+            domain
+        )
+        .fetch_one(&self.0)
+        .await?
+        .suppressed;
+
+        Ok(suppressed)
+    }
+
+    /// Returns `false` when the value is already suppressed.
+    #[tracing::instrument(skip_all)]
+    pub async fn insert_suppression(
+        &self,
+        target: &SuppressionTarget,
+        reason: SuppressionReason,
+    ) -> Result<bool, RepositoryError> {
+        let mut transaction = self.begin_transaction().await?;
+        let inserted = insert_suppression_tx(&mut transaction, target, reason).await?;
+        transaction.commit().await?;
+
+        Ok(inserted)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn get_suppressions(
+        &self,
+    ) -> Result<Vec<Result<Suppression, DomainError>>, RepositoryError> {
+        let suppressions =
+            sqlx::query!("SELECT value, reason, created_at FROM suppressions ORDER BY created_at")
+                .fetch_all(&self.0)
+                .await?
+                .into_iter()
+                .map(|x| {
+                    Ok(Suppression {
+                        target: SuppressionTarget::parse(x.value)?,
+                        reason: x.reason.parse::<SuppressionReason>().map_err(|_| {
+                            DomainError::from(format!("Unknown suppression reason {}", x.reason))
+                        })?,
+                        created_at: x.created_at,
+                    })
+                })
+                .collect::<Vec<_>>();
+
+        Ok(suppressions)
+    }
+
+    /// Returns `false` when the value wasn't suppressed.
+    #[tracing::instrument(skip_all)]
+    pub async fn delete_suppression(
+        &self,
+        target: &SuppressionTarget,
+    ) -> Result<bool, RepositoryError> {
+        let result = sqlx::query!("DELETE FROM suppressions WHERE value=$1", target.as_ref())
+            .execute(&self.0)
+            .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn insert_outbox_email_tx(
+        &self,
+        transaction: &mut Transaction<'_, Postgres>,
+        email: &OutboxEmail,
+    ) -> Result<(), RepositoryError> {
+        sqlx::query!(
+            r#"
+        INSERT INTO email_outbox
+            (id, recipient, subject, html_content, text_content, tag, created_at, next_attempt_at)
+        VALUES ($1, $2, $3, $4, $5, $6, now(), now())
+        "#,
+            email.id.as_ref(),
+            email.recipient.as_ref(),
+            email.subject,
+            email.html_content,
+            email.text_content,
+            email.tag
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Claims due emails for `lease`, so other relays skip them while they're sent. The attempt
+    /// is counted right away, an email that crashes the relay is retried after the lease.
+    #[tracing::instrument(skip_all)]
+    pub async fn claim_outbox_emails(
+        &self,
+        limit: i64,
+        lease: Duration,
+        max_attempts: i32,
+    ) -> Result<Vec<Result<OutboxEmail, DomainError>>, RepositoryError> {
+        let emails = sqlx::query!(
+            r#"
+        UPDATE email_outbox
+        SET attempts = attempts + 1, next_attempt_at = now() + make_interval(secs => $2)
+        WHERE id IN (
+            SELECT id FROM email_outbox
+            WHERE sent_at IS NULL AND next_attempt_at <= now() AND attempts < $3
+            ORDER BY next_attempt_at
+            LIMIT $1
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING id, recipient, subject, html_content, text_content, tag, attempts
+        "#,
+            limit,
+            lease.num_seconds() as f64,
+            max_attempts
+        )
+        .fetch_all(&self.0)
+        .await?
+        .into_iter()
+        .map(|x| {
+            Ok(OutboxEmail {
+                id: OutboxEmailId::from(x.id),
+                recipient: SubscriberEmail::parse(x.recipient)?,
+                subject: x.subject,
+                html_content: x.html_content,
+                text_content: x.text_content,
+                tag: x.tag,
+                attempts: x.attempts,
+            })
+        })
+        .collect();
+
+        Ok(emails)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn mark_outbox_email_sent(&self, id: &OutboxEmailId) -> Result<(), RepositoryError> {
+        sqlx::query!(
+            "UPDATE email_outbox SET sent_at = now(), last_error = NULL WHERE id = $1",
+            id.as_ref()
+        )
+        .execute(&self.0)
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn mark_outbox_email_failed(
+        &self,
+        id: &OutboxEmailId,
+        error: &str,
+        retry_at: DateTime<Utc>,
+    ) -> Result<(), RepositoryError> {
+        sqlx::query!(
+            "UPDATE email_outbox SET last_error = $2, next_attempt_at = $3 WHERE id = $1",
+            id.as_ref(),
+            error,
+            retry_at
+        )
+        .execute(&self.0)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn user_by_credentials_exists(
+        &self,
+        username: &str,
+        password_hash: &PasswordHash,
+    ) -> Result<bool, RepositoryError> {
+        sqlx::query!(
+            r#"
+        SELECT * FROM users
+        WHERE username=$1 AND password_hash=$2
+        "#,
+            username,
+            password_hash.as_ref()
+        )
+        .fetch_optional(&self.0)
+        .await
+        .map(|r| r.is_some())
+        .map_err(RepositoryError::Database)
+    }
+}
+
+async fn insert_suppression_tx(
+    transaction: &mut Transaction<'_, Postgres>,
+    target: &SuppressionTarget,
+    reason: SuppressionReason,
+) -> Result<bool, RepositoryError> {
+    let result = sqlx::query!(
+        r#"
+    INSERT INTO suppressions (value, reason, created_at)
+    VALUES ($1, $2, now())
+    ON CONFLICT (value) DO NOTHING
+    "#,
+        target.as_ref(),
+        reason.as_ref()
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+/// Appends the joins and conditions selecting the audience to a query selecting from
+/// `subscriptions s`.
+fn push_confirmed_audience(query: &mut QueryBuilder<'_, Postgres>, audience: &Audience) {
+    let list_ids = audience
+        .list_ids
+        .iter()
+        .map(|x| *x.as_ref())
+        .collect::<Vec<_>>();
+
+    query
+        .push("JOIN list_subscriptions ls ON ls.subscriber_id = s.id WHERE ls.status = ")
+        .push_bind(ConfirmationStatus::Confirmed.as_ref().to_owned())
+        .push(" AND ls.list_id = ANY(")
+        .push_bind(list_ids)
+        .push(")");
+
+    if !audience.include_tags.is_empty() {
+        query
+            .push(" AND NOT EXISTS (SELECT 1 FROM unnest(")
+            .push_bind(tags_to_strings(&audience.include_tags))
+            .push("::text[]) AS t(tag) WHERE NOT EXISTS (SELECT 1 FROM subscriber_tags st WHERE st.subscriber_id = s.id AND st.tag = t.tag))");
+    }
+    if !audience.exclude_tags.is_empty() {
+        query
+            .push(" AND NOT EXISTS (SELECT 1 FROM subscriber_tags st WHERE st.subscriber_id = s.id AND st.tag = ANY(")
+            .push_bind(tags_to_strings(&audience.exclude_tags))
+            .push("))");
+    }
+
+    let conditions = audience.segment.as_ref().map(|x| x.conditions());
+    for condition in conditions.unwrap_or_default() {
+        query.push(" AND ");
+        push_segment_condition(query, condition);
+    }
+}
+
+fn tags_to_strings(tags: &[Tag]) -> Vec<String> {
+    tags.iter().map(|x| x.as_ref().to_owned()).collect()
+}
+
+fn push_segment_condition(query: &mut QueryBuilder<'_, Postgres>, condition: &SegmentCondition) {
+    match condition {
+        SegmentCondition::Attribute {
+            key,
+            operator,
+            value,
+        } => {
+            let sql_operator = match operator {
+                ComparisonOperator::Eq | ComparisonOperator::NotEq => "=",
+                ComparisonOperator::Gt => ">",
+                ComparisonOperator::Gte => ">=",
+                ComparisonOperator::Lt => "<",
+                ComparisonOperator::Lte => "<=",
+            };
+            // Missing keys and mismatched JSON types never match, so `!=` is `(=) IS NOT TRUE`
+            query.push("(");
+            match value {
+                SegmentValue::Text(text) => {
+                    query
+                        .push("s.attributes ->> ")
+                        .push_bind(key.clone())
+                        .push(format!(" {} ", sql_operator))
+                        .push_bind(text.clone());
+                }
+                SegmentValue::Number(number) => {
+                    query
+                        .push("CASE WHEN jsonb_typeof(s.attributes -> ")
+                        .push_bind(key.clone())
+                        .push(") = 'number' THEN (s.attributes ->> ")
+                        .push_bind(key.clone())
+                        .push(format!(")::float8 {} ", sql_operator))
+                        .push_bind(*number)
+                        .push(" END");
+                }
+                SegmentValue::Bool(flag) => {
+                    query
+                        .push("s.attributes -> ")
+                        .push_bind(key.clone())
+                        .push(" = to_jsonb(")
+                        .push_bind(*flag)
+                        .push(")");
+                }
+            }
+            query.push(")");
+            if *operator == ComparisonOperator::NotEq {
+                query.push(" IS NOT TRUE");
+            }
+        }
+        SegmentCondition::HasTag(tag) => {
+            query
+                .push("EXISTS (SELECT 1 FROM subscriber_tags st WHERE st.subscriber_id = s.id AND st.tag = ")
+                .push_bind(tag.clone())
+                .push(")");
+        }
+        SegmentCondition::LacksTag(tag) => {
+            query
+                .push("NOT EXISTS (SELECT 1 FROM subscriber_tags st WHERE st.subscriber_id = s.id AND st.tag = ")
+                .push_bind(tag.clone())
+                .push(")");
+        }
+        SegmentCondition::JoinedBefore(date) => {
+            query
+                .push("s.subscribed_at < ")
+                .push_bind(date.and_time(NaiveTime::MIN).and_utc());
+        }
+        SegmentCondition::JoinedAfter(date) => {
+            query.push("s.subscribed_at >= ").push_bind(
+                date.succ_opt()
+                    .unwrap_or(*date)
+                    .and_time(NaiveTime::MIN)
+                    .and_utc(),
+            );
+        }
+    }
+}
+
+struct SubscriberRow {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+    source: String,
+    consent_version: Option<String>,
+    confirmed_at: Option<DateTime<Utc>>,
+    confirmation_ip: Option<String>,
+    confirmation_user_agent: Option<String>,
+    attributes: serde_json::Value,
+    content_format: String,
+    locale: Option<String>,
+    tracking_opt_out: bool,
+    tags: Vec<String>,
+}
+
+impl TryFrom<SubscriberRow> for Subscriber {
+    type Error = DomainError;
+
+    fn try_from(x: SubscriberRow) -> Result<Self, Self::Error> {
+        let status = x
+            .status
+            .parse::<ConfirmationStatus>()
+            .map_err(|_| format!("Unknown confirmation status {}", x.status))?;
+        let source = x
+            .source
+            .parse::<SubscriptionSource>()
+            .map_err(|_| format!("Unknown subscription source {}", x.source))?;
+        let content_format = x
+            .content_format
+            .parse::<ContentFormat>()
+            .map_err(|_| format!("Unknown content format {}", x.content_format))?;
+        let confirmation = x.confirmed_at.map(|confirmed_at| ConsentConfirmation {
+            confirmed_at,
+            ip: x.confirmation_ip,
+            user_agent: x.confirmation_user_agent,
+        });
+
+        Ok(Subscriber {
+            id: SubscriberId::from(x.id),
+            email: SubscriberEmail::parse(x.email)?,
+            name: SubscriberName::parse(x.name)?,
+            status,
+            subscribed_at: x.subscribed_at,
+            source,
+            consent_version: x.consent_version,
+            confirmation,
+            attributes: x.attributes,
+            content_format,
+            locale: x.locale.map(Locale::parse).transpose()?,
+            tracking_opt_out: x.tracking_opt_out,
+            tags: x
+                .tags
+                .into_iter()
+                .map(Tag::parse)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct RecipientRow {
+    id: Uuid,
+    email: String,
+    name: String,
+    content_format: String,
+    attributes: serde_json::Value,
+    locale: Option<String>,
+    tracking_opt_out: bool,
+    preference_token: String,
+}
+
+impl TryFrom<RecipientRow> for Recipient {
+    type Error = DomainError;
+
+    fn try_from(x: RecipientRow) -> Result<Self, Self::Error> {
+        Ok(Recipient {
+            id: SubscriberId::from(x.id),
+            email: SubscriberEmail::parse(x.email)?,
+            name: SubscriberName::parse(x.name)?,
+            content_format: x
+                .content_format
+                .parse::<ContentFormat>()
+                .map_err(|_| format!("Unknown content format {}", x.content_format))?,
+            preference_token: x.preference_token,
+            attributes: x.attributes,
+            locale: x.locale.map(Locale::parse).transpose()?,
+            tracking_opt_out: x.tracking_opt_out,
+        })
     }
 }
@@ -0,0 +1,278 @@
+use crate::app_config::DeliveryConfig;
+use crate::app_state::AppState;
+use crate::domain::entities::delivery::DeliveryUpdate;
+use crate::domain::entities::newsletter_issue::NewsletterIssue;
+use crate::domain::entities::recipient::Recipient;
+use crate::domain::value_objects::{
+    ContentFormat, DeliveryStatus, IssueId, SubscriberEmail, SubscriberId, SubscriberName,
+};
+use crate::email_client::{Attachment, EmailClient, EmailMessage, SendOutcome};
+use crate::email_templates::{EmailTemplates, RenderedEmail};
+use crate::error::{ApplicationError, DomainError, InternalLogicDomainError};
+use crate::html_processing::{rewrite_links, sanitize_html, CLIPPING_SIZE};
+use crate::merge_fields::IssueContent;
+use crate::routes::preferences::preferences_link;
+use crate::tracking::TrackingSigner;
+use anyhow::anyhow;
+use futures_util::{stream, StreamExt};
+use reqwest::Url;
+use secrecy::ExposeSecret;
+use serde_json::json;
+use std::sync::Arc;
+
+/// Renders an issue into the `newsletter` template per recipient. Previews, test sends and
+/// real sends all go through it, so they produce the same message.
+pub struct IssueRenderer {
+    issue_id: IssueId,
+    content: IssueContent,
+    templates: EmailTemplates,
+    base_url: String,
+    tracking: Option<TrackingSigner>,
+    /// `List-Id` header value, identifying the newsletter for mail client filters
+    list_id: Option<String>,
+    /// Regular attachments followed by inline images
+    attachments: Vec<Attachment>,
+    regular_attachments: usize,
+}
+
+impl IssueRenderer {
+    pub async fn load(
+        app_state: &AppState,
+        issue: &NewsletterIssue,
+    ) -> Result<Self, ApplicationError> {
+        let renderer = Self {
+            issue_id: issue.id,
+            content: IssueContent::parse(
+                issue.title.clone(),
+                issue.html_content.clone(),
+                issue.text_content.clone(),
+            )?,
+            templates: EmailTemplates::load(
+                &app_state.repository,
+                &app_state.config.default_locale,
+            )
+            .await?,
+            base_url: app_state.config.base_url.clone(),
+            tracking: issue
+                .tracking_enabled
+                .then(|| TrackingSigner::new(app_state.config.tracking_secret.expose_secret())),
+            list_id: Url::parse(&app_state.config.base_url)
+                .ok()
+                .and_then(|url| url.host_str().map(|host| format!("<newsletter.{}>", host))),
+            attachments: issue_attachments(app_state, issue).await?,
+            regular_attachments: issue.attachments.len(),
+        };
+
+        let sample = renderer.render(&seed_recipient(SubscriberEmail::parse(
+            "subscriber@example.com".to_string(),
+        )?)?)?;
+        if sample.html.len() > CLIPPING_SIZE {
+            tracing::warn!(
+                size = sample.html.len(),
+                "Newsletter HTML exceeds the clipping size of common mail clients"
+            );
+        }
+
+        Ok(renderer)
+    }
+
+    /// Renders the message, the HTML part is empty for plain-text recipients. Links and an open
+    /// pixel are tracked when the issue has tracking enabled and the recipient didn't opt out.
+    pub fn render(&self, recipient: &Recipient) -> Result<RenderedEmail, DomainError> {
+        let unsubscribe_link = preferences_link(&self.base_url, &recipient.preference_token);
+        let mut issue = self.content.render(recipient, &unsubscribe_link)?;
+        issue.html = sanitize_html(&issue.html);
+        if let Some(signer) = self
+            .tracking
+            .as_ref()
+            .filter(|_| !recipient.tracking_opt_out)
+        {
+            issue.html = self.track(signer, &issue.html, &recipient.id);
+        }
+        let context = |content: &str| {
+            json!({
+                "title": issue.title,
+                "content": content,
+                "name": recipient.name.as_ref(),
+                "unsubscribe_link": unsubscribe_link,
+            })
+        };
+
+        let html = self.templates.render(
+            "newsletter",
+            recipient.locale.as_ref(),
+            &context(&issue.html),
+        )?;
+        let text = self.templates.render(
+            "newsletter",
+            recipient.locale.as_ref(),
+            &context(&issue.text),
+        )?;
+        Ok(RenderedEmail {
+            subject: html.subject,
+            html: match recipient.content_format {
+                ContentFormat::Html => html.html,
+                ContentFormat::PlainText => String::new(),
+            },
+            text: text.text,
+        })
+    }
+
+    /// Rendered email tagged as `newsletter` with the issue id as metadata.
+    pub fn message(&self, recipient: &Recipient) -> Result<EmailMessage, DomainError> {
+        let email = self.render(recipient)?;
+        let mut message = EmailMessage::new(recipient.email.clone(), email.subject, email.text)
+            .html(email.html)
+            .tag("newsletter")
+            .metadata("issue_id", self.issue_id.as_ref().to_string())
+            .attachments(self.attachments(recipient).iter().cloned());
+        if let Some(list_id) = &self.list_id {
+            message = message.header("List-Id", list_id);
+        }
+        Ok(message)
+    }
+
+    /// Inline images are left out for plain-text recipients.
+    fn attachments(&self, recipient: &Recipient) -> &[Attachment] {
+        match recipient.content_format {
+            ContentFormat::Html => &self.attachments,
+            ContentFormat::PlainText => &self.attachments[..self.regular_attachments],
+        }
+    }
+
+    fn track(&self, signer: &TrackingSigner, html: &str, subscriber_id: &SubscriberId) -> String {
+        let (base_url, issue_id) = (self.base_url.clone(), self.issue_id);
+        let (link_signer, link_subscriber_id) = (signer.clone(), subscriber_id.clone());
+        let mut html = rewrite_links(html, move |url| {
+            link_signer.click_url(&base_url, &issue_id, &link_subscriber_id, url)
+        });
+        html.push_str(&format!(
+            r#"<img src="{}" width="1" height="1" alt="">"#,
+            signer.open_url(&self.base_url, &self.issue_id, subscriber_id)
+        ));
+        html
+    }
+
+    /// Renders and sends the issue to every recipient, with up to `concurrency` sends or batches
+    /// in flight. A failing recipient doesn't affect the others. Results are in the order of
+    /// `recipients`, so failed recipients can be retried in their original order.
+    pub async fn deliver(
+        &self,
+        email_client: &EmailClient,
+        recipients: &[Recipient],
+        config: &DeliveryConfig,
+    ) -> Vec<DeliveryResult> {
+        let batch_size = config.batch_size.get().min(EmailClient::MAX_BATCH_SIZE);
+
+        let batches = recipients
+            .chunks(batch_size)
+            .map(|batch| self.deliver_batch(email_client, batch))
+            .collect::<Vec<_>>();
+
+        stream::iter(batches)
+            .buffered(config.concurrency.get())
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    async fn deliver_batch(
+        &self,
+        email_client: &EmailClient,
+        batch: &[Recipient],
+    ) -> Vec<DeliveryResult> {
+        let mut messages = Vec::with_capacity(batch.len());
+        let rendered = batch
+            .iter()
+            .map(|recipient| {
+                messages.push(self.message(recipient).map_err(|e| anyhow!(e))?);
+                Ok(())
+            })
+            .collect::<Vec<Result<(), anyhow::Error>>>();
+
+        let mut sent = match messages.as_slice() {
+            [] => vec![],
+            [message] if batch.len() == 1 => vec![email_client.send(message).await],
+            messages => email_client.send_batch(messages).await,
+        }
+        .into_iter();
+
+        batch
+            .iter()
+            .zip(rendered)
+            .map(|(recipient, rendered)| DeliveryResult {
+                subscriber_id: recipient.id.clone(),
+                result: rendered.and_then(|_| {
+                    sent.next()
+                        .unwrap_or_else(|| Err(anyhow!("Provider returned no result")))
+                }),
+            })
+            .collect()
+    }
+}
+
+async fn issue_attachments(
+    app_state: &AppState,
+    issue: &NewsletterIssue,
+) -> Result<Vec<Attachment>, ApplicationError> {
+    let asset_ids = [issue.attachments.as_slice(), issue.inline_images.as_slice()].concat();
+    let assets = app_state.repository.get_assets(&asset_ids).await?;
+
+    asset_ids
+        .iter()
+        .enumerate()
+        .map(|(i, id)| {
+            let asset = assets
+                .iter()
+                .find(|asset| asset.id == *id)
+                .ok_or_else(|| DomainError::from("Issue asset wasn't found"))
+                .map_err(InternalLogicDomainError::from)?;
+            Ok(Attachment {
+                filename: asset.filename.clone(),
+                content_type: asset.content_type.clone(),
+                content: Arc::from(asset.content.as_slice()),
+                content_id: (i >= issue.attachments.len()).then(|| id.as_ref().to_string()),
+            })
+        })
+        .collect()
+}
+
+/// Send outcome of one recipient.
+pub struct DeliveryResult {
+    pub subscriber_id: SubscriberId,
+    pub result: Result<SendOutcome, anyhow::Error>,
+}
+
+impl From<&DeliveryResult> for DeliveryUpdate {
+    fn from(delivery: &DeliveryResult) -> Self {
+        let (status, provider_message_id, error) = match &delivery.result {
+            Ok(SendOutcome::Sent { message_id }) => {
+                (DeliveryStatus::Sent, message_id.clone(), None)
+            }
+            Ok(SendOutcome::Suppressed) => (DeliveryStatus::Suppressed, None, None),
+            Err(error) => (DeliveryStatus::Failed, None, Some(format!("{:#}", error))),
+        };
+        Self {
+            subscriber_id: delivery.subscriber_id.clone(),
+            status,
+            provider_message_id,
+            error,
+        }
+    }
+}
+
+/// Recipient for addresses outside the subscriber base, e.g. test sends. It isn't tracked.
+pub fn seed_recipient(email: SubscriberEmail) -> Result<Recipient, DomainError> {
+    Ok(Recipient {
+        id: SubscriberId::new(),
+        email,
+        name: SubscriberName::parse("Test subscriber".to_string())?,
+        content_format: ContentFormat::Html,
+        preference_token: "test".to_string(),
+        attributes: json!({}),
+        locale: None,
+        tracking_opt_out: true,
+    })
+}
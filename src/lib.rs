@@ -2,8 +2,19 @@ pub mod app_config;
 pub mod app_state;
 pub mod domain;
 pub mod email_client;
+pub mod email_events;
+pub mod email_providers;
+pub mod email_templates;
 pub mod error;
+pub mod extractors;
+pub mod html_processing;
 pub mod infrastructure;
+pub mod issue_delivery;
+pub mod markdown;
+pub mod merge_fields;
 pub mod middlewares;
+pub mod outbox_relay;
+pub mod rate_limiter;
 pub mod routes;
 pub mod startup;
+pub mod tracking;
@@ -37,6 +37,13 @@ async fn main() -> Result<(), anyhow::Error> {
 
     let tracer = provider.tracer("zero2prod");
 
+    let meter_provider = opentelemetry_otlp::new_pipeline()
+        .metrics(runtime::Tokio)
+        .with_exporter(opentelemetry_otlp::new_exporter().tonic())
+        .with_resource(resource())
+        .build()?;
+    global::set_meter_provider(meter_provider);
+
     tracing_subscriber::registry()
         .with(filter)
         .with(tracing_subscriber::fmt::layer())
@@ -0,0 +1,138 @@
+use crate::merge_fields::{protect_tags, restore_tags};
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+
+/// HTML and plain-text variants generated from a Markdown issue.
+#[derive(Debug)]
+pub struct MarkdownContent {
+    pub html: String,
+    pub text: String,
+}
+
+/// Converts Markdown to sanitized HTML and readable plain text.
+///
+/// Merge field tags such as `{{ attributes.city | default("Earthsea") }}` are kept verbatim in
+/// both variants, so they can still be rendered per recipient afterwards. Images may show
+/// uploaded assets inline with `![logo](cid:<asset id>)`.
+pub fn render_markdown(source: &str) -> MarkdownContent {
+    let (source, tags) = protect_tags(source);
+    let options = Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TABLES;
+
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, Parser::new_ext(&source, options));
+    let html = ammonia::Builder::default()
+        .add_url_schemes(&["cid"])
+        .clean(&html)
+        .to_string();
+    let text = plain_text(Parser::new_ext(&source, options));
+
+    MarkdownContent {
+        html: restore_tags(html, &tags),
+        text: restore_tags(text, &tags),
+    }
+}
+
+fn plain_text<'a>(events: impl Iterator<Item = Event<'a>>) -> String {
+    let mut text = String::new();
+    let mut lists: Vec<Option<u64>> = vec![];
+    let mut links = vec![];
+
+    for event in events {
+        match event {
+            Event::Text(s) | Event::Code(s) => text.push_str(&s),
+            Event::SoftBreak | Event::HardBreak => text.push('\n'),
+            Event::Rule => text.push_str("----\n\n"),
+            Event::Start(Tag::List(start)) => lists.push(start),
+            Event::End(TagEnd::List(_)) => {
+                lists.pop();
+                if lists.is_empty() {
+                    text.push('\n');
+                }
+            }
+            Event::Start(Tag::Item) => {
+                if !text.is_empty() && !text.ends_with('\n') {
+                    text.push('\n');
+                }
+                text.push_str(&"  ".repeat(lists.len().saturating_sub(1)));
+                match lists.last_mut() {
+                    Some(Some(n)) => {
+                        text.push_str(&format!("{}. ", n));
+                        *n += 1;
+                    }
+                    _ => text.push_str("- "),
+                }
+            }
+            Event::End(TagEnd::Item) if !text.ends_with('\n') => text.push('\n'),
+            Event::Start(Tag::Link { dest_url, .. }) => links.push((dest_url, text.len())),
+            Event::End(TagEnd::Link) => {
+                if let Some((url, start)) = links.pop() {
+                    if text[start..] != *url {
+                        text.push_str(&format!(" ({})", url));
+                    }
+                }
+            }
+            Event::End(
+                TagEnd::Paragraph | TagEnd::Heading(_) | TagEnd::CodeBlock | TagEnd::BlockQuote(_),
+            ) => {
+                text.push_str("\n\n");
+            }
+            _ => {}
+        }
+    }
+
+    let mut normalized = String::with_capacity(text.len());
+    for line in text.trim().lines() {
+        if line.is_empty() && normalized.ends_with("\n\n") {
+            continue;
+        }
+        normalized.push_str(line.trim_end());
+        normalized.push('\n');
+    }
+    normalized.trim_end().to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markdown_is_converted_to_both_variants() {
+        let content = render_markdown(
+            "# News\n\nHello *there*, read [the post](https://example.com/post).\n\n- one\n- two",
+        );
+
+        assert_eq!(
+            content.html,
+            "<h1>News</h1>\n<p>Hello <em>there</em>, read \
+<a href=\"https://example.com/post\" rel=\"noopener noreferrer\">the post</a>.</p>\n\
+<ul>\n<li>one</li>\n<li>two</li>\n</ul>\n"
+        );
+        assert_eq!(
+            content.text,
+            "News\n\nHello there, read the post (https://example.com/post).\n\n- one\n- two"
+        );
+    }
+
+    #[test]
+    fn raw_html_is_sanitized() {
+        let content = render_markdown("Hi <script>alert(1)</script><b onclick=\"x()\">there</b>");
+
+        assert_eq!(content.html, "<p>Hi <b>there</b></p>\n");
+    }
+
+    #[test]
+    fn merge_fields_are_kept_verbatim() {
+        let content = render_markdown(
+            "Hi {{ attributes.city | default(\"*Earthsea*\") }}, [leave]({{ unsubscribe_link }})",
+        );
+
+        assert_eq!(
+            content.html,
+            "<p>Hi {{ attributes.city | default(\"*Earthsea*\") }}, \
+<a href=\"{{ unsubscribe_link }}\" rel=\"noopener noreferrer\">leave</a></p>\n"
+        );
+        assert_eq!(
+            content.text,
+            "Hi {{ attributes.city | default(\"*Earthsea*\") }}, leave ({{ unsubscribe_link }})"
+        );
+    }
+}
@@ -0,0 +1,322 @@
+use crate::domain::entities::recipient::Recipient;
+use crate::email_templates::html_formatter;
+use crate::error::DomainError;
+use minijinja::{AutoEscape, Environment};
+use serde_json::json;
+
+/// Fields available to issue content, e.g. `Hi {{ name }}` or
+/// `{{ attributes.city | default("your city") }}`. Missing values render empty unless a
+/// `default` fallback is given.
+pub const MERGE_FIELDS: [&str; 4] = ["name", "email", "attributes", "unsubscribe_link"];
+
+const TITLE: &str = "title";
+const HTML: &str = "html";
+const TEXT: &str = "text";
+
+/// Issue title and bodies compiled for per recipient rendering.
+pub struct IssueContent {
+    env: Environment<'static>,
+}
+
+#[derive(Debug)]
+pub struct PersonalizedIssue {
+    pub title: String,
+    pub html: String,
+    pub text: String,
+}
+
+impl IssueContent {
+    /// Compiles the content and rejects references to unknown merge fields.
+    pub fn parse(title: String, html: String, text: String) -> Result<Self, DomainError> {
+        let mut env = Environment::new();
+        env.set_auto_escape_callback(|name| match name {
+            HTML => AutoEscape::Html,
+            _ => AutoEscape::None,
+        });
+        env.set_formatter(html_formatter);
+
+        for (name, source) in [(TITLE, title), (HTML, html), (TEXT, text)] {
+            if let Some(tag) = protect_tags(&source)
+                .1
+                .into_iter()
+                .find(|tag| !is_merge_field_tag(tag))
+            {
+                return Err(format!("Only merge fields are allowed in {}: {}", name, tag).into());
+            }
+            env.add_template_owned(name, source)
+                .map_err(|e| content_error(name, e))?;
+            let template = env.get_template(name).map_err(|e| content_error(name, e))?;
+            let mut unknown = template
+                .undeclared_variables(false)
+                .into_iter()
+                .filter(|field| !MERGE_FIELDS.contains(&field.as_str()))
+                .collect::<Vec<_>>();
+            if !unknown.is_empty() {
+                unknown.sort();
+                return Err(
+                    format!("Unknown merge fields in {}: {}", name, unknown.join(", ")).into(),
+                );
+            }
+        }
+
+        let content = Self { env };
+        content.render_context(&json!({
+            "name": "Subscriber",
+            "email": "subscriber@example.com",
+            "attributes": {},
+            "unsubscribe_link": "https://example.com/unsubscribe",
+        }))?;
+        Ok(content)
+    }
+
+    pub fn render(
+        &self,
+        recipient: &Recipient,
+        unsubscribe_link: &str,
+    ) -> Result<PersonalizedIssue, DomainError> {
+        self.render_context(&json!({
+            "name": recipient.name.as_ref(),
+            "email": recipient.email.as_ref(),
+            "attributes": recipient.attributes,
+            "unsubscribe_link": unsubscribe_link,
+        }))
+    }
+
+    fn render_context(
+        &self,
+        context: &serde_json::Value,
+    ) -> Result<PersonalizedIssue, DomainError> {
+        let render = |name: &str| {
+            self.env
+                .get_template(name)
+                .and_then(|t| t.render(context))
+                .map_err(|e| content_error(name, e))
+        };
+
+        Ok(PersonalizedIssue {
+            title: render(TITLE)?,
+            html: render(HTML)?,
+            text: render(TEXT)?,
+        })
+    }
+}
+
+/// Replaces template tags with inert placeholders, so HTML and Markdown processing leaves them
+/// intact. [`restore_tags`] puts them back.
+pub fn protect_tags(source: &str) -> (String, Vec<&str>) {
+    let mut protected = String::with_capacity(source.len());
+    let mut tags = vec![];
+    let mut rest = source;
+
+    while let Some(start) = ["{{", "{%", "{#"]
+        .iter()
+        .filter_map(|open| rest.find(open))
+        .min()
+    {
+        let close = match &rest[start + 1..start + 2] {
+            "{" => "}}",
+            "%" => "%}",
+            _ => "#}",
+        };
+        let Some(end) = rest[start + 2..].find(close).map(|i| start + 2 + i + 2) else {
+            break;
+        };
+        protected.push_str(&rest[..start]);
+        protected.push_str(&placeholder(tags.len()));
+        tags.push(&rest[start..end]);
+        rest = &rest[end..];
+    }
+    protected.push_str(rest);
+
+    (protected, tags)
+}
+
+pub fn restore_tags(mut s: String, tags: &[&str]) -> String {
+    for (i, tag) in tags.iter().enumerate() {
+        s = s.replace(&placeholder(i), tag);
+    }
+    s
+}
+
+/// Whether a processed value starts with a protected tag, e.g. `href="{{ unsubscribe_link }}"`.
+pub fn starts_with_tag(s: &str) -> bool {
+    s.starts_with(PLACEHOLDER_PREFIX)
+}
+
+/// Whether the tag is a comment or a plain merge field lookup, optionally with a `default`
+/// fallback. Literals, other filters, expressions and statements are rejected, their output
+/// would bypass escaping and the HTML sanitizer.
+fn is_merge_field_tag(tag: &str) -> bool {
+    if tag.starts_with("{#") {
+        return true;
+    }
+    let Some(expression) = tag.strip_prefix("{{").and_then(|x| x.strip_suffix("}}")) else {
+        return false;
+    };
+    let expression = expression.strip_prefix(['-', '+']).unwrap_or(expression);
+    let expression = expression.strip_suffix(['-', '+']).unwrap_or(expression);
+
+    let mut lookup = TagCursor(expression);
+    let path = lookup.ident().is_some()
+        && loop {
+            if lookup.eat(".") {
+                if lookup.ident().is_none() {
+                    break false;
+                }
+            } else if lookup.eat("[") {
+                if !(lookup.string() && lookup.eat("]")) {
+                    break false;
+                }
+            } else {
+                break true;
+            }
+        };
+    let fallback = !lookup.eat("|")
+        || (lookup.ident() == Some("default")
+            && lookup.eat("(")
+            && lookup.string()
+            && lookup.eat(")"));
+    path && fallback && lookup.0.trim().is_empty()
+}
+
+/// Remaining tag expression, every step skips the leading whitespace.
+struct TagCursor<'a>(&'a str);
+
+impl<'a> TagCursor<'a> {
+    fn eat(&mut self, token: &str) -> bool {
+        match self.0.trim_start().strip_prefix(token) {
+            Some(rest) => {
+                self.0 = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn ident(&mut self) -> Option<&'a str> {
+        let rest = self.0.trim_start();
+        let end = rest
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or(rest.len());
+        if end == 0 || rest.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+        self.0 = &rest[end..];
+        Some(&rest[..end])
+    }
+
+    /// Quoted string without escapes.
+    fn string(&mut self) -> bool {
+        let rest = self.0.trim_start();
+        let Some(quote) = rest.chars().next().filter(|c| matches!(c, '"' | '\'')) else {
+            return false;
+        };
+        match rest[1..].find([quote, '\\']) {
+            Some(end) if rest[1 + end..].starts_with(quote) => {
+                self.0 = &rest[end + 2..];
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+const PLACEHOLDER_PREFIX: &str = "MERGEFIELD";
+
+fn placeholder(i: usize) -> String {
+    format!("{}{}X", PLACEHOLDER_PREFIX, i)
+}
+
+fn content_error(name: &str, error: minijinja::Error) -> DomainError {
+    format!("Issue {} is invalid: {}", name, error).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::value_objects::{
+        ContentFormat, SubscriberEmail, SubscriberId, SubscriberName,
+    };
+
+    fn recipient(attributes: serde_json::Value) -> Recipient {
+        Recipient {
+            id: SubscriberId::new(),
+            email: SubscriberEmail::parse("ursula@earthsea.com".to_string()).unwrap(),
+            name: SubscriberName::parse("Ursula & Ged".to_string()).unwrap(),
+            content_format: ContentFormat::Html,
+            preference_token: "token".to_string(),
+            attributes,
+            locale: None,
+            tracking_opt_out: false,
+        }
+    }
+
+    fn parse(title: &str, html: &str, text: &str) -> Result<IssueContent, DomainError> {
+        IssueContent::parse(title.to_string(), html.to_string(), text.to_string())
+    }
+
+    #[test]
+    fn fields_are_rendered_per_recipient() {
+        let content = parse(
+            "News for {{ name }}",
+            "<p>{{ email }} in {{ attributes.city }}</p>",
+            "Leave: {{ unsubscribe_link }}",
+        )
+        .unwrap();
+
+        let issue = content
+            .render(&recipient(json!({ "city": "Havnor" })), "https://x/u")
+            .unwrap();
+
+        assert_eq!(issue.title, "News for Ursula & Ged");
+        assert_eq!(issue.html, "<p>ursula@earthsea.com in Havnor</p>");
+        assert_eq!(issue.text, "Leave: https://x/u");
+    }
+
+    #[test]
+    fn missing_values_use_fallback() {
+        let content = parse(
+            "",
+            "{{ attributes.city | default(\"your town\") }}",
+            "{{ attributes.city }}",
+        )
+        .unwrap();
+
+        let issue = content.render(&recipient(json!({})), "").unwrap();
+
+        assert_eq!(issue.html, "your town");
+        assert_eq!(issue.text, "");
+    }
+
+    #[test]
+    fn html_values_are_escaped() {
+        let content = parse("{{ name }}", "{{ name }}", "{{ name }}").unwrap();
+
+        let issue = content.render(&recipient(json!({})), "").unwrap();
+
+        assert_eq!(issue.html, "Ursula &amp; Ged");
+        assert_eq!(issue.text, "Ursula & Ged");
+    }
+
+    #[test]
+    fn only_merge_field_lookups_are_allowed() {
+        assert!(parse(
+            "",
+            r#"{{ attributes["city"] | default('x') }}{{- name -}}{# note #}"#,
+            ""
+        )
+        .is_ok());
+        assert!(parse("", r#"{{ "<script>alert(1)</script>" | safe }}"#, "").is_err());
+        assert!(parse("", r#"<a href="{{ 'javascript:alert(1)' }}">x</a>"#, "").is_err());
+        assert!(parse("", "{{ name | safe }}", "").is_err());
+        assert!(parse("", "{{ name | upper }}", "").is_err());
+        assert!(parse("", "{{ name ~ email }}", "").is_err());
+        assert!(parse("", "{% if name %}Hi{% endif %}", "").is_err());
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        assert!(parse("", "{{ first_name }}", "").is_err());
+        assert!(parse("{{ nickname }}", "", "").is_err());
+    }
+}
@@ -16,8 +16,7 @@ pub async fn basic_auth(
     req: Request,
     next: Next,
 ) -> Result<Response, ApplicationError> {
-    let credentials =
-        extract_credentials(&req).map_err(|e| ApplicationError::AuthError(e.into()))?;
+    let credentials = extract_credentials(&req).map_err(ApplicationError::AuthError)?;
 
     let password_hash = PasswordHash::new_from_password(&credentials.password);
     let user_exists = state
@@ -56,8 +55,9 @@ fn extract_credentials(req: &Request) -> Result<BasicAuthCredentials, anyhow::Er
         bail!("Authorization header value credentials");
     };
 
-    let decoded_bytes =
-        base64_url::decode(credentials).context("Failed to to decode base64 credentials")?;
+    let decoded_bytes = data_encoding::BASE64
+        .decode(credentials.as_bytes())
+        .context("Failed to to decode base64 credentials")?;
 
     let decoded_credentials = std::str::from_utf8(&decoded_bytes)
         .context("Decoded credentials string isn't valid UTF-8 string")?;
@@ -75,3 +75,28 @@ fn extract_credentials(req: &Request) -> Result<BasicAuthCredentials, anyhow::Er
 
     Ok(BasicAuthCredentials { username, password })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+
+    fn request(authorization: &str) -> Request {
+        Request::builder()
+            .header("Authorization", authorization)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[test]
+    fn credentials_are_standard_base64() {
+        // "admin:~~~?" and "admin:p>?>?" encode to `+`, `/` and padding, unlike URL-safe base64
+        let plus = extract_credentials(&request("Basic YWRtaW46fn5+Pw==")).unwrap();
+        let slash = extract_credentials(&request("Basic YWRtaW46cD4/Pj8=")).unwrap();
+
+        assert_eq!(plus.username, "admin");
+        assert_eq!(plus.password, "~~~?");
+        assert_eq!(slash.password, "p>?>?");
+        assert!(extract_credentials(&request("Basic YWRtaW46fn5-Pw")).is_err());
+    }
+}
@@ -1 +1,2 @@
 pub mod basic_auth;
+pub mod problem_details;
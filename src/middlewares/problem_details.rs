@@ -0,0 +1,32 @@
+use crate::app_config::AppEnvironment;
+use crate::app_state::AppState;
+use crate::error::Problem;
+use axum::extract::{Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+use std::sync::Arc;
+
+/// Renders problem responses again with the request id as `instance`. Internal error details
+/// are only shown in Local and Testing.
+pub async fn problem_details(
+    State(state): State<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let request_id = req
+        .headers()
+        .get("x-request-id")
+        .and_then(|x| x.to_str().ok())
+        .map(str::to_owned);
+
+    let response = next.run(req).await;
+    let Some(problem) = response.extensions().get::<Problem>() else {
+        return response;
+    };
+
+    let show_internal = matches!(
+        state.config.environment,
+        AppEnvironment::Local | AppEnvironment::Testing
+    );
+    problem.to_response(request_id.as_deref(), show_internal)
+}
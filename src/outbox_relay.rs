@@ -0,0 +1,112 @@
+use crate::app_state::AppState;
+use crate::domain::entities::outbox_email::OutboxEmail;
+use crate::email_client::EmailMessage;
+use anyhow::anyhow;
+use chrono::{Duration, Utc};
+use std::sync::Arc;
+use tracing::{error, info, warn};
+
+/// Claimed emails are retried by another relay after this long, e.g. when this one crashed.
+const CLAIM_LEASE: Duration = Duration::minutes(5);
+/// Failed emails are retried with exponential backoff up to this delay.
+const MAX_RETRY_DELAY: Duration = Duration::hours(1);
+
+/// Sends the emails written to the outbox until the app stops. Runs right after
+/// `outbox_notify` is notified and otherwise every poll interval, which picks up retries.
+pub async fn run_outbox_relay(app_state: Arc<AppState>) {
+    let poll_interval =
+        std::time::Duration::from_secs(app_state.config.email_client.outbox.poll_interval_seconds);
+    loop {
+        if let Err(error) = relay_outbox(&app_state).await {
+            error!(?error, "Outbox relay failed");
+        }
+
+        tokio::select! {
+            _ = app_state.outbox_notify.notified() => {}
+            _ = tokio::time::sleep(poll_interval) => {}
+        }
+    }
+}
+
+/// Sends due emails batch by batch, returning how many were claimed.
+pub async fn relay_outbox(app_state: &AppState) -> Result<usize, anyhow::Error> {
+    let config = &app_state.config.email_client.outbox;
+    let batch_size = i64::from(config.batch_size.get());
+    let mut claimed = 0;
+
+    loop {
+        let emails = app_state
+            .repository
+            .claim_outbox_emails(batch_size, CLAIM_LEASE, config.max_attempts)
+            .await
+            .map_err(|e| anyhow!(e))?;
+        let count = emails.len();
+        claimed += count;
+
+        for email in emails {
+            match email {
+                Ok(email) => relay_email(app_state, email).await?,
+                Err(error) => warn!(?error, "Skipped outbox email with invalid stored data"),
+            }
+        }
+        if (count as i64) < batch_size {
+            return Ok(claimed);
+        }
+    }
+}
+
+async fn relay_email(app_state: &AppState, email: OutboxEmail) -> Result<(), anyhow::Error> {
+    let mut message = EmailMessage::new(email.recipient, email.subject, email.text_content)
+        .html(email.html_content);
+    if let Some(tag) = email.tag {
+        message = message.tag(tag);
+    }
+
+    match app_state.email_client.send(&message).await {
+        Ok(_) => {
+            info!(id = ?email.id, "Outbox email sent");
+            app_state
+                .repository
+                .mark_outbox_email_sent(&email.id)
+                .await
+                .map_err(|e| anyhow!(e))?;
+        }
+        Err(error) => {
+            let max_attempts = app_state.config.email_client.outbox.max_attempts;
+            if email.attempts >= max_attempts {
+                error!(?error, id = ?email.id, "Outbox email given up after {} attempts", email.attempts);
+            } else {
+                warn!(?error, id = ?email.id, attempt = email.attempts, "Outbox email failed");
+            }
+            app_state
+                .repository
+                .mark_outbox_email_failed(
+                    &email.id,
+                    &format!("{:#}", error),
+                    Utc::now() + retry_delay(email.attempts),
+                )
+                .await
+                .map_err(|e| anyhow!(e))?;
+        }
+    }
+    Ok(())
+}
+
+/// 2, 4, 8... seconds after the first, second, third... failed attempt.
+fn retry_delay(attempts: i32) -> Duration {
+    let exponent = attempts.clamp(1, 12) as u32;
+    Duration::seconds(2_i64.pow(exponent)).min(MAX_RETRY_DELAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_delay_grows_exponentially_up_to_an_hour() {
+        assert_eq!(retry_delay(1), Duration::seconds(2));
+        assert_eq!(retry_delay(3), Duration::seconds(8));
+        assert_eq!(retry_delay(12), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(100), MAX_RETRY_DELAY);
+    }
+}
@@ -0,0 +1,278 @@
+use crate::app_config::RateLimitConfig;
+use crate::domain::value_objects::SubscriberEmail;
+use anyhow::bail;
+use opentelemetry::global;
+use opentelemetry::metrics::{Counter, Histogram};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+/// Idle domain buckets are dropped once this many domains are tracked.
+const MAX_TRACKED_DOMAINS: usize = 10_000;
+
+/// Paces outgoing emails to the provider limits: globally per second and per day, and per
+/// recipient domain per second. Concurrent senders share the limits.
+///
+/// Sends that would wait longer than `max_wait` fail instead, so a request handler isn't held
+/// for hours when the daily limit is used up.
+///
+/// Throttling is reported as the `email.throttled` counter and the `email.throttle_duration`
+/// histogram in seconds.
+#[derive(Debug)]
+pub struct SendRateLimiter {
+    buckets: Mutex<Buckets>,
+    per_domain_per_second: u32,
+    max_wait: Duration,
+    throttled: Counter<u64>,
+    throttle_duration: Histogram<f64>,
+}
+
+#[derive(Debug)]
+struct Buckets {
+    per_second: TokenBucket,
+    per_day: TokenBucket,
+    domains: HashMap<String, TokenBucket>,
+}
+
+/// Token bucket holding up to `capacity` tokens and refilling `capacity` tokens per `period`.
+///
+/// Tokens are reserved ahead: the balance goes negative while senders wait, so every sender
+/// gets its own slot even if they all arrive at once.
+#[derive(Debug)]
+struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    refill_per_second: f64,
+    updated_at: Instant,
+}
+
+impl SendRateLimiter {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        let now = Instant::now();
+        let meter = global::meter("zero2prod");
+        Self {
+            buckets: Mutex::new(Buckets {
+                per_second: TokenBucket::new(config.per_second.get(), Duration::from_secs(1), now),
+                per_day: TokenBucket::new(config.per_day.get(), DAY, now),
+                domains: HashMap::new(),
+            }),
+            per_domain_per_second: config.per_domain_per_second.get(),
+            max_wait: Duration::from_secs(config.max_wait_seconds),
+            throttled: meter
+                .u64_counter("email.throttled")
+                .with_description("Emails delayed by the send rate limit")
+                .init(),
+            throttle_duration: meter
+                .f64_histogram("email.throttle_duration")
+                .with_description("Time emails waited for the send rate limit")
+                .with_unit("s")
+                .init(),
+        }
+    }
+
+    /// Waits until an email to `recipient` may be sent, or fails when that's more than
+    /// `max_wait` away. The slot is given back when the wait is cancelled.
+    pub async fn acquire(&self, recipient: &SubscriberEmail) -> Result<(), anyhow::Error> {
+        let wait = self.reserve(recipient, Instant::now());
+        let reservation = Reservation {
+            limiter: self,
+            recipient,
+        };
+        if wait > self.max_wait {
+            drop(reservation);
+            bail!(
+                "Send rate limit exceeded, the next slot is in {} seconds",
+                wait.as_secs()
+            );
+        }
+        if !wait.is_zero() {
+            tracing::debug!(?wait, "Email throttled");
+            self.throttled.add(1, &[]);
+            self.throttle_duration.record(wait.as_secs_f64(), &[]);
+            tokio::time::sleep(wait).await;
+        }
+
+        std::mem::forget(reservation);
+        Ok(())
+    }
+
+    fn reserve(&self, recipient: &SubscriberEmail, now: Instant) -> Duration {
+        let domain = domain(recipient);
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if buckets.domains.len() >= MAX_TRACKED_DOMAINS {
+            buckets.domains.retain(|_, bucket| !bucket.is_full(now));
+        }
+        let domain_wait = buckets
+            .domains
+            .entry(domain)
+            .or_insert_with(|| {
+                TokenBucket::new(self.per_domain_per_second, Duration::from_secs(1), now)
+            })
+            .reserve(now);
+
+        buckets
+            .per_second
+            .reserve(now)
+            .max(buckets.per_day.reserve(now))
+            .max(domain_wait)
+    }
+
+    fn refund(&self, recipient: &SubscriberEmail, now: Instant) {
+        let mut buckets = self.buckets.lock().unwrap();
+        buckets.per_second.refund(now);
+        buckets.per_day.refund(now);
+        if let Some(bucket) = buckets.domains.get_mut(&domain(recipient)) {
+            bucket.refund(now);
+        }
+    }
+}
+
+/// Slot taken by a pending `acquire`, given back when dropped.
+struct Reservation<'a> {
+    limiter: &'a SendRateLimiter,
+    recipient: &'a SubscriberEmail,
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        self.limiter.refund(self.recipient, Instant::now());
+    }
+}
+
+fn domain(recipient: &SubscriberEmail) -> String {
+    recipient
+        .as_ref()
+        .rsplit('@')
+        .next()
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+impl TokenBucket {
+    fn new(capacity: u32, period: Duration, now: Instant) -> Self {
+        let capacity = f64::from(capacity);
+        Self {
+            capacity,
+            tokens: capacity,
+            refill_per_second: capacity / period.as_secs_f64(),
+            updated_at: now,
+        }
+    }
+
+    /// Takes a token and returns how long to wait until it is available.
+    fn reserve(&mut self, now: Instant) -> Duration {
+        self.refill(now);
+        self.tokens -= 1.0;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.refill_per_second)
+        }
+    }
+
+    /// Gives back a reserved token.
+    fn refund(&mut self, now: Instant) {
+        self.refill(now);
+        self.tokens = (self.tokens + 1.0).min(self.capacity);
+    }
+
+    fn is_full(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        self.tokens >= self.capacity
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_second).min(self.capacity);
+        self.updated_at = self.updated_at.max(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::num::NonZeroU32;
+
+    fn limiter(per_second: u32, per_day: u32, per_domain_per_second: u32) -> SendRateLimiter {
+        SendRateLimiter::new(&RateLimitConfig {
+            per_second: NonZeroU32::new(per_second).unwrap(),
+            per_day: NonZeroU32::new(per_day).unwrap(),
+            per_domain_per_second: NonZeroU32::new(per_domain_per_second).unwrap(),
+            max_wait_seconds: 60,
+        })
+    }
+
+    fn email(s: &str) -> SubscriberEmail {
+        SubscriberEmail::parse(s.to_string()).unwrap()
+    }
+
+    #[test]
+    fn bucket_allows_burst_then_spaces_tokens() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(2, Duration::from_secs(1), now);
+
+        assert_eq!(bucket.reserve(now), Duration::ZERO);
+        assert_eq!(bucket.reserve(now), Duration::ZERO);
+        assert_eq!(bucket.reserve(now), Duration::from_millis(500));
+        assert_eq!(bucket.reserve(now), Duration::from_secs(1));
+        assert_eq!(bucket.reserve(now + Duration::from_secs(3)), Duration::ZERO);
+    }
+
+    #[test]
+    fn domains_are_limited_separately() {
+        let limiter = limiter(100, 1000, 1);
+        let now = Instant::now();
+
+        assert_eq!(
+            limiter.reserve(&email("a@earthsea.com"), now),
+            Duration::ZERO
+        );
+        assert_eq!(limiter.reserve(&email("b@havnor.com"), now), Duration::ZERO);
+        assert_eq!(
+            limiter.reserve(&email("c@Earthsea.com"), now),
+            Duration::from_secs(1)
+        );
+    }
+
+    #[test]
+    fn daily_limit_applies_across_domains() {
+        let limiter = limiter(100, 2, 100);
+        let now = Instant::now();
+
+        limiter.reserve(&email("a@earthsea.com"), now);
+        limiter.reserve(&email("b@havnor.com"), now);
+
+        assert_eq!(
+            limiter.reserve(&email("c@gont.com"), now),
+            Duration::from_secs(12 * 60 * 60)
+        );
+    }
+
+    #[tokio::test]
+    async fn long_waits_fail_without_using_a_slot() {
+        let limiter = limiter(100, 1, 100);
+
+        assert!(limiter.acquire(&email("a@earthsea.com")).await.is_ok());
+        assert!(limiter.acquire(&email("b@havnor.com")).await.is_err());
+
+        let wait = limiter.reserve(&email("c@gont.com"), Instant::now());
+        assert!(wait <= DAY && wait > DAY - Duration::from_secs(60));
+    }
+
+    #[tokio::test]
+    async fn cancelled_wait_gives_the_slot_back() {
+        let limiter = limiter(1, 1000, 100);
+        limiter.acquire(&email("a@earthsea.com")).await.unwrap();
+
+        let cancelled = tokio::time::timeout(
+            Duration::from_millis(10),
+            limiter.acquire(&email("b@havnor.com")),
+        )
+        .await;
+
+        assert!(cancelled.is_err());
+        assert!(limiter.reserve(&email("c@gont.com"), Instant::now()) <= Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,83 @@
+use crate::app_state::AppState;
+use crate::domain::entities::asset::Asset;
+use crate::domain::value_objects::AssetId;
+use crate::email_client::MAX_ATTACHMENTS_SIZE;
+use crate::error::{ApplicationError, DomainError};
+use crate::extractors::QueryParams;
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderMap, StatusCode};
+use axum::Json;
+use mime::Mime;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Larger uploads couldn't be sent in any email.
+pub const MAX_ASSET_SIZE: usize = MAX_ATTACHMENTS_SIZE;
+const MAX_FILENAME_LENGTH: usize = 255;
+
+#[derive(Deserialize)]
+pub struct UploadAssetQuery {
+    filename: String,
+}
+
+#[derive(Serialize)]
+pub struct AssetResponse {
+    id: Uuid,
+    filename: String,
+    content_type: String,
+    size: usize,
+}
+
+/// Stores the raw request body as an asset issues can attach, the `Content-Type` header is
+/// kept as its content type.
+#[tracing::instrument(skip_all)]
+pub async fn upload_asset(
+    State(app_state): State<Arc<AppState>>,
+    QueryParams(query): QueryParams<UploadAssetQuery>,
+    headers: HeaderMap,
+    content: Bytes,
+) -> Result<(StatusCode, Json<AssetResponse>), ApplicationError> {
+    let filename = parse_filename(query.filename)?;
+    let content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| x.parse::<Mime>().ok())
+        .ok_or_else(|| DomainError::from("Content-Type header must be a valid media type"))?;
+    if content.is_empty() {
+        return Err(DomainError::from("Asset can't be empty").into());
+    }
+
+    let asset = Asset {
+        id: AssetId::new(),
+        filename,
+        content_type: content_type.essence_str().to_owned(),
+        content: content.to_vec(),
+    };
+    app_state.repository.insert_asset(&asset).await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(AssetResponse {
+            id: *asset.id.as_ref(),
+            size: asset.content.len(),
+            filename: asset.filename,
+            content_type: asset.content_type,
+        }),
+    ))
+}
+
+fn parse_filename(filename: String) -> Result<String, DomainError> {
+    let filename = filename.trim();
+    let is_valid = !filename.is_empty()
+        && filename.len() <= MAX_FILENAME_LENGTH
+        && !filename
+            .chars()
+            .any(|c| c.is_control() || matches!(c, '/' | '\\' | '"'));
+    if !is_valid {
+        return Err(format!("{} is not valid filename", filename).into());
+    }
+    Ok(filename.to_owned())
+}
@@ -0,0 +1,66 @@
+use crate::app_state::AppState;
+use crate::domain::value_objects::{Audience, ListSlug, SegmentId, Tag};
+use crate::error::{ApplicationError, DomainError, NotFoundError};
+use serde::Deserialize;
+use uuid::Uuid;
+
+/// Targeting part of a send request.
+#[derive(Deserialize, Default, Debug)]
+pub struct AudienceRequest {
+    #[serde(default)]
+    pub lists: Vec<String>,
+    pub segment_id: Option<Uuid>,
+    #[serde(default)]
+    pub include_tags: Vec<String>,
+    #[serde(default)]
+    pub exclude_tags: Vec<String>,
+}
+
+/// Targets the default list when no lists are named.
+pub async fn resolve_audience(
+    app_state: &AppState,
+    request: AudienceRequest,
+) -> Result<Audience, ApplicationError> {
+    let slugs = if request.lists.is_empty() {
+        vec![ListSlug::default()]
+    } else {
+        request
+            .lists
+            .into_iter()
+            .map(ListSlug::parse)
+            .collect::<Result<Vec<_>, _>>()?
+    };
+
+    let mut list_ids = Vec::with_capacity(slugs.len());
+    for slug in slugs {
+        let id = app_state
+            .repository
+            .get_list_id_by_slug(&slug)
+            .await?
+            .ok_or_else(|| NotFoundError::from(format!("List {} wasn't found", slug.as_ref())))?;
+        list_ids.push(id);
+    }
+
+    let segment = match request.segment_id {
+        Some(id) => {
+            let segment = app_state
+                .repository
+                .get_segment(&SegmentId::from(id))
+                .await?
+                .ok_or_else(|| NotFoundError::from(format!("Segment {} wasn't found", id)))?;
+            Some(segment.filter)
+        }
+        None => None,
+    };
+
+    Ok(Audience {
+        list_ids,
+        segment,
+        include_tags: parse_tags(request.include_tags)?,
+        exclude_tags: parse_tags(request.exclude_tags)?,
+    })
+}
+
+pub fn parse_tags(tags: Vec<String>) -> Result<Vec<Tag>, DomainError> {
+    tags.into_iter().map(Tag::parse).collect()
+}
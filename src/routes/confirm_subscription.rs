@@ -1,10 +1,16 @@
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 
-use axum::extract::{Query, State};
+use axum::extract::{ConnectInfo, State};
+use axum::http::header::USER_AGENT;
+use axum::http::HeaderMap;
+use chrono::Utc;
 use serde::Deserialize;
 
 use crate::app_state::AppState;
-use crate::error::{ApplicationError, DomainError};
+use crate::domain::value_objects::ConsentConfirmation;
+use crate::error::{ApplicationError, NotFoundError, RepositoryError};
+use crate::extractors::QueryParams;
 
 #[derive(Deserialize)]
 pub struct ConfirmSubscriptionQuery {
@@ -13,21 +19,103 @@ pub struct ConfirmSubscriptionQuery {
 #[tracing::instrument(skip_all)]
 pub async fn confirm_subscription(
     State(app_state): State<Arc<AppState>>,
-    Query(query): Query<ConfirmSubscriptionQuery>,
+    ConnectInfo(peer_addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    QueryParams(query): QueryParams<ConfirmSubscriptionQuery>,
 ) -> Result<(), ApplicationError> {
-    let subscriber_id = app_state
+    let subscription = app_state
         .repository
-        .get_subscriber_id_by_token(&query.token)
+        .get_subscription_by_token(&query.token)
         .await?;
 
-    if let Some(id) = subscriber_id {
+    if let Some((id, list_id)) = subscription {
+        let confirmation = ConsentConfirmation {
+            confirmed_at: Utc::now(),
+            ip: Some(
+                client_ip(&headers, peer_addr.ip(), &app_state.config.trusted_proxies).to_string(),
+            ),
+            user_agent: headers
+                .get(USER_AGENT)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_owned),
+        };
+        let mut transaction = app_state.repository.begin_transaction().await?;
         app_state
             .repository
-            .update_subscriber_confirmation_status(&id)
+            .update_subscriber_confirmation_status_tx(&mut transaction, &id, &confirmation)
             .await?;
+        app_state
+            .repository
+            .confirm_list_subscription_tx(&mut transaction, &id, &list_id)
+            .await?;
+        transaction.commit().await.map_err(RepositoryError::from)?;
     } else {
-        return Err(DomainError::from("Token wasn't found").into());
+        return Err(NotFoundError::from("Token wasn't found").into());
     }
 
     Ok(())
 }
+
+/// The right-most `X-Forwarded-For` hop that isn't a trusted proxy. Only trusted proxies may
+/// forward the header, so clients can't forge the recorded address.
+fn client_ip(headers: &HeaderMap, peer_ip: IpAddr, trusted_proxies: &[IpAddr]) -> IpAddr {
+    if !trusted_proxies.contains(&peer_ip) {
+        return peer_ip;
+    }
+
+    let hops = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|v| v.trim().parse::<IpAddr>())
+        .collect::<Vec<_>>();
+    let mut client_ip = peer_ip;
+    for hop in hops.into_iter().rev() {
+        match hop {
+            Ok(ip) => {
+                client_ip = ip;
+                if !trusted_proxies.contains(&ip) {
+                    break;
+                }
+            }
+            Err(_) => break,
+        }
+    }
+    client_ip
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn forwarded_for(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn forwarded_for_is_ignored_from_untrusted_peers() {
+        let peer: IpAddr = "203.0.113.7".parse().unwrap();
+
+        let ip = client_ip(&forwarded_for("198.51.100.1"), peer, &[]);
+
+        assert_eq!(ip, peer);
+    }
+
+    #[test]
+    fn right_most_untrusted_hop_is_the_client() {
+        let proxies: Vec<IpAddr> = vec!["10.0.0.1".parse().unwrap(), "10.0.0.2".parse().unwrap()];
+        let headers = forwarded_for("1.1.1.1, 198.51.100.1, 10.0.0.2");
+
+        let ip = client_ip(&headers, proxies[0], &proxies);
+
+        assert_eq!(ip, "198.51.100.1".parse::<IpAddr>().unwrap());
+        assert_eq!(
+            client_ip(&forwarded_for("garbage"), proxies[0], &proxies),
+            proxies[0]
+        );
+    }
+}
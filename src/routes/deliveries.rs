@@ -0,0 +1,111 @@
+use crate::app_state::AppState;
+use crate::domain::entities::delivery::{Delivery, DeliverySummary};
+use crate::domain::value_objects::DeliveryStatus;
+use crate::error::{ApplicationError, DomainError};
+use crate::extractors::{PathParams, QueryParams};
+use crate::routes::issues::issue_by_id;
+use axum::extract::State;
+use axum::Json;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[derive(Deserialize)]
+pub struct DeliveriesQuery {
+    /// Only deliveries with this status, e.g. `failed`
+    status: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct DeliveryResponse {
+    subscriber_id: Uuid,
+    email: String,
+    status: String,
+    provider_message_id: Option<String>,
+    error: Option<String>,
+    attempts: i32,
+    queued_at: DateTime<Utc>,
+    sent_at: Option<DateTime<Utc>>,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct DeliverySummaryResponse {
+    queued: i64,
+    sent: i64,
+    failed: i64,
+    bounced: i64,
+    suppressed: i64,
+}
+
+impl From<Delivery> for DeliveryResponse {
+    fn from(delivery: Delivery) -> Self {
+        Self {
+            subscriber_id: *delivery.subscriber_id.as_ref(),
+            email: delivery.email.as_ref().to_owned(),
+            status: delivery.status.as_ref().to_owned(),
+            provider_message_id: delivery.provider_message_id,
+            error: delivery.error,
+            attempts: delivery.attempts,
+            queued_at: delivery.queued_at,
+            sent_at: delivery.sent_at,
+            updated_at: delivery.updated_at,
+        }
+    }
+}
+
+impl From<DeliverySummary> for DeliverySummaryResponse {
+    fn from(summary: DeliverySummary) -> Self {
+        Self {
+            queued: summary.queued,
+            sent: summary.sent,
+            failed: summary.failed,
+            bounced: summary.bounced,
+            suppressed: summary.suppressed,
+        }
+    }
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn get_deliveries(
+    State(app_state): State<Arc<AppState>>,
+    PathParams(issue_id): PathParams<Uuid>,
+    QueryParams(query): QueryParams<DeliveriesQuery>,
+) -> Result<Json<Vec<DeliveryResponse>>, ApplicationError> {
+    let status = query
+        .status
+        .map(|x| {
+            x.parse::<DeliveryStatus>()
+                .map_err(|_| DomainError::from(format!("{} is not valid delivery status", x)))
+        })
+        .transpose()?;
+    let issue = issue_by_id(&app_state, issue_id).await?;
+
+    let deliveries = app_state
+        .repository
+        .get_deliveries(&issue.id, status)
+        .await?
+        .into_iter()
+        .filter_map(|delivery| match delivery {
+            Ok(delivery) => Some(delivery.into()),
+            Err(error) => {
+                tracing::warn!(?error, "Skipped delivery with invalid stored data");
+                None
+            }
+        })
+        .collect();
+
+    Ok(Json(deliveries))
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn get_delivery_summary(
+    State(app_state): State<Arc<AppState>>,
+    PathParams(issue_id): PathParams<Uuid>,
+) -> Result<Json<DeliverySummaryResponse>, ApplicationError> {
+    let issue = issue_by_id(&app_state, issue_id).await?;
+    let summary = app_state.repository.get_delivery_summary(&issue.id).await?;
+
+    Ok(Json(summary.into()))
+}
@@ -0,0 +1,137 @@
+use crate::app_state::AppState;
+use crate::domain::entities::outbox_email::OutboxEmail;
+use crate::domain::value_objects::{Locale, OutboxEmailId, SubscriberEmail};
+use crate::email_templates::EmailTemplates;
+use crate::error::{
+    ApplicationError, DomainError, InternalLogicDomainError, NotFoundError, RepositoryError,
+};
+use crate::extractors::{FormBody, QueryParams};
+use crate::routes::preferences::preferences_link;
+use crate::routes::subscribe::generate_subscription_token;
+use axum::extract::State;
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+
+#[derive(Deserialize)]
+pub struct EmailChangeFormData {
+    /// Preference center token of the subscriber
+    token: String,
+    new_email: String,
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmEmailChangeQuery {
+    token: String,
+}
+
+/// Sends a confirmation link to the new address, the current one stays active until it's used.
+#[tracing::instrument(skip_all)]
+pub async fn request_email_change(
+    State(app_state): State<Arc<AppState>>,
+    FormBody(form): FormBody<EmailChangeFormData>,
+) -> Result<(), ApplicationError> {
+    let subscriber_id = app_state
+        .repository
+        .get_subscriber_id_by_preference_token(&form.token)
+        .await?
+        .ok_or_else(|| NotFoundError::from("Token wasn't found"))?;
+    let subscriber = app_state
+        .repository
+        .get_subscriber(&subscriber_id)
+        .await?
+        .ok_or_else(|| NotFoundError::from("Subscriber wasn't found"))?;
+
+    let new_email = SubscriberEmail::parse(form.new_email)?;
+    if new_email == subscriber.email {
+        return Err(DomainError::from("New email must differ from the current one").into());
+    }
+    if app_state.repository.email_exists(&new_email).await? {
+        return Err(DomainError::from(format!(
+            "{} is already used by another subscriber",
+            new_email.as_ref()
+        ))
+        .into());
+    }
+
+    let token = generate_subscription_token();
+    let templates =
+        EmailTemplates::load(&app_state.repository, &app_state.config.default_locale).await?;
+    let email = email_change_confirmation(
+        &new_email,
+        subscriber.locale.as_ref(),
+        &templates,
+        &token,
+        &form.token,
+        &app_state.config.base_url,
+    )
+    .map_err(InternalLogicDomainError::from)?;
+
+    let mut transaction = app_state.repository.begin_transaction().await?;
+    app_state
+        .repository
+        .insert_email_change_tx(
+            &mut transaction,
+            &subscriber.id,
+            &subscriber.email,
+            &new_email,
+            &token,
+        )
+        .await?;
+    app_state
+        .repository
+        .insert_outbox_email_tx(&mut transaction, &email)
+        .await?;
+    transaction.commit().await.map_err(RepositoryError::from)?;
+    app_state.outbox_notify.notify_one();
+
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn confirm_email_change(
+    State(app_state): State<Arc<AppState>>,
+    QueryParams(query): QueryParams<ConfirmEmailChangeQuery>,
+) -> Result<(), ApplicationError> {
+    let applied = match app_state.repository.apply_email_change(&query.token).await {
+        Ok(applied) => applied,
+        // The address was taken after the change had been requested
+        Err(RepositoryError::Domain(e)) => return Err(e.into()),
+        Err(e) => return Err(e.into()),
+    };
+
+    if !applied {
+        return Err(NotFoundError::from("Token wasn't found").into());
+    }
+
+    Ok(())
+}
+
+/// Renders the confirmation email for the outbox, it's sent once the change is committed.
+fn email_change_confirmation(
+    new_email: &SubscriberEmail,
+    locale: Option<&Locale>,
+    templates: &EmailTemplates,
+    token: &str,
+    preference_token: &str,
+    base_url: &str,
+) -> Result<OutboxEmail, DomainError> {
+    let context = json!({
+        "confirmation_link": format!(
+            "{}/subscriptions/email-change/confirm?token={}",
+            base_url, token
+        ),
+        "unsubscribe_link": preferences_link(base_url, preference_token),
+    });
+    let email = templates.render("email_change", locale, &context)?;
+
+    Ok(OutboxEmail {
+        id: OutboxEmailId::new(),
+        recipient: new_email.clone(),
+        subject: email.subject,
+        html_content: email.html,
+        text_content: email.text,
+        tag: Some("email-change".to_string()),
+        attempts: 0,
+    })
+}
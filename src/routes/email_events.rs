@@ -0,0 +1,76 @@
+use crate::app_state::AppState;
+use crate::domain::value_objects::SuppressionReason;
+use crate::email_events::{EmailEvent, EmailEventKind, ProviderEvent};
+use crate::error::ApplicationError;
+use crate::extractors::JsonBody;
+use anyhow::anyhow;
+use axum::extract::State;
+use axum::http::HeaderMap;
+use secrecy::ExposeSecret;
+use std::sync::Arc;
+use subtle::ConstantTimeEq;
+
+/// Receives delivery, bounce and complaint webhooks of the email provider. Bounced and
+/// complaining addresses are suppressed even when they aren't subscribed.
+#[tracing::instrument(skip_all)]
+pub async fn receive_email_event(
+    State(app_state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    JsonBody(payload): JsonBody<ProviderEvent>,
+) -> Result<(), ApplicationError> {
+    let token = headers
+        .get("X-Webhook-Token")
+        .map(|x| x.as_bytes())
+        .unwrap_or_default();
+    let expected = app_state
+        .config
+        .email_client
+        .webhook_token
+        .expose_secret()
+        .as_bytes();
+    if !bool::from(token.ct_eq(expected)) {
+        return Err(ApplicationError::AuthError(anyhow!(
+            "Webhook token is missing or invalid"
+        )));
+    }
+
+    let Some(event) = EmailEvent::parse(payload)? else {
+        return Ok(());
+    };
+    let repository = &app_state.repository;
+    let email = &event.email;
+
+    if matches!(
+        event.kind,
+        EmailEventKind::HardBounce | EmailEventKind::SoftBounce
+    ) {
+        repository
+            .mark_delivery_bounced(email, event.message_id.as_deref())
+            .await?;
+    }
+    match event.kind {
+        EmailEventKind::Delivered => {
+            repository.reset_soft_bounces(email).await?;
+        }
+        EmailEventKind::HardBounce => {
+            repository
+                .suppress_address(email, SuppressionReason::Bounced)
+                .await?;
+        }
+        EmailEventKind::Complaint => {
+            repository
+                .suppress_address(email, SuppressionReason::Complained)
+                .await?;
+        }
+        EmailEventKind::SoftBounce => {
+            let count = repository.increment_soft_bounces(email).await?;
+            if count >= Some(app_state.config.email_client.soft_bounce_threshold) {
+                repository
+                    .suppress_address(email, SuppressionReason::Bounced)
+                    .await?;
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use crate::app_state::AppState;
+use crate::email_providers::ProviderHealth;
+use axum::extract::State;
+use axum::Json;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::Arc;
+
+#[derive(Serialize)]
+pub struct ProviderHealthResponse {
+    name: String,
+    priority: u32,
+    /// `Closed`, `Open` or `HalfOpen`
+    state: String,
+    consecutive_failures: u32,
+    last_error: Option<String>,
+    last_failure_at: Option<DateTime<Utc>>,
+}
+
+impl From<ProviderHealth> for ProviderHealthResponse {
+    fn from(health: ProviderHealth) -> Self {
+        Self {
+            name: health.name,
+            priority: health.priority,
+            state: health.state.as_ref().to_owned(),
+            consecutive_failures: health.consecutive_failures,
+            last_error: health.last_error,
+            last_failure_at: health.last_failure_at,
+        }
+    }
+}
+
+/// Circuit breaker state of the email providers, in priority order.
+#[tracing::instrument(skip_all)]
+pub async fn get_email_providers(
+    State(app_state): State<Arc<AppState>>,
+) -> Json<Vec<ProviderHealthResponse>> {
+    Json(
+        app_state
+            .email_client
+            .provider_health()
+            .into_iter()
+            .map(ProviderHealthResponse::from)
+            .collect(),
+    )
+}
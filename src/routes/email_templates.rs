@@ -0,0 +1,173 @@
+use crate::app_state::AppState;
+use crate::domain::entities::email_template::EmailTemplate;
+use crate::domain::value_objects::{Locale, TemplateKind, TemplateName};
+use crate::email_templates::EmailTemplates;
+use crate::error::{ApplicationError, DomainError, InternalLogicDomainError, NotFoundError};
+use crate::extractors::{JsonBody, PathParams, QueryParams};
+use axum::extract::State;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::Arc;
+
+/// Templates rendered by the application, they can be edited but must stay sendable.
+const REQUIRED_EMAILS: [&str; 3] = ["confirmation", "email_change", "newsletter"];
+
+#[derive(Deserialize)]
+pub struct TemplateBody {
+    kind: String,
+    #[serde(default)]
+    subject: String,
+    html_body: String,
+    text_body: String,
+}
+
+#[derive(Deserialize)]
+pub struct TemplateQuery {
+    /// Defaults to the configured default locale
+    locale: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct TemplateResponse {
+    name: String,
+    locale: String,
+    kind: String,
+    subject: String,
+    html_body: String,
+    text_body: String,
+}
+
+impl From<EmailTemplate> for TemplateResponse {
+    fn from(template: EmailTemplate) -> Self {
+        Self {
+            name: template.name.as_ref().to_owned(),
+            locale: template.locale.as_ref().to_owned(),
+            kind: template.kind.as_ref().to_owned(),
+            subject: template.subject,
+            html_body: template.html_body,
+            text_body: template.text_body,
+        }
+    }
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn get_email_templates(
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<Vec<TemplateResponse>>, ApplicationError> {
+    let templates = app_state
+        .repository
+        .get_email_templates()
+        .await?
+        .into_iter()
+        .filter_map(|template| match template {
+            Ok(template) => Some(template.into()),
+            Err(error) => {
+                tracing::warn!(?error, "Skipped template with invalid stored data");
+                None
+            }
+        })
+        .collect();
+
+    Ok(Json(templates))
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn get_email_template(
+    State(app_state): State<Arc<AppState>>,
+    PathParams(name): PathParams<String>,
+    QueryParams(query): QueryParams<TemplateQuery>,
+) -> Result<Json<TemplateResponse>, ApplicationError> {
+    let name = TemplateName::parse(name)?;
+    let locale = template_locale(&app_state, query)?;
+    let template = app_state
+        .repository
+        .get_email_templates()
+        .await?
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .find(|template| template.name == name && template.locale == locale)
+        .ok_or_else(|| {
+            NotFoundError::from(format!(
+                "Template {} wasn't found for locale {}",
+                name.as_ref(),
+                locale.as_ref()
+            ))
+        })?;
+
+    Ok(Json(template.into()))
+}
+
+/// Creates or replaces a template. The change is rejected when it would leave any email
+/// template unrenderable, e.g. a broken layout or a missing partial.
+#[tracing::instrument(skip_all)]
+pub async fn put_email_template(
+    State(app_state): State<Arc<AppState>>,
+    PathParams(name): PathParams<String>,
+    QueryParams(query): QueryParams<TemplateQuery>,
+    JsonBody(body): JsonBody<TemplateBody>,
+) -> Result<Json<TemplateResponse>, ApplicationError> {
+    let template = EmailTemplate {
+        name: TemplateName::parse(name)?,
+        locale: template_locale(&app_state, query)?,
+        kind: body
+            .kind
+            .parse::<TemplateKind>()
+            .map_err(|_| DomainError::from(format!("{} is not valid template kind", body.kind)))?,
+        subject: body.subject,
+        html_body: body.html_body,
+        text_body: body.text_body,
+    };
+
+    let mut templates = app_state
+        .repository
+        .get_email_templates()
+        .await?
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+    templates.retain(|x| x.name != template.name || x.locale != template.locale);
+    templates.push(template.clone());
+
+    let candidate = EmailTemplates::new(templates, default_locale(&app_state)?)?;
+    let sample = json!({
+        "name": "Subscriber",
+        "title": "Title",
+        "content": "Content",
+        "confirmation_link": "https://example.com/confirm",
+        "unsubscribe_link": "https://example.com/unsubscribe",
+    });
+    let names = candidate
+        .email_names()
+        .map(str::to_owned)
+        .collect::<Vec<_>>();
+    for name in REQUIRED_EMAILS {
+        if !names.iter().any(|x| x == name) {
+            return Err(DomainError::from(format!("Email template {} is required", name)).into());
+        }
+    }
+    for locale in candidate.locales() {
+        for name in &names {
+            candidate.render(name, Some(locale), &sample)?;
+        }
+    }
+
+    app_state
+        .repository
+        .upsert_email_template(&template)
+        .await?;
+
+    Ok(Json(template.into()))
+}
+
+fn template_locale(app_state: &AppState, query: TemplateQuery) -> Result<Locale, ApplicationError> {
+    match query.locale {
+        Some(locale) => Ok(Locale::parse(locale)?),
+        None => default_locale(app_state),
+    }
+}
+
+fn default_locale(app_state: &AppState) -> Result<Locale, ApplicationError> {
+    Ok(Locale::parse(app_state.config.default_locale.clone())
+        .map_err(InternalLogicDomainError::from)?)
+}
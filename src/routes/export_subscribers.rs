@@ -0,0 +1,67 @@
+use crate::app_state::AppState;
+use crate::error::ApplicationError;
+use axum::extract::State;
+use axum::Json;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[derive(Serialize)]
+pub struct SubscriberExportRow {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+    source: String,
+    consent_version: Option<String>,
+    confirmed_at: Option<DateTime<Utc>>,
+    confirmation_ip: Option<String>,
+    confirmation_user_agent: Option<String>,
+    attributes: serde_json::Value,
+    locale: Option<String>,
+    tags: Vec<String>,
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn export_subscribers(
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<Vec<SubscriberExportRow>>, ApplicationError> {
+    let subscribers = app_state.repository.get_subscribers(&[], &[]).await?;
+
+    let rows = subscribers
+        .into_iter()
+        .filter_map(|subscriber| match subscriber {
+            Ok(subscriber) => Some(subscriber),
+            Err(error) => {
+                tracing::warn!(?error, "Skipped subscriber with invalid stored data");
+                None
+            }
+        })
+        .map(|subscriber| {
+            let confirmation = subscriber.confirmation;
+            SubscriberExportRow {
+                id: *subscriber.id.as_ref(),
+                email: subscriber.email.as_ref().to_owned(),
+                name: subscriber.name.as_ref().to_owned(),
+                status: subscriber.status.as_ref().to_owned(),
+                subscribed_at: subscriber.subscribed_at,
+                source: subscriber.source.as_ref().to_owned(),
+                consent_version: subscriber.consent_version,
+                confirmed_at: confirmation.as_ref().map(|c| c.confirmed_at),
+                confirmation_ip: confirmation.as_ref().and_then(|c| c.ip.clone()),
+                confirmation_user_agent: confirmation.and_then(|c| c.user_agent),
+                attributes: subscriber.attributes,
+                locale: subscriber.locale.map(|x| x.as_ref().to_owned()),
+                tags: subscriber
+                    .tags
+                    .iter()
+                    .map(|x| x.as_ref().to_owned())
+                    .collect(),
+            }
+        })
+        .collect();
+
+    Ok(Json(rows))
+}
@@ -0,0 +1,341 @@
+use crate::app_state::AppState;
+use crate::domain::entities::delivery::DeliveryUpdate;
+use crate::domain::entities::newsletter_issue::NewsletterIssue;
+use crate::domain::value_objects::{AssetId, Audience, IssueId, SubscriberEmail, SubscriberId};
+use crate::email_client::MAX_ATTACHMENTS_SIZE;
+use crate::error::{ApplicationError, DomainError, InternalLogicError, NotFoundError};
+use crate::extractors::{JsonBody, PathParams, QueryParams};
+use crate::html_processing::{inline_image_ids, process_html};
+use crate::issue_delivery::{seed_recipient, DeliveryResult, IssueRenderer};
+use crate::markdown::render_markdown;
+use crate::merge_fields::IssueContent;
+use crate::routes::audience::{resolve_audience, AudienceRequest};
+use anyhow::anyhow;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[derive(Deserialize)]
+pub struct CreateIssueBody {
+    title: String,
+    content: BodyContent,
+    /// Track opens and clicks of subscribers who didn't opt out
+    #[serde(default)]
+    tracking: bool,
+}
+
+/// Either `markdown` or both `html_content` and `text_content` must be given, explicit
+/// variants take precedence over the ones generated from Markdown.
+///
+/// Uploaded assets are shown inline by images with `cid:<asset id>` sources.
+#[derive(Deserialize)]
+pub struct BodyContent {
+    markdown: Option<String>,
+    text_content: Option<String>,
+    html_content: Option<String>,
+    /// Ids of uploaded assets attached to the email
+    #[serde(default)]
+    attachments: Vec<Uuid>,
+}
+
+impl BodyContent {
+    fn into_variants(self) -> Result<(String, String), DomainError> {
+        let generated = self.markdown.as_deref().map(render_markdown);
+        let (html, text) =
+            match generated {
+                Some(generated) => (
+                    self.html_content.unwrap_or(generated.html),
+                    self.text_content.unwrap_or(generated.text),
+                ),
+                None => match (self.html_content, self.text_content) {
+                    (Some(html), Some(text)) => (html, text),
+                    _ => return Err(
+                        "Either markdown or both html_content and text_content must be provided"
+                            .into(),
+                    ),
+                },
+            };
+        Ok((html, text))
+    }
+}
+
+#[derive(Serialize)]
+pub struct IssueResponse {
+    id: Uuid,
+}
+
+#[derive(Deserialize)]
+pub struct PreviewQuery {
+    subscriber_id: Uuid,
+}
+
+#[derive(Serialize)]
+pub struct PreviewResponse {
+    subject: String,
+    html: String,
+    text: String,
+}
+
+#[derive(Deserialize)]
+pub struct TestSendBody {
+    emails: Vec<String>,
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn create_issue(
+    State(app_state): State<Arc<AppState>>,
+    JsonBody(body): JsonBody<CreateIssueBody>,
+) -> Result<(StatusCode, Json<IssueResponse>), ApplicationError> {
+    let issue = store_issue(&app_state, body.title, body.content, body.tracking).await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(IssueResponse {
+            id: *issue.id.as_ref(),
+        }),
+    ))
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn preview_issue(
+    State(app_state): State<Arc<AppState>>,
+    PathParams(issue_id): PathParams<Uuid>,
+    QueryParams(query): QueryParams<PreviewQuery>,
+) -> Result<Json<PreviewResponse>, ApplicationError> {
+    let issue = issue_by_id(&app_state, issue_id).await?;
+    let recipient = app_state
+        .repository
+        .get_recipient(&SubscriberId::from(query.subscriber_id))
+        .await?
+        .ok_or_else(|| NotFoundError::from("Subscriber wasn't found"))??;
+
+    let email = IssueRenderer::load(&app_state, &issue)
+        .await?
+        .render(&recipient)?;
+
+    Ok(Json(PreviewResponse {
+        subject: email.subject,
+        html: email.html,
+        text: email.text,
+    }))
+}
+
+/// Sends the issue to the given addresses only, rendered for a placeholder subscriber.
+#[tracing::instrument(skip_all)]
+pub async fn test_send_issue(
+    State(app_state): State<Arc<AppState>>,
+    PathParams(issue_id): PathParams<Uuid>,
+    JsonBody(body): JsonBody<TestSendBody>,
+) -> Result<(), ApplicationError> {
+    if body.emails.is_empty() {
+        return Err(DomainError::from("At least one email must be provided").into());
+    }
+    let recipients = body
+        .emails
+        .into_iter()
+        .map(|email| seed_recipient(SubscriberEmail::parse(email)?))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let issue = issue_by_id(&app_state, issue_id).await?;
+    let renderer = IssueRenderer::load(&app_state, &issue).await?;
+    let results = renderer
+        .deliver(
+            &app_state.email_client,
+            &recipients,
+            &app_state.config.email_client.delivery,
+        )
+        .await;
+
+    check_deliveries(results)
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn publish_issue(
+    State(app_state): State<Arc<AppState>>,
+    PathParams(issue_id): PathParams<Uuid>,
+    JsonBody(audience): JsonBody<AudienceRequest>,
+) -> Result<(), ApplicationError> {
+    let issue = issue_by_id(&app_state, issue_id).await?;
+    let audience = resolve_audience(&app_state, audience).await?;
+
+    deliver_issue(&app_state, &issue, &audience).await
+}
+
+/// Validates and stores the content as a new unpublished issue.
+pub async fn store_issue(
+    app_state: &AppState,
+    title: String,
+    content: BodyContent,
+    tracking_enabled: bool,
+) -> Result<NewsletterIssue, ApplicationError> {
+    let mut attachments = content.attachments.clone();
+    attachments.sort_unstable();
+    attachments.dedup();
+    let (html_content, text_content) = content.into_variants()?;
+    let html_content = process_html(&html_content, &app_state.config.base_url)?;
+    IssueContent::parse(title.clone(), html_content.clone(), text_content.clone())?;
+    let inline_images = inline_image_ids(&html_content)
+        .into_iter()
+        .map(|id| {
+            Uuid::parse_str(id)
+                .map(AssetId::from)
+                .map_err(|_| DomainError::from(format!("cid:{} isn't an uploaded asset", id)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let attachments = attachments
+        .into_iter()
+        .map(AssetId::from)
+        .filter(|x| !inline_images.contains(x))
+        .collect();
+
+    let issue = NewsletterIssue {
+        id: IssueId::new(),
+        title,
+        html_content,
+        text_content,
+        tracking_enabled,
+        attachments,
+        inline_images,
+    };
+    check_assets(app_state, &issue).await?;
+    app_state.repository.insert_issue(&issue).await?;
+
+    Ok(issue)
+}
+
+/// Referenced assets must exist, inline ones must be images and all of them together must fit
+/// into one email.
+async fn check_assets(
+    app_state: &AppState,
+    issue: &NewsletterIssue,
+) -> Result<(), ApplicationError> {
+    let ids = issue
+        .attachments
+        .iter()
+        .chain(&issue.inline_images)
+        .copied()
+        .collect::<Vec<_>>();
+    if ids.is_empty() {
+        return Ok(());
+    }
+    let assets = app_state.repository.get_assets(&ids).await?;
+
+    let mut size = 0;
+    for id in &ids {
+        let asset = assets
+            .iter()
+            .find(|x| x.id == *id)
+            .ok_or_else(|| NotFoundError::from(format!("Asset {} wasn't found", id.as_ref())))?;
+        if issue.inline_images.contains(id) && !asset.content_type.starts_with("image/") {
+            return Err(DomainError::from(format!(
+                "Asset {} is shown inline but isn't an image",
+                id.as_ref()
+            ))
+            .into());
+        }
+        size += asset.content.len();
+    }
+    if size > MAX_ATTACHMENTS_SIZE {
+        return Err(DomainError::from(format!(
+            "Attachments of {} bytes exceed the limit of {} bytes",
+            size, MAX_ATTACHMENTS_SIZE
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// Sends the issue to audience members it wasn't delivered to yet and retries failed deliveries.
+pub async fn deliver_issue(
+    app_state: &AppState,
+    issue: &NewsletterIssue,
+    audience: &Audience,
+) -> Result<(), ApplicationError> {
+    let renderer = IssueRenderer::load(app_state, issue).await?;
+    let recipients = app_state
+        .repository
+        .get_recipients(audience)
+        .await?
+        .into_iter()
+        .filter_map(|recipient| match recipient {
+            Ok(recipient) => Some(recipient),
+            Err(error) => {
+                tracing::warn!(
+                    ?error,
+                    "Skipped confirmed subscriber with invalid stored data",
+                );
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let subscriber_ids = recipients.iter().map(|x| x.id.clone()).collect::<Vec<_>>();
+    let queued = app_state
+        .repository
+        .queue_deliveries(&issue.id, &subscriber_ids)
+        .await?
+        .iter()
+        .map(|x| *x.as_ref())
+        .collect::<HashSet<_>>();
+    let recipients = recipients
+        .into_iter()
+        .filter(|x| queued.contains(x.id.as_ref()))
+        .collect::<Vec<_>>();
+
+    let results = renderer
+        .deliver(
+            &app_state.email_client,
+            &recipients,
+            &app_state.config.email_client.delivery,
+        )
+        .await;
+
+    let updates = results.iter().map(DeliveryUpdate::from).collect::<Vec<_>>();
+    app_state
+        .repository
+        .record_deliveries(&issue.id, &updates)
+        .await?;
+    app_state.repository.mark_issue_published(&issue.id).await?;
+    check_deliveries(results)
+}
+
+/// Logs failed recipients and reports them as one error once everybody else was sent to.
+fn check_deliveries(results: Vec<DeliveryResult>) -> Result<(), ApplicationError> {
+    let total = results.len();
+    let mut failed = 0;
+    for DeliveryResult {
+        subscriber_id,
+        result,
+    } in results
+    {
+        if let Err(error) = result {
+            failed += 1;
+            tracing::warn!(?error, ?subscriber_id, "Failed to send issue");
+        }
+    }
+
+    if failed > 0 {
+        return Err(InternalLogicError::from(anyhow!(
+            "{} of {} emails failed to send",
+            failed,
+            total
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+pub async fn issue_by_id(
+    app_state: &AppState,
+    issue_id: Uuid,
+) -> Result<NewsletterIssue, ApplicationError> {
+    app_state
+        .repository
+        .get_issue(&IssueId::from(issue_id))
+        .await?
+        .ok_or_else(|| NotFoundError::from("Issue wasn't found").into())
+}
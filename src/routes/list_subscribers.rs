@@ -0,0 +1,75 @@
+use crate::app_state::AppState;
+use crate::error::ApplicationError;
+use crate::extractors::QueryParams;
+use crate::routes::audience::parse_tags;
+use axum::extract::State;
+use axum::Json;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[derive(Deserialize)]
+pub struct ListSubscribersQuery {
+    /// Comma separated tags the subscriber must all have
+    tags: Option<String>,
+    /// Comma separated tags the subscriber must not have
+    exclude_tags: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct SubscriberListItem {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+    tags: Vec<String>,
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn list_subscribers(
+    State(app_state): State<Arc<AppState>>,
+    QueryParams(query): QueryParams<ListSubscribersQuery>,
+) -> Result<Json<Vec<SubscriberListItem>>, ApplicationError> {
+    let with_tags = parse_tags(split_comma_separated(query.tags))?;
+    let without_tags = parse_tags(split_comma_separated(query.exclude_tags))?;
+
+    let subscribers = app_state
+        .repository
+        .get_subscribers(&with_tags, &without_tags)
+        .await?
+        .into_iter()
+        .filter_map(|subscriber| match subscriber {
+            Ok(subscriber) => Some(SubscriberListItem {
+                id: *subscriber.id.as_ref(),
+                email: subscriber.email.as_ref().to_owned(),
+                name: subscriber.name.as_ref().to_owned(),
+                status: subscriber.status.as_ref().to_owned(),
+                subscribed_at: subscriber.subscribed_at,
+                tags: subscriber
+                    .tags
+                    .iter()
+                    .map(|x| x.as_ref().to_owned())
+                    .collect(),
+            }),
+            Err(error) => {
+                tracing::warn!(?error, "Skipped subscriber with invalid stored data");
+                None
+            }
+        })
+        .collect();
+
+    Ok(Json(subscribers))
+}
+
+fn split_comma_separated(value: Option<String>) -> Vec<String> {
+    value
+        .map(|v| {
+            v.split(',')
+                .map(|x| x.trim().to_owned())
+                .filter(|x| !x.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
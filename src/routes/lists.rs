@@ -0,0 +1,80 @@
+use crate::app_state::AppState;
+use crate::domain::entities::mailing_list::MailingList;
+use crate::domain::value_objects::{ListId, ListSlug};
+use crate::error::{ApplicationError, DomainError};
+use crate::extractors::JsonBody;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[derive(Deserialize)]
+pub struct CreateListBody {
+    slug: String,
+    name: String,
+}
+
+#[derive(Serialize)]
+pub struct ListResponse {
+    id: Uuid,
+    slug: String,
+    name: String,
+}
+
+impl From<MailingList> for ListResponse {
+    fn from(list: MailingList) -> Self {
+        Self {
+            id: *list.id.as_ref(),
+            slug: list.slug.as_ref().to_owned(),
+            name: list.name,
+        }
+    }
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn create_list(
+    State(app_state): State<Arc<AppState>>,
+    JsonBody(body): JsonBody<CreateListBody>,
+) -> Result<(StatusCode, Json<ListResponse>), ApplicationError> {
+    if body.name.trim().is_empty() {
+        return Err(DomainError::from("List name must not be empty").into());
+    }
+
+    let list = MailingList {
+        id: ListId::new(),
+        slug: ListSlug::parse(body.slug)?,
+        name: body.name,
+    };
+
+    let created = app_state.repository.insert_list(&list).await?;
+    if !created {
+        return Err(
+            DomainError::from(format!("List {} already exists", list.slug.as_ref())).into(),
+        );
+    }
+
+    Ok((StatusCode::CREATED, Json(list.into())))
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn get_lists(
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<Vec<ListResponse>>, ApplicationError> {
+    let lists = app_state
+        .repository
+        .get_lists()
+        .await?
+        .into_iter()
+        .filter_map(|list| match list {
+            Ok(list) => Some(list.into()),
+            Err(error) => {
+                tracing::warn!(?error, "Skipped list with invalid stored data");
+                None
+            }
+        })
+        .collect();
+
+    Ok(Json(lists))
+}
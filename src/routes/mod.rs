@@ -1,3 +1,20 @@
+pub mod assets;
+pub mod audience;
 pub mod confirm_subscription;
+pub mod deliveries;
+pub mod email_change;
+pub mod email_events;
+pub mod email_providers;
+pub mod email_templates;
+pub mod export_subscribers;
+pub mod issues;
+pub mod list_subscribers;
+pub mod lists;
+pub mod preferences;
 pub mod publish_newsletter;
+pub mod segments;
 pub mod subscribe;
+pub mod subscriber_attributes;
+pub mod subscriber_tags;
+pub mod suppressions;
+pub mod tracking;
@@ -0,0 +1,228 @@
+use crate::app_state::AppState;
+use crate::domain::entities::mailing_list::ListMembership;
+use crate::domain::entities::subscriber::Subscriber;
+use crate::domain::value_objects::{
+    ConfirmationStatus, ContentFormat, SubscriberId, SubscriberName,
+};
+use crate::email_templates::escape_html;
+use crate::error::{ApplicationError, DomainError, NotFoundError};
+use crate::extractors::{FormBody, QueryParams};
+use axum::extract::State;
+use axum::response::Html;
+use serde::Deserialize;
+use std::fmt::Write;
+use std::sync::Arc;
+
+#[derive(Deserialize)]
+pub struct PreferencesQuery {
+    token: String,
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn get_preferences(
+    State(app_state): State<Arc<AppState>>,
+    QueryParams(query): QueryParams<PreferencesQuery>,
+) -> Result<Html<String>, ApplicationError> {
+    let subscriber_id = subscriber_by_token(&app_state, &query.token).await?;
+
+    render_preferences(&app_state, &subscriber_id, &query.token, None).await
+}
+
+/// Accepts the preference center form: `token`, `name`, `content_format`, one `list` field per
+/// selected list slug, `tracking_opt_out` and `unsubscribe` to leave every list.
+#[tracing::instrument(skip_all)]
+pub async fn update_preferences(
+    State(app_state): State<Arc<AppState>>,
+    FormBody(fields): FormBody<Vec<(String, String)>>,
+) -> Result<Html<String>, ApplicationError> {
+    let field = |name: &str| {
+        fields
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.clone())
+    };
+
+    let token = field("token").ok_or_else(|| DomainError::from("Token must be provided"))?;
+    let subscriber_id = subscriber_by_token(&app_state, &token).await?;
+
+    if field("unsubscribe").is_some() {
+        app_state
+            .repository
+            .unsubscribe_subscriber(&subscriber_id)
+            .await?;
+        return render_preferences(
+            &app_state,
+            &subscriber_id,
+            &token,
+            Some("You have been unsubscribed from all lists."),
+        )
+        .await;
+    }
+
+    let name = SubscriberName::parse(
+        field("name").ok_or_else(|| DomainError::from("Name must be provided"))?,
+    )?;
+    let content_format = field("content_format")
+        .map(|x| {
+            x.parse::<ContentFormat>()
+                .map_err(|_| DomainError::from(format!("{} is not valid content format", x)))
+        })
+        .transpose()?
+        .unwrap_or_default();
+
+    let lists = app_state.repository.get_lists().await?;
+    let mut list_ids = vec![];
+    for slug in fields
+        .iter()
+        .filter(|(key, _)| key == "list")
+        .map(|(_, value)| value)
+    {
+        let list = lists
+            .iter()
+            .flatten()
+            .find(|list| list.slug.as_ref() == slug)
+            .ok_or_else(|| NotFoundError::from(format!("List {} wasn't found", slug)))?;
+        list_ids.push(list.id);
+    }
+
+    app_state
+        .repository
+        .update_subscriber_preferences(
+            &subscriber_id,
+            &name,
+            content_format,
+            field("tracking_opt_out").is_some(),
+            &list_ids,
+        )
+        .await?;
+
+    render_preferences(
+        &app_state,
+        &subscriber_id,
+        &token,
+        Some("Your preferences have been saved."),
+    )
+    .await
+}
+
+pub fn preferences_link(base_url: &str, preference_token: &str) -> String {
+    format!(
+        "{}/subscriptions/preferences?token={}",
+        base_url, preference_token
+    )
+}
+
+async fn subscriber_by_token(
+    app_state: &AppState,
+    token: &str,
+) -> Result<SubscriberId, ApplicationError> {
+    app_state
+        .repository
+        .get_subscriber_id_by_preference_token(token)
+        .await?
+        .ok_or_else(|| NotFoundError::from("Token wasn't found").into())
+}
+
+async fn render_preferences(
+    app_state: &AppState,
+    subscriber_id: &SubscriberId,
+    token: &str,
+    notice: Option<&str>,
+) -> Result<Html<String>, ApplicationError> {
+    let subscriber = app_state
+        .repository
+        .get_subscriber(subscriber_id)
+        .await?
+        .ok_or_else(|| NotFoundError::from("Subscriber wasn't found"))?;
+    let memberships = app_state
+        .repository
+        .get_subscriber_memberships(subscriber_id)
+        .await?
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Html(preferences_page(
+        &subscriber,
+        &memberships,
+        token,
+        notice,
+    )))
+}
+
+fn preferences_page(
+    subscriber: &Subscriber,
+    memberships: &[ListMembership],
+    token: &str,
+    notice: Option<&str>,
+) -> String {
+    let mut lists = String::new();
+    for ListMembership { list, status } in memberships {
+        let checked = match status {
+            Some(ConfirmationStatus::Confirmed) => " checked",
+            _ => "",
+        };
+        let _ = writeln!(
+            lists,
+            r#"<label><input type="checkbox" name="list" value="{}"{}> {}</label><br>"#,
+            escape_html(list.slug.as_ref()),
+            checked,
+            escape_html(&list.name)
+        );
+    }
+
+    let format_option = |format: ContentFormat, label: &str| {
+        let checked = if subscriber.content_format == format {
+            " checked"
+        } else {
+            ""
+        };
+        format!(
+            r#"<label><input type="radio" name="content_format" value="{}"{}> {}</label>"#,
+            format.as_ref(),
+            checked,
+            label
+        )
+    };
+
+    format!(
+        r#"<!doctype html>
+<html>
+<head><meta charset="utf-8"><title>Subscription preferences</title></head>
+<body>
+<h1>Subscription preferences</h1>
+<p>{notice}</p>
+<form method="post" action="/subscriptions/preferences">
+<input type="hidden" name="token" value="{token}">
+<label>Name <input type="text" name="name" value="{name}"></label><br>
+<fieldset><legend>Lists</legend>
+{lists}</fieldset>
+<fieldset><legend>Format</legend>
+{html} {plain_text}
+</fieldset>
+<label><input type="checkbox" name="tracking_opt_out" value="on"{tracking_opt_out}> Don't track opens and clicks</label><br>
+<button type="submit">Save</button>
+</form>
+<form method="post" action="/subscriptions/email-change">
+<input type="hidden" name="token" value="{token}">
+<label>New email <input type="email" name="new_email"></label>
+<button type="submit">Change email</button>
+</form>
+<form method="post" action="/subscriptions/preferences">
+<input type="hidden" name="token" value="{token}">
+<button type="submit" name="unsubscribe" value="all">Unsubscribe from everything</button>
+</form>
+</body>
+</html>"#,
+        notice = escape_html(notice.unwrap_or_default()),
+        token = escape_html(token),
+        name = escape_html(subscriber.name.as_ref()),
+        lists = lists,
+        html = format_option(ContentFormat::Html, "HTML"),
+        plain_text = format_option(ContentFormat::PlainText, "Plain text"),
+        tracking_opt_out = if subscriber.tracking_opt_out {
+            " checked"
+        } else {
+            ""
+        },
+    )
+}
@@ -1,7 +1,9 @@
 use crate::app_state::AppState;
-use crate::error::{ApplicationError, InternalLogicError};
+use crate::error::ApplicationError;
+use crate::extractors::JsonBody;
+use crate::routes::audience::{resolve_audience, AudienceRequest};
+use crate::routes::issues::{deliver_issue, store_issue, BodyContent};
 use axum::extract::State;
-use axum::Json;
 use serde::Deserialize;
 use std::sync::Arc;
 
@@ -9,41 +11,25 @@ use std::sync::Arc;
 pub struct BodyData {
     title: String,
     content: BodyContent,
+    #[serde(default)]
+    tracking: bool,
+    #[serde(flatten)]
+    audience: AudienceRequest,
 }
 
-#[derive(Deserialize)]
-pub struct BodyContent {
-    text_content: String,
-    html_content: String,
-}
 #[tracing::instrument(skip_all)]
 pub async fn publish_newsletter(
     app_state: State<Arc<AppState>>,
-    Json(body_data): Json<BodyData>,
+    JsonBody(body_data): JsonBody<BodyData>,
 ) -> Result<(), ApplicationError> {
-    let emails = app_state.repository.get_confirmed_emails().await?;
+    let audience = resolve_audience(&app_state, body_data.audience).await?;
+    let issue = store_issue(
+        &app_state,
+        body_data.title,
+        body_data.content,
+        body_data.tracking,
+    )
+    .await?;
 
-    for email in emails {
-        match email {
-            Ok(email) => {
-                app_state
-                    .email_client
-                    .send(
-                        &email,
-                        &body_data.title,
-                        &body_data.content.html_content,
-                        &body_data.content.text_content,
-                    )
-                    .await
-                    .map_err(InternalLogicError::from)?;
-            }
-            Err(error) => {
-                tracing::warn!(
-                    ?error,
-                    "Skipped confirmed subscriber with invalid stored data",
-                )
-            }
-        }
-    }
-    Ok(())
+    deliver_issue(&app_state, &issue, &audience).await
 }
@@ -0,0 +1,115 @@
+use crate::app_state::AppState;
+use crate::domain::entities::segment::Segment;
+use crate::domain::value_objects::{SegmentFilter, SegmentId};
+use crate::error::{ApplicationError, DomainError};
+use crate::extractors::{JsonBody, PathParams, QueryParams};
+use crate::routes::audience::{resolve_audience, AudienceRequest};
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[derive(Deserialize)]
+pub struct CreateSegmentBody {
+    name: String,
+    filter: String,
+}
+
+#[derive(Serialize)]
+pub struct SegmentResponse {
+    id: Uuid,
+    name: String,
+    filter: String,
+}
+
+impl From<Segment> for SegmentResponse {
+    fn from(segment: Segment) -> Self {
+        Self {
+            id: *segment.id.as_ref(),
+            name: segment.name,
+            filter: segment.filter.as_ref().to_owned(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SegmentCountQuery {
+    /// Comma separated list slugs
+    lists: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct SegmentCountResponse {
+    count: i64,
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn create_segment(
+    State(app_state): State<Arc<AppState>>,
+    JsonBody(body): JsonBody<CreateSegmentBody>,
+) -> Result<(StatusCode, Json<SegmentResponse>), ApplicationError> {
+    if body.name.trim().is_empty() {
+        return Err(DomainError::from("Segment name must not be empty").into());
+    }
+
+    let segment = Segment {
+        id: SegmentId::new(),
+        name: body.name,
+        filter: SegmentFilter::parse(body.filter)?,
+    };
+
+    let created = app_state.repository.insert_segment(&segment).await?;
+    if !created {
+        return Err(DomainError::from(format!("Segment {} already exists", segment.name)).into());
+    }
+
+    Ok((StatusCode::CREATED, Json(segment.into())))
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn get_segments(
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<Vec<SegmentResponse>>, ApplicationError> {
+    let segments = app_state
+        .repository
+        .get_segments()
+        .await?
+        .into_iter()
+        .filter_map(|segment| match segment {
+            Ok(segment) => Some(segment.into()),
+            Err(error) => {
+                tracing::warn!(?error, "Skipped segment with invalid stored data");
+                None
+            }
+        })
+        .collect();
+
+    Ok(Json(segments))
+}
+
+/// Dry run: how many subscribers a send to the segment would reach.
+#[tracing::instrument(skip_all)]
+pub async fn count_segment(
+    State(app_state): State<Arc<AppState>>,
+    PathParams(segment_id): PathParams<Uuid>,
+    QueryParams(query): QueryParams<SegmentCountQuery>,
+) -> Result<Json<SegmentCountResponse>, ApplicationError> {
+    let request = AudienceRequest {
+        lists: query
+            .lists
+            .map(|lists| lists.split(',').map(|x| x.trim().to_owned()).collect())
+            .unwrap_or_default(),
+        segment_id: Some(segment_id),
+        ..Default::default()
+    };
+    let audience = resolve_audience(&app_state, request).await?;
+
+    let count = app_state
+        .repository
+        .count_confirmed_subscribers(&audience)
+        .await?;
+
+    Ok(Json(SegmentCountResponse { count }))
+}
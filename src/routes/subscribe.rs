@@ -1,93 +1,218 @@
 use crate::app_state::AppState;
+use crate::domain::entities::outbox_email::OutboxEmail;
 use crate::domain::entities::subscriber::Subscriber;
-use crate::domain::value_objects::{SubscriberEmail, SubscriberId, SubscriberName};
-use crate::email_client::EmailClient;
-use crate::error::{ApplicationError, DomainError, RepositoryError};
+use crate::domain::value_objects::{
+    ConfirmationStatus, ContentFormat, ListSlug, Locale, OutboxEmailId, SubscriberEmail,
+    SubscriberId, SubscriberName, SubscriptionSource,
+};
+use crate::email_templates::EmailTemplates;
+use crate::error::{
+    ApplicationError, DomainError, InternalLogicDomainError, InternalLogicError, NotFoundError,
+    RepositoryError, ValidationError,
+};
+use crate::extractors::FormBody;
+use crate::routes::preferences::preferences_link;
+use anyhow::anyhow;
 use axum::extract::State;
-use axum::Form;
+use axum::http::{header, HeaderMap};
+use chrono::Utc;
 use rand::{thread_rng, Rng};
 use serde::Deserialize;
+use serde_json::json;
 use std::sync::Arc;
 use tracing::info;
 
 #[derive(Deserialize, Debug)]
 pub struct SubscribeFormData {
-    name: String,
-    email: String,
+    /// Required, missing fields are reported along with the invalid ones
+    name: Option<String>,
+    email: Option<String>,
+    list: Option<String>,
+    /// Preferred locale, `Accept-Language` is used when missing
+    locale: Option<String>,
 }
 
 #[tracing::instrument(skip(app_state))]
 pub async fn subscribe(
     State(app_state): State<Arc<AppState>>,
-    Form(form): Form<SubscribeFormData>,
+    headers: HeaderMap,
+    FormBody(form): FormBody<SubscribeFormData>,
 ) -> Result<(), ApplicationError> {
+    let (name, email, locale, list_slug) = parse_form(form)?;
+    let locale = locale.or_else(|| {
+        headers
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|x| x.to_str().ok())
+            .and_then(Locale::from_accept_language)
+    });
+    let list_slug = list_slug.unwrap_or_default();
+    let list_id = app_state
+        .repository
+        .get_list_id_by_slug(&list_slug)
+        .await?
+        .ok_or_else(|| NotFoundError::from(format!("List {} wasn't found", list_slug.as_ref())))?;
+
     let subscriber = Subscriber {
         id: SubscriberId::new(),
-        email: SubscriberEmail::parse(form.email)?,
-        name: SubscriberName::parse(form.name)?,
+        email,
+        name,
+        status: ConfirmationStatus::PendingConfirmation,
+        subscribed_at: Utc::now(),
+        source: SubscriptionSource::Form,
+        consent_version: Some(app_state.config.consent_version.clone()),
+        confirmation: None,
+        attributes: serde_json::json!({}),
+        content_format: ContentFormat::default(),
+        locale,
+        tracking_opt_out: false,
+        tags: vec![],
     };
     let token = generate_subscription_token();
+    let templates =
+        EmailTemplates::load(&app_state.repository, &app_state.config.default_locale).await?;
+
+    let mut transaction = app_state.repository.begin_transaction().await?;
+    // Inserting first instead of looking the email up avoids racing a concurrent sign-up
+    let inserted = app_state
+        .repository
+        .insert_subscriber_tx(&mut transaction, &subscriber)
+        .await?;
 
-    {
-        let mut transaction = app_state.repository.begin_transaction().await?;
+    // An existing subscriber keeps the stored name and locale, the form can't change them
+    let (subscriber, preference_token) = if inserted {
+        let preference_token = generate_subscription_token();
         app_state
             .repository
-            .insert_subscriber_tx(&mut transaction, &subscriber)
+            .store_preference_token_tx(&mut transaction, &subscriber.id, &preference_token)
             .await?;
+        (subscriber, preference_token)
+    } else {
+        let subscriber = app_state
+            .repository
+            .get_subscriber_by_email_tx(&mut transaction, &subscriber.email)
+            .await?
+            .ok_or_else(|| InternalLogicError::from(anyhow!("Existing subscriber wasn't found")))?;
+        let preference_token = app_state
+            .repository
+            .get_preference_token_tx(&mut transaction, &subscriber.id)
+            .await?
+            .ok_or_else(|| {
+                InternalLogicError::from(anyhow!("Subscriber has no preference token"))
+            })?;
+        (subscriber, preference_token)
+    };
 
+    let status = app_state
+        .repository
+        .insert_list_subscription_tx(&mut transaction, &subscriber.id, &list_id)
+        .await?;
+    let needs_confirmation = status == ConfirmationStatus::PendingConfirmation;
+    if needs_confirmation {
         app_state
             .repository
-            .store_token_tx(&mut transaction, &subscriber, &token)
+            .store_token_tx(&mut transaction, &subscriber.id, &list_id, &token)
+            .await?;
+        let email = confirmation_email(
+            &subscriber,
+            &templates,
+            &token,
+            &preference_token,
+            &app_state.config.base_url,
+        )
+        .map_err(InternalLogicDomainError::from)?;
+        app_state
+            .repository
+            .insert_outbox_email_tx(&mut transaction, &email)
             .await?;
-
-        transaction.commit().await.map_err(RepositoryError::from)?;
     }
+    transaction.commit().await.map_err(RepositoryError::from)?;
 
-    send_confirmation_email(
-        &subscriber,
-        &app_state.email_client,
-        &token,
-        &app_state.config.base_url,
-    )
-    .await
-    .map_err(|e| DomainError::from(format!("{}", e)))?;
+    if !needs_confirmation {
+        info!("Subscriber is already confirmed in the list");
+        return Ok(());
+    }
+    app_state.outbox_notify.notify_one();
 
     info!("New subscription!");
 
     Ok(())
 }
 
-#[tracing::instrument(skip_all)]
-async fn send_confirmation_email(
+/// Parses every field, so all invalid and missing ones are reported together.
+fn parse_form(
+    form: SubscribeFormData,
+) -> Result<
+    (
+        SubscriberName,
+        SubscriberEmail,
+        Option<Locale>,
+        Option<ListSlug>,
+    ),
+    ValidationError,
+> {
+    let mut errors = ValidationError::default();
+    let mut required = |field: &'static str, value: Option<String>| {
+        if value.is_none() {
+            errors.add(field, format!("{} is missing", field));
+        }
+        value
+    };
+    let name = required("name", form.name);
+    let email = required("email", form.email);
+
+    let name = name.and_then(|x| {
+        SubscriberName::parse(x)
+            .map_err(|e| errors.add("name", e))
+            .ok()
+    });
+    let email = email.and_then(|x| {
+        SubscriberEmail::parse(x)
+            .map_err(|e| errors.add("email", e))
+            .ok()
+    });
+    let locale = form
+        .locale
+        .and_then(|x| Locale::parse(x).map_err(|e| errors.add("locale", e)).ok());
+    let list_slug = form
+        .list
+        .and_then(|x| ListSlug::parse(x).map_err(|e| errors.add("list", e)).ok());
+
+    match (name, email) {
+        (Some(name), Some(email)) if errors.is_empty() => Ok((name, email, locale, list_slug)),
+        _ => Err(errors),
+    }
+}
+
+/// Renders the confirmation email for the outbox, it's sent once the subscription is committed.
+fn confirmation_email(
     subscriber: &Subscriber,
-    email_client: &EmailClient,
+    templates: &EmailTemplates,
     confirmation_token: &str,
+    preference_token: &str,
     base_url: &str,
-) -> Result<(), anyhow::Error> {
-    let subject = "Welcome!";
-    let confirmation_link = format!(
-        "{}/subscriptions/confirm?token={}",
-        base_url, confirmation_token
-    );
-
-    let plain_body = format!(
-        "Welcome to our newsletter!\nVisit {} to confirm your subscription",
-        confirmation_link
-    );
+) -> Result<OutboxEmail, DomainError> {
+    let context = json!({
+        "name": subscriber.name.as_ref(),
+        "confirmation_link": format!(
+            "{}/subscriptions/confirm?token={}",
+            base_url, confirmation_token
+        ),
+        "unsubscribe_link": preferences_link(base_url, preference_token),
+    });
+    let email = templates.render("confirmation", subscriber.locale.as_ref(), &context)?;
 
-    let html_body = format!(
-        "Welcome to our newsletter!<br />\
-Click <a href=\"{}\">here</a> to confirm your subscription.",
-        confirmation_link
-    );
-    email_client
-        .send(&subscriber.email, subject, &html_body, &plain_body)
-        .await?;
-
-    Ok(())
+    Ok(OutboxEmail {
+        id: OutboxEmailId::new(),
+        recipient: subscriber.email.clone(),
+        subject: email.subject,
+        html_content: email.html,
+        text_content: email.text,
+        tag: Some("confirmation".to_string()),
+        attempts: 0,
+    })
 }
 
-fn generate_subscription_token() -> String {
+pub fn generate_subscription_token() -> String {
     let mut rng = thread_rng();
     std::iter::repeat_with(|| rng.sample(rand::distributions::Alphanumeric))
         .map(char::from)
@@ -0,0 +1,31 @@
+use crate::app_state::AppState;
+use crate::domain::value_objects::SubscriberId;
+use crate::error::{ApplicationError, DomainError, NotFoundError};
+use crate::extractors::{JsonBody, PathParams};
+use axum::extract::State;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Merges the JSON object into the subscriber attributes, `null` values remove attributes.
+#[tracing::instrument(skip_all)]
+pub async fn update_subscriber_attributes(
+    State(app_state): State<Arc<AppState>>,
+    PathParams(subscriber_id): PathParams<Uuid>,
+    JsonBody(attributes): JsonBody<serde_json::Value>,
+) -> Result<(), ApplicationError> {
+    if !attributes.is_object() {
+        return Err(DomainError::from("Attributes must be a JSON object").into());
+    }
+
+    let updated = app_state
+        .repository
+        .merge_subscriber_attributes(&SubscriberId::from(subscriber_id), &attributes)
+        .await?;
+    if !updated {
+        return Err(
+            NotFoundError::from(format!("Subscriber {} wasn't found", subscriber_id)).into(),
+        );
+    }
+
+    Ok(())
+}
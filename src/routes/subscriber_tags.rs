@@ -0,0 +1,105 @@
+use crate::app_state::AppState;
+use crate::domain::value_objects::{SubscriberId, Tag};
+use crate::error::{ApplicationError, NotFoundError};
+use crate::extractors::{JsonBody, PathParams};
+use crate::routes::audience::parse_tags;
+use axum::extract::State;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[derive(Deserialize)]
+pub struct AddTagsBody {
+    tags: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct BulkTagsBody {
+    subscriber_ids: Vec<Uuid>,
+    #[serde(default)]
+    add: Vec<String>,
+    #[serde(default)]
+    remove: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct BulkTagsResponse {
+    added: u64,
+    removed: u64,
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn add_subscriber_tags(
+    State(app_state): State<Arc<AppState>>,
+    PathParams(subscriber_id): PathParams<Uuid>,
+    JsonBody(body): JsonBody<AddTagsBody>,
+) -> Result<(), ApplicationError> {
+    let subscriber_id = existing_subscriber(&app_state, subscriber_id).await?;
+    let tags = parse_tags(body.tags)?;
+
+    app_state
+        .repository
+        .add_tags(&[subscriber_id], &tags)
+        .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn remove_subscriber_tag(
+    State(app_state): State<Arc<AppState>>,
+    PathParams((subscriber_id, tag)): PathParams<(Uuid, String)>,
+) -> Result<(), ApplicationError> {
+    let subscriber_id = existing_subscriber(&app_state, subscriber_id).await?;
+    let tag = Tag::parse(tag)?;
+
+    app_state
+        .repository
+        .remove_tags(&[subscriber_id], &[tag])
+        .await?;
+
+    Ok(())
+}
+
+/// Adds and removes tags for many subscribers at once, unknown subscriber ids are ignored.
+#[tracing::instrument(skip_all)]
+pub async fn bulk_update_tags(
+    State(app_state): State<Arc<AppState>>,
+    JsonBody(body): JsonBody<BulkTagsBody>,
+) -> Result<Json<BulkTagsResponse>, ApplicationError> {
+    let subscriber_ids = body
+        .subscriber_ids
+        .into_iter()
+        .map(SubscriberId::from)
+        .collect::<Vec<_>>();
+    let add = parse_tags(body.add)?;
+    let remove = parse_tags(body.remove)?;
+
+    let added = app_state.repository.add_tags(&subscriber_ids, &add).await?;
+    let removed = app_state
+        .repository
+        .remove_tags(&subscriber_ids, &remove)
+        .await?;
+
+    Ok(Json(BulkTagsResponse { added, removed }))
+}
+
+async fn existing_subscriber(
+    app_state: &AppState,
+    subscriber_id: Uuid,
+) -> Result<SubscriberId, ApplicationError> {
+    let subscriber_id = SubscriberId::from(subscriber_id);
+    if !app_state
+        .repository
+        .subscriber_exists(&subscriber_id)
+        .await?
+    {
+        return Err(NotFoundError::from(format!(
+            "Subscriber {} wasn't found",
+            subscriber_id.as_ref()
+        ))
+        .into());
+    }
+    Ok(subscriber_id)
+}
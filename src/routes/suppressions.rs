@@ -0,0 +1,91 @@
+use crate::app_state::AppState;
+use crate::domain::entities::suppression::Suppression;
+use crate::domain::value_objects::{SuppressionReason, SuppressionTarget};
+use crate::error::{ApplicationError, DomainError, NotFoundError};
+use crate::extractors::{JsonBody, PathParams};
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+#[derive(Deserialize)]
+pub struct CreateSuppressionBody {
+    /// Email address or domain
+    value: String,
+}
+
+#[derive(Serialize)]
+pub struct SuppressionResponse {
+    value: String,
+    domain: bool,
+    reason: String,
+    created_at: DateTime<Utc>,
+}
+
+impl From<Suppression> for SuppressionResponse {
+    fn from(suppression: Suppression) -> Self {
+        Self {
+            domain: suppression.target.is_domain(),
+            value: suppression.target.as_ref().to_owned(),
+            reason: suppression.reason.as_ref().to_owned(),
+            created_at: suppression.created_at,
+        }
+    }
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn get_suppressions(
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<Vec<SuppressionResponse>>, ApplicationError> {
+    let suppressions = app_state
+        .repository
+        .get_suppressions()
+        .await?
+        .into_iter()
+        .filter_map(|suppression| match suppression {
+            Ok(suppression) => Some(suppression.into()),
+            Err(error) => {
+                tracing::warn!(?error, "Skipped suppression with invalid stored data");
+                None
+            }
+        })
+        .collect();
+
+    Ok(Json(suppressions))
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn create_suppression(
+    State(app_state): State<Arc<AppState>>,
+    JsonBody(body): JsonBody<CreateSuppressionBody>,
+) -> Result<StatusCode, ApplicationError> {
+    let target = SuppressionTarget::parse(body.value)?;
+
+    let created = app_state
+        .repository
+        .insert_suppression(&target, SuppressionReason::Manual)
+        .await?;
+    if !created {
+        return Err(DomainError::from(format!("{} is already suppressed", target.as_ref())).into());
+    }
+
+    Ok(StatusCode::CREATED)
+}
+
+/// Lifts a suppression, e.g. one added by mistake. Subscriber statuses are left unchanged.
+#[tracing::instrument(skip_all)]
+pub async fn delete_suppression(
+    State(app_state): State<Arc<AppState>>,
+    PathParams(value): PathParams<String>,
+) -> Result<StatusCode, ApplicationError> {
+    let target = SuppressionTarget::parse(value)?;
+
+    let deleted = app_state.repository.delete_suppression(&target).await?;
+    if !deleted {
+        return Err(NotFoundError::from(format!("{} isn't suppressed", target.as_ref())).into());
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,110 @@
+use crate::app_state::AppState;
+use crate::domain::entities::newsletter_issue::IssueStats;
+use crate::domain::value_objects::TrackingEventKind;
+use crate::error::{ApplicationError, DomainError};
+use crate::extractors::{PathParams, QueryParams};
+use crate::routes::issues::issue_by_id;
+use crate::tracking::TrackingSigner;
+use axum::extract::State;
+use axum::http::header;
+use axum::response::{IntoResponse, Redirect};
+use axum::Json;
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Transparent 1x1 GIF.
+const PIXEL: [u8; 43] = [
+    0x47, 0x49, 0x46, 0x38, 0x39, 0x61, 0x01, 0x00, 0x01, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xff, 0xff, 0xff, 0x21, 0xf9, 0x04, 0x01, 0x00, 0x00, 0x00, 0x00, 0x2c, 0x00, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x01, 0x00, 0x00, 0x02, 0x02, 0x44, 0x01, 0x00, 0x3b,
+];
+
+#[derive(Deserialize)]
+pub struct ClickQuery {
+    url: String,
+}
+
+#[derive(Serialize)]
+pub struct IssueStatsResponse {
+    opens: i64,
+    unique_opens: i64,
+    clicks: i64,
+    unique_clicks: i64,
+}
+
+impl From<IssueStats> for IssueStatsResponse {
+    fn from(stats: IssueStats) -> Self {
+        Self {
+            opens: stats.opens,
+            unique_opens: stats.unique_opens,
+            clicks: stats.clicks,
+            unique_clicks: stats.unique_clicks,
+        }
+    }
+}
+
+/// Always answers with the pixel, invalid tokens are just not recorded.
+#[tracing::instrument(skip_all)]
+pub async fn track_open(
+    State(app_state): State<Arc<AppState>>,
+    PathParams(token): PathParams<String>,
+) -> impl IntoResponse {
+    let signer = TrackingSigner::new(app_state.config.tracking_secret.expose_secret());
+    if let Some((issue_id, subscriber_id)) = signer.verify(&token, None) {
+        if let Err(error) = app_state
+            .repository
+            .insert_tracking_event(&issue_id, &subscriber_id, TrackingEventKind::Open, None)
+            .await
+        {
+            tracing::warn!(?error, "Failed to record open");
+        }
+    }
+
+    (
+        [
+            (header::CONTENT_TYPE, "image/gif"),
+            (header::CACHE_CONTROL, "no-store"),
+        ],
+        PIXEL,
+    )
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn track_click(
+    State(app_state): State<Arc<AppState>>,
+    PathParams(token): PathParams<String>,
+    QueryParams(query): QueryParams<ClickQuery>,
+) -> Result<Redirect, ApplicationError> {
+    let signer = TrackingSigner::new(app_state.config.tracking_secret.expose_secret());
+    let (issue_id, subscriber_id) = signer
+        .verify(&token, Some(&query.url))
+        .ok_or_else(|| DomainError::from("Tracking link is invalid"))?;
+
+    if let Err(error) = app_state
+        .repository
+        .insert_tracking_event(
+            &issue_id,
+            &subscriber_id,
+            TrackingEventKind::Click,
+            Some(&query.url),
+        )
+        .await
+    {
+        tracing::warn!(?error, "Failed to record click");
+    }
+
+    Ok(Redirect::to(&query.url))
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn get_issue_stats(
+    State(app_state): State<Arc<AppState>>,
+    PathParams(issue_id): PathParams<Uuid>,
+) -> Result<Json<IssueStatsResponse>, ApplicationError> {
+    let issue = issue_by_id(&app_state, issue_id).await?;
+    let stats = app_state.repository.get_issue_stats(&issue.id).await?;
+
+    Ok(Json(stats.into()))
+}
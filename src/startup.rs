@@ -1,25 +1,49 @@
 use crate::app_config::{AppConfig, DatabaseConfig};
 use crate::app_state::AppState;
 use crate::domain::value_objects::SubscriberEmail;
-use crate::email_client::EmailClient;
+use crate::email_client::{DkimSigner, EmailClient};
+use crate::email_providers::EmailProviders;
+use crate::error::{ErrorCode, Problem};
 use crate::infrastructure::sqlx_postgres_repository::SqlxPostgresRepository;
 use crate::middlewares::basic_auth::basic_auth;
+use crate::middlewares::problem_details::problem_details;
+use crate::outbox_relay::run_outbox_relay;
+use crate::rate_limiter::SendRateLimiter;
+use crate::routes::assets::{upload_asset, MAX_ASSET_SIZE};
 use crate::routes::confirm_subscription::confirm_subscription;
+use crate::routes::deliveries::{get_deliveries, get_delivery_summary};
+use crate::routes::email_change::{confirm_email_change, request_email_change};
+use crate::routes::email_events::receive_email_event;
+use crate::routes::email_providers::get_email_providers;
+use crate::routes::email_templates::{get_email_template, get_email_templates, put_email_template};
+use crate::routes::export_subscribers::export_subscribers;
+use crate::routes::issues::{create_issue, preview_issue, publish_issue, test_send_issue};
+use crate::routes::list_subscribers::list_subscribers;
+use crate::routes::lists::{create_list, get_lists};
+use crate::routes::preferences::{get_preferences, update_preferences};
 use crate::routes::publish_newsletter::publish_newsletter;
+use crate::routes::segments::{count_segment, create_segment, get_segments};
 use crate::routes::subscribe::subscribe;
+use crate::routes::subscriber_attributes::update_subscriber_attributes;
+use crate::routes::subscriber_tags::{
+    add_subscriber_tags, bulk_update_tags, remove_subscriber_tag,
+};
+use crate::routes::suppressions::{create_suppression, delete_suppression, get_suppressions};
+use crate::routes::tracking::{get_issue_stats, track_click, track_open};
 use anyhow::anyhow;
 use axum::body::Body;
-use axum::extract::Request;
-use axum::http::StatusCode;
+use axum::extract::{DefaultBodyLimit, Request};
 use axum::middleware::Next;
 use axum::response::IntoResponse;
-use axum::routing::{get, post};
+use axum::routing::{delete, get, patch, post};
 use axum::Router;
 use sqlx::postgres::PgPoolOptions;
 use sqlx::PgPool;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::net::TcpListener;
+use tokio::sync::Notify;
 use tracing::{error, info, info_span};
 
 pub async fn build(config: AppConfig) -> Result<(TcpListener, AppState), anyhow::Error> {
@@ -30,11 +54,23 @@ pub async fn build(config: AppConfig) -> Result<(TcpListener, AppState), anyhow:
     let address = format!("{}:{}", config.host, config.port);
     let listener = TcpListener::bind(address).await?;
 
+    let repository = SqlxPostgresRepository::new(db_pool);
     let email_client = EmailClient::new(
-        config.email_client.base_url.to_string(),
-        config.email_client.authorization_token.to_string(),
+        EmailProviders::new(
+            &config.email_client.providers,
+            &config.email_client.circuit_breaker,
+        )?,
         SubscriberEmail::parse(config.email_client.sender_email.to_string())
             .map_err(|e| anyhow!(e))?,
+        config.email_client.sender_name.clone(),
+        repository.clone(),
+        SendRateLimiter::new(&config.email_client.rate_limit),
+        config
+            .email_client
+            .dkim
+            .as_ref()
+            .map(DkimSigner::load)
+            .transpose()?,
     );
     let addr = listener.local_addr()?;
     if addr.ip().is_unspecified() {
@@ -47,12 +83,11 @@ pub async fn build(config: AppConfig) -> Result<(TcpListener, AppState), anyhow:
         info!("Listening http://{}", listener.local_addr()?);
     }
 
-    let repository = SqlxPostgresRepository::new(db_pool);
-
     let state = AppState {
         repository,
         email_client,
         config,
+        outbox_notify: Notify::new(),
     };
     Ok((listener, state))
 }
@@ -72,12 +107,69 @@ pub async fn run_until_stopped(
     listener: TcpListener,
 ) -> Result<(), anyhow::Error> {
     let state = Arc::new(state);
+    tokio::spawn(run_outbox_relay(state.clone()));
     let router = Router::new()
         .route("/newsletter", post(publish_newsletter))
+        .route("/admin/subscribers", get(list_subscribers))
+        .route("/admin/subscribers/export", get(export_subscribers))
+        .route("/admin/lists", get(get_lists).post(create_list))
+        .route("/admin/segments", get(get_segments).post(create_segment))
+        .route("/admin/segments/:segment_id/count", get(count_segment))
+        .route(
+            "/admin/subscribers/:subscriber_id/attributes",
+            patch(update_subscriber_attributes),
+        )
+        .route(
+            "/admin/subscribers/:subscriber_id/tags",
+            post(add_subscriber_tags),
+        )
+        .route(
+            "/admin/subscribers/:subscriber_id/tags/:tag",
+            delete(remove_subscriber_tag),
+        )
+        .route("/admin/tags/bulk", post(bulk_update_tags))
+        .route(
+            "/admin/assets",
+            post(upload_asset).layer(DefaultBodyLimit::max(MAX_ASSET_SIZE)),
+        )
+        .route("/admin/issues", post(create_issue))
+        .route("/admin/issues/:issue_id/preview", get(preview_issue))
+        .route("/admin/issues/:issue_id/test-send", post(test_send_issue))
+        .route("/admin/issues/:issue_id/publish", post(publish_issue))
+        .route("/admin/issues/:issue_id/stats", get(get_issue_stats))
+        .route("/admin/issues/:issue_id/deliveries", get(get_deliveries))
+        .route(
+            "/admin/issues/:issue_id/deliveries/summary",
+            get(get_delivery_summary),
+        )
+        .route(
+            "/admin/suppressions",
+            get(get_suppressions).post(create_suppression),
+        )
+        .route("/admin/suppressions/:value", delete(delete_suppression))
+        .route("/admin/email-providers", get(get_email_providers))
+        .route("/admin/templates", get(get_email_templates))
+        .route(
+            "/admin/templates/:name",
+            get(get_email_template).put(put_email_template),
+        )
         .layer(axum::middleware::from_fn_with_state(state.clone(), basic_auth))
         .route("/health", get(|| async {}))
         .route("/subscriptions", post(subscribe))
         .route("/subscriptions/confirm", get(confirm_subscription))
+        .route("/t/open/:token", get(track_open))
+        .route("/t/click/:token", get(track_click))
+        .route("/webhooks/email-events", post(receive_email_event))
+        .route(
+            "/subscriptions/preferences",
+            get(get_preferences).post(update_preferences),
+        )
+        .route("/subscriptions/email-change", post(request_email_change))
+        .route(
+            "/subscriptions/email-change/confirm",
+            get(confirm_email_change),
+        )
+        .fallback(|| async { Problem::new(ErrorCode::NotFound, "Route wasn't found") })
         .layer(
             tower_http::trace::TraceLayer::new_for_http()
                 .make_span_with(|req: &Request<Body>| {
@@ -92,30 +184,21 @@ pub async fn run_until_stopped(
                 })
         )
         .layer(axum::middleware::from_fn(log_error_response))
-        .layer(axum::middleware::from_fn(override_code))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            problem_details,
+        ))
         .layer(tower_http::request_id::PropagateRequestIdLayer::x_request_id())
         .layer(tower_http::request_id::SetRequestIdLayer::x_request_id(
             tower_http::request_id::MakeRequestUuid,
         ))
-        .fallback(|| async { (StatusCode::NOT_FOUND, "Route wasn't found") })
         .with_state(state)
-        .into_make_service();
+        .into_make_service_with_connect_info::<SocketAddr>();
 
     axum::serve(listener, router).await?;
     Ok(())
 }
 
-async fn override_code(req: Request, next: Next) -> impl IntoResponse {
-    let mut response = next.run(req).await;
-
-    let status = response.status_mut();
-
-    if *status == StatusCode::UNPROCESSABLE_ENTITY {
-        *status = StatusCode::BAD_REQUEST;
-    }
-
-    response
-}
 async fn log_error_response(req: Request, next: Next) -> impl IntoResponse {
     let mut response = next.run(req).await;
     let status = response.status_mut();
@@ -0,0 +1,141 @@
+use crate::domain::value_objects::{IssueId, SubscriberId};
+use data_encoding::BASE64URL_NOPAD;
+use hmac::{Hmac, Mac};
+use reqwest::Url;
+use sha3::Sha3_256;
+use uuid::Uuid;
+
+type HmacSha3 = Hmac<Sha3_256>;
+
+const IDS_LEN: usize = 32;
+
+/// Signs per recipient tracking ids, so opens and clicks can't be forged and the click
+/// redirect only leads to links that were part of the issue.
+#[derive(Clone)]
+pub struct TrackingSigner {
+    secret: Vec<u8>,
+}
+
+impl TrackingSigner {
+    pub fn new(secret: &str) -> Self {
+        Self {
+            secret: secret.as_bytes().to_vec(),
+        }
+    }
+
+    /// Signs the ids, click tokens also sign the target `url`.
+    pub fn sign(
+        &self,
+        issue_id: &IssueId,
+        subscriber_id: &SubscriberId,
+        url: Option<&str>,
+    ) -> String {
+        let mut token = Vec::with_capacity(IDS_LEN + 32);
+        token.extend_from_slice(issue_id.as_ref().as_bytes());
+        token.extend_from_slice(subscriber_id.as_ref().as_bytes());
+        let signature = self.mac(&token, url).finalize().into_bytes();
+        token.extend_from_slice(&signature);
+
+        BASE64URL_NOPAD.encode(&token)
+    }
+
+    pub fn verify(&self, token: &str, url: Option<&str>) -> Option<(IssueId, SubscriberId)> {
+        let token = BASE64URL_NOPAD.decode(token.as_bytes()).ok()?;
+        if token.len() <= IDS_LEN {
+            return None;
+        }
+        let (ids, signature) = token.split_at(IDS_LEN);
+        self.mac(ids, url).verify_slice(signature).ok()?;
+
+        let issue_id = Uuid::from_slice(&ids[..16]).ok()?;
+        let subscriber_id = Uuid::from_slice(&ids[16..]).ok()?;
+        Some((IssueId::from(issue_id), SubscriberId::from(subscriber_id)))
+    }
+
+    pub fn open_url(
+        &self,
+        base_url: &str,
+        issue_id: &IssueId,
+        subscriber_id: &SubscriberId,
+    ) -> String {
+        format!(
+            "{}/t/open/{}",
+            base_url,
+            self.sign(issue_id, subscriber_id, None)
+        )
+    }
+
+    pub fn click_url(
+        &self,
+        base_url: &str,
+        issue_id: &IssueId,
+        subscriber_id: &SubscriberId,
+        url: &str,
+    ) -> String {
+        let path = format!(
+            "{}/t/click/{}",
+            base_url,
+            self.sign(issue_id, subscriber_id, Some(url))
+        );
+        match Url::parse_with_params(&path, [("url", url)]) {
+            Ok(tracked) => tracked.into(),
+            Err(_) => url.to_owned(),
+        }
+    }
+
+    fn mac(&self, ids: &[u8], url: Option<&str>) -> HmacSha3 {
+        let mut mac =
+            HmacSha3::new_from_slice(&self.secret).expect("HMAC accepts keys of any size");
+        mac.update(ids);
+        if let Some(url) = url {
+            mac.update(url.as_bytes());
+        }
+        mac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids() -> (IssueId, SubscriberId) {
+        (IssueId::new(), SubscriberId::new())
+    }
+
+    #[test]
+    fn signed_ids_are_verified() {
+        let signer = TrackingSigner::new("secret");
+        let (issue_id, subscriber_id) = ids();
+
+        let token = signer.sign(&issue_id, &subscriber_id, Some("https://example.com"));
+
+        assert_eq!(
+            signer.verify(&token, Some("https://example.com")),
+            Some((issue_id, subscriber_id))
+        );
+    }
+
+    #[test]
+    fn token_for_other_url_is_rejected() {
+        let signer = TrackingSigner::new("secret");
+        let (issue_id, subscriber_id) = ids();
+
+        let token = signer.sign(&issue_id, &subscriber_id, Some("https://example.com"));
+
+        assert_eq!(
+            signer.verify(&token, Some("https://evil.example.com")),
+            None
+        );
+        assert_eq!(signer.verify(&token, None), None);
+    }
+
+    #[test]
+    fn token_signed_with_other_secret_is_rejected() {
+        let (issue_id, subscriber_id) = ids();
+
+        let token = TrackingSigner::new("other").sign(&issue_id, &subscriber_id, None);
+
+        assert_eq!(TrackingSigner::new("secret").verify(&token, None), None);
+        assert_eq!(TrackingSigner::new("secret").verify("garbage", None), None);
+    }
+}
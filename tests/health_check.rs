@@ -1,5 +1,9 @@
-use crate::helpers::spawn_app;
+use crate::helpers::{batch_response, eventually, spawn_app, spawn_app_with, SmtpServer};
 use maplit::hashmap;
+use std::num::NonZeroU32;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+use zero2prod::app_config::{DkimConfig, EmailProviderConfig, EmailProviderKind};
 use zero2prod::domain::value_objects::ConfirmationStatus;
 
 mod helpers;
@@ -100,3 +104,1685 @@ async fn subscribe_returns_a_400_when_fields_are_present_but_invalid() -> Result
 
     Ok(())
 }
+
+#[tokio::test]
+async fn subscribe_reports_every_invalid_field() -> Result<(), anyhow::Error> {
+    let app = spawn_app().await?;
+
+    let invalid = app
+        .post_subscriptions(&hashmap! { "name" => "", "locale" => "not a locale" })
+        .await?;
+
+    assert_eq!(400, invalid.status().as_u16());
+    let problem: serde_json::Value = invalid.json().await?;
+    assert_eq!(problem["code"], "validation_failed");
+    let fields = problem["errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|x| x["field"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(fields, ["email", "name", "locale"]);
+    assert_eq!(problem["errors"][0]["reason"], "email is missing");
+    Ok(())
+}
+
+#[tokio::test]
+async fn confirmation_records_consent_provenance() -> Result<(), anyhow::Error> {
+    let app = spawn_app_with(|config| {
+        config.trusted_proxies = vec!["127.0.0.1".parse().unwrap()];
+    })
+    .await?;
+    let form = hashmap! {
+        "name" => "Le Guin",
+        "email" =>"ursula_le_guin@gmail.com"
+    };
+    app.post_subscriptions(&form).await?.error_for_status()?;
+    let token = app
+        .confirmation_token("ursula_le_guin@gmail.com", "default")
+        .await?;
+
+    let response = reqwest::Client::new()
+        .get(format!("{}/subscriptions/confirm", app.base_address))
+        .query(&[("token", &token)])
+        .header("User-Agent", "integration-test")
+        .header("X-Forwarded-For", "198.51.100.1, 203.0.113.7")
+        .send()
+        .await?;
+    assert!(response.status().is_success());
+
+    let saved = sqlx::query!(
+        r#"
+    SELECT status, source, consent_version, confirmed_at, confirmation_ip, confirmation_user_agent
+    FROM subscriptions
+    "#
+    )
+    .fetch_one(&app.pool)
+    .await?;
+
+    assert_eq!(saved.status, ConfirmationStatus::Confirmed.as_ref());
+    assert_eq!(saved.source, "Form");
+    assert!(saved.consent_version.is_some());
+    assert!(saved.confirmed_at.is_some());
+    assert_eq!(saved.confirmation_ip.as_deref(), Some("203.0.113.7"));
+    assert_eq!(
+        saved.confirmation_user_agent.as_deref(),
+        Some("integration-test")
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn subscribers_export_includes_consent_provenance() -> Result<(), anyhow::Error> {
+    let app = spawn_app().await?;
+    let form = hashmap! {
+        "name" => "Le Guin",
+        "email" =>"ursula_le_guin@gmail.com"
+    };
+    app.post_subscriptions(&form).await?.error_for_status()?;
+
+    let unauthorized =
+        reqwest::get(format!("{}/admin/subscribers/export", app.base_address)).await?;
+    assert_eq!(401, unauthorized.status().as_u16());
+
+    let export: serde_json::Value = app
+        .admin_get("/admin/subscribers/export")
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    let row = &export[0];
+    assert_eq!(row["email"], "ursula_le_guin@gmail.com");
+    assert_eq!(row["source"], "Form");
+    assert_eq!(row["status"], "PendingConfirmation");
+    assert!(row["consent_version"].is_string());
+    assert!(row["confirmed_at"].is_null());
+    Ok(())
+}
+
+#[tokio::test]
+async fn subscriber_confirms_each_list_separately() -> Result<(), anyhow::Error> {
+    let app = spawn_app().await?;
+    app.admin_post("/admin/lists")
+        .json(&serde_json::json!({ "slug": "rust-weekly", "name": "Rust weekly" }))
+        .send()
+        .await?
+        .error_for_status()?;
+
+    for list in ["default", "rust-weekly"] {
+        let form = hashmap! {
+            "name" => "Le Guin",
+            "email" => "ursula_le_guin@gmail.com",
+            "list" => list,
+        };
+        app.post_subscriptions(&form).await?.error_for_status()?;
+    }
+    let token = app
+        .confirmation_token("ursula_le_guin@gmail.com", "rust-weekly")
+        .await?;
+    app.confirm(&token).await?.error_for_status()?;
+
+    let memberships = sqlx::query!(
+        r#"
+    SELECT l.slug, ls.status FROM list_subscriptions ls
+    JOIN lists l ON l.id = ls.list_id
+    ORDER BY l.slug
+    "#
+    )
+    .fetch_all(&app.pool)
+    .await?;
+
+    assert_eq!(memberships.len(), 2);
+    assert_eq!(memberships[0].slug, "default");
+    assert_eq!(
+        memberships[0].status,
+        ConfirmationStatus::PendingConfirmation.as_ref()
+    );
+    assert_eq!(memberships[1].slug, "rust-weekly");
+    assert_eq!(
+        memberships[1].status,
+        ConfirmationStatus::Confirmed.as_ref()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn subscribe_returns_404_for_unknown_list() -> Result<(), anyhow::Error> {
+    let app = spawn_app().await?;
+    let form = hashmap! {
+        "name" => "Le Guin",
+        "email" => "ursula_le_guin@gmail.com",
+        "list" => "missing",
+    };
+
+    let response = app.post_subscriptions(&form).await?;
+
+    assert_eq!(404, response.status().as_u16());
+    Ok(())
+}
+
+#[tokio::test]
+async fn segment_count_matches_attribute_filter() -> Result<(), anyhow::Error> {
+    let app = spawn_app().await?;
+    let german = app
+        .create_confirmed_subscriber("Hermann", "hermann@example.com")
+        .await?;
+    let french = app
+        .create_confirmed_subscriber("Albert", "albert@example.com")
+        .await?;
+    for (id, attributes) in [
+        (german, serde_json::json!({ "locale": "de", "age": 30 })),
+        (french, serde_json::json!({ "locale": "fr", "age": 40 })),
+    ] {
+        app.admin_patch(&format!("/admin/subscribers/{}/attributes", id))
+            .json(&attributes)
+            .send()
+            .await?
+            .error_for_status()?;
+    }
+
+    let segment: serde_json::Value = app
+        .admin_post("/admin/segments")
+        .json(&serde_json::json!({
+            "name": "German adults",
+            "filter": r#"attr.locale = "de" and attr.age >= 18 and joined_after 2000-01-01"#,
+        }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    let count: serde_json::Value = app
+        .admin_get(&format!(
+            "/admin/segments/{}/count",
+            segment["id"].as_str().unwrap()
+        ))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    assert_eq!(count["count"], 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn invalid_segment_filter_is_rejected() -> Result<(), anyhow::Error> {
+    let app = spawn_app().await?;
+
+    let response = app
+        .admin_post("/admin/segments")
+        .json(&serde_json::json!({ "name": "Broken", "filter": "attr.locale > \"de\"" }))
+        .send()
+        .await?;
+
+    assert_eq!(400, response.status().as_u16());
+    Ok(())
+}
+
+#[tokio::test]
+async fn subscriber_listing_filters_by_tags() -> Result<(), anyhow::Error> {
+    let app = spawn_app().await?;
+    let vip = app
+        .create_confirmed_subscriber("Hermann", "hermann@example.com")
+        .await?;
+    let churned = app
+        .create_confirmed_subscriber("Albert", "albert@example.com")
+        .await?;
+
+    app.admin_post(&format!("/admin/subscribers/{}/tags", vip))
+        .json(&serde_json::json!({ "tags": ["vip"] }))
+        .send()
+        .await?
+        .error_for_status()?;
+    let bulk: serde_json::Value = app
+        .admin_post("/admin/tags/bulk")
+        .json(&serde_json::json!({
+            "subscriber_ids": [vip, churned],
+            "add": ["beta", "churned"],
+        }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(bulk["added"], 4);
+    reqwest::Client::new()
+        .delete(format!(
+            "{}/admin/subscribers/{}/tags/churned",
+            app.base_address, vip
+        ))
+        .basic_auth(&app.test_user.username, Some(&app.test_user.password))
+        .send()
+        .await?
+        .error_for_status()?;
+
+    let listed: serde_json::Value = app
+        .admin_get("/admin/subscribers?tags=beta&exclude_tags=churned")
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    assert_eq!(listed.as_array().map(|x| x.len()), Some(1));
+    assert_eq!(listed[0]["email"], "hermann@example.com");
+    assert_eq!(listed[0]["tags"], serde_json::json!(["beta", "vip"]));
+    Ok(())
+}
+
+#[tokio::test]
+async fn preference_center_updates_name_format_and_lists() -> Result<(), anyhow::Error> {
+    let app = spawn_app().await?;
+    app.admin_post("/admin/lists")
+        .json(&serde_json::json!({ "slug": "rust-weekly", "name": "Rust weekly" }))
+        .send()
+        .await?
+        .error_for_status()?;
+    app.create_confirmed_subscriber("Le Guin", "ursula_le_guin@gmail.com")
+        .await?;
+    let token = app.preference_token("ursula_le_guin@gmail.com").await?;
+
+    let page = reqwest::get(format!(
+        "{}/subscriptions/preferences?token={}",
+        app.base_address, token
+    ))
+    .await?
+    .error_for_status()?
+    .text()
+    .await?;
+    assert!(page.contains(r#"value="Le Guin""#));
+
+    app.post_preferences(&[
+        ("token", &token),
+        ("name", "Ursula K. Le Guin"),
+        ("content_format", "PlainText"),
+        ("list", "rust-weekly"),
+    ])
+    .await?
+    .error_for_status()?;
+
+    let saved = sqlx::query!("SELECT name, content_format FROM subscriptions")
+        .fetch_one(&app.pool)
+        .await?;
+    assert_eq!(saved.name, "Ursula K. Le Guin");
+    assert_eq!(saved.content_format, "PlainText");
+    let memberships = sqlx::query!(
+        r#"
+    SELECT l.slug, ls.status FROM list_subscriptions ls
+    JOIN lists l ON l.id = ls.list_id
+    ORDER BY l.slug
+    "#
+    )
+    .fetch_all(&app.pool)
+    .await?;
+    assert_eq!(
+        memberships[0].status,
+        ConfirmationStatus::Unsubscribed.as_ref()
+    );
+    assert_eq!(
+        memberships[1].status,
+        ConfirmationStatus::Confirmed.as_ref()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn preference_center_unsubscribes_from_everything() -> Result<(), anyhow::Error> {
+    let app = spawn_app().await?;
+    app.create_confirmed_subscriber("Le Guin", "ursula_le_guin@gmail.com")
+        .await?;
+    let token = app.preference_token("ursula_le_guin@gmail.com").await?;
+
+    app.post_preferences(&[("token", &token), ("unsubscribe", "all")])
+        .await?
+        .error_for_status()?;
+
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.pool)
+        .await?;
+    assert_eq!(saved.status, ConfirmationStatus::Unsubscribed.as_ref());
+    let invalid_name = app
+        .post_preferences(&[("token", &token), ("name", "<script>")])
+        .await?;
+    assert_eq!(400, invalid_name.status().as_u16());
+    Ok(())
+}
+
+#[tokio::test]
+async fn email_change_takes_effect_after_confirmation() -> Result<(), anyhow::Error> {
+    let app = spawn_app().await?;
+    app.create_confirmed_subscriber("Le Guin", "ursula_le_guin@gmail.com")
+        .await?;
+    let token = app.preference_token("ursula_le_guin@gmail.com").await?;
+
+    app.post_email_change(&token, "ursula@earthsea.com")
+        .await?
+        .error_for_status()?;
+    let pending = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.pool)
+        .await?;
+    assert_eq!(pending.email, "ursula_le_guin@gmail.com");
+    let queued = sqlx::query!(
+        "SELECT tag FROM email_outbox WHERE recipient = $1",
+        "ursula@earthsea.com"
+    )
+    .fetch_one(&app.pool)
+    .await?;
+    assert_eq!(queued.tag.as_deref(), Some("email-change"));
+
+    let change_token = sqlx::query!("SELECT change_token FROM email_changes")
+        .fetch_one(&app.pool)
+        .await?
+        .change_token;
+    reqwest::get(format!(
+        "{}/subscriptions/email-change/confirm?token={}",
+        app.base_address, change_token
+    ))
+    .await?
+    .error_for_status()?;
+
+    let saved = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.pool)
+        .await?;
+    assert_eq!(saved.email, "ursula@earthsea.com");
+    let history = sqlx::query!("SELECT old_email, confirmed_at FROM email_changes")
+        .fetch_one(&app.pool)
+        .await?;
+    assert_eq!(history.old_email, "ursula_le_guin@gmail.com");
+    assert!(history.confirmed_at.is_some());
+    Ok(())
+}
+
+#[tokio::test]
+async fn email_change_to_taken_address_returns_400() -> Result<(), anyhow::Error> {
+    let app = spawn_app().await?;
+    app.create_confirmed_subscriber("Le Guin", "ursula_le_guin@gmail.com")
+        .await?;
+    let token = app.preference_token("ursula_le_guin@gmail.com").await?;
+    app.post_email_change(&token, "ursula@earthsea.com")
+        .await?
+        .error_for_status()?;
+    app.create_confirmed_subscriber("Ged", "ursula@earthsea.com")
+        .await?;
+
+    let taken = app.post_email_change(&token, "ursula@earthsea.com").await?;
+    assert_eq!(400, taken.status().as_u16());
+
+    let change_token = sqlx::query!("SELECT change_token FROM email_changes")
+        .fetch_one(&app.pool)
+        .await?
+        .change_token;
+    let raced = reqwest::get(format!(
+        "{}/subscriptions/email-change/confirm?token={}",
+        app.base_address, change_token
+    ))
+    .await?;
+    assert_eq!(400, raced.status().as_u16());
+    Ok(())
+}
+
+#[tokio::test]
+async fn edited_template_is_validated_and_saved() -> Result<(), anyhow::Error> {
+    let app = spawn_app().await?;
+
+    let broken = app
+        .admin_put("/admin/templates/layout")
+        .json(&serde_json::json!({
+            "kind": "Layout",
+            "html_body": "{% block content %}",
+            "text_body": "{% block content %}{% endblock %}",
+        }))
+        .send()
+        .await?;
+    assert_eq!(400, broken.status().as_u16());
+
+    let demoted = app
+        .admin_put("/admin/templates/confirmation")
+        .json(&serde_json::json!({
+            "kind": "Partial",
+            "html_body": "",
+            "text_body": "",
+        }))
+        .send()
+        .await?;
+    assert_eq!(400, demoted.status().as_u16());
+
+    app.admin_put("/admin/templates/confirmation")
+        .json(&serde_json::json!({
+            "kind": "Email",
+            "subject": "Hello {{ name }}",
+            "html_body": "{% extends \"layout\" %}{% block content %}<a href=\"{{ confirmation_link }}\">Join</a>{% endblock %}",
+            "text_body": "{% extends \"layout\" %}{% block content %}Join: {{ confirmation_link }}{% endblock %}",
+        }))
+        .send()
+        .await?
+        .error_for_status()?;
+
+    let template: serde_json::Value = app
+        .admin_get("/admin/templates/confirmation")
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(template["subject"], "Hello {{ name }}");
+
+    let form = hashmap! {
+        "name" => "Le Guin",
+        "email" => "ursula_le_guin@gmail.com"
+    };
+    app.post_subscriptions(&form).await?.error_for_status()?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn newsletter_with_unknown_merge_field_returns_400() -> Result<(), anyhow::Error> {
+    let app = spawn_app().await?;
+    app.create_confirmed_subscriber("Le Guin", "ursula_le_guin@gmail.com")
+        .await?;
+
+    let body = |html: &str| {
+        serde_json::json!({
+            "title": "News for {{ name }}",
+            "content": {
+                "html_content": html,
+                "text_content": "Hi {{ name }}, leave at {{ unsubscribe_link }}",
+            },
+        })
+    };
+
+    let unknown = app
+        .admin_post("/newsletter")
+        .json(&body("<p>Hi {{ first_name }}</p>"))
+        .send()
+        .await?;
+    assert_eq!(400, unknown.status().as_u16());
+
+    app.admin_post("/newsletter")
+        .json(&body(
+            "<p>Hi {{ name }} from {{ attributes.city | default(\"Earthsea\") }}</p>",
+        ))
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn newsletter_accepts_markdown_only_content() -> Result<(), anyhow::Error> {
+    let app = spawn_app().await?;
+    app.create_confirmed_subscriber("Le Guin", "ursula_le_guin@gmail.com")
+        .await?;
+
+    app.admin_post("/newsletter")
+        .json(&serde_json::json!({
+            "title": "News",
+            "content": { "markdown": "# Hi {{ name }}\n\n[Leave]({{ unsubscribe_link }})" },
+        }))
+        .send()
+        .await?
+        .error_for_status()?;
+
+    let missing_text = app
+        .admin_post("/newsletter")
+        .json(&serde_json::json!({
+            "title": "News",
+            "content": { "html_content": "<p>Hi</p>" },
+        }))
+        .send()
+        .await?;
+    assert_eq!(400, missing_text.status().as_u16());
+    Ok(())
+}
+
+#[tokio::test]
+async fn issue_preview_renders_for_subscriber_and_test_send_uses_seeds() -> Result<(), anyhow::Error>
+{
+    let app = spawn_app().await?;
+    let subscriber_id = app
+        .create_confirmed_subscriber("Le Guin", "ursula_le_guin@gmail.com")
+        .await?;
+
+    let issue: serde_json::Value = app
+        .admin_post("/admin/issues")
+        .json(&serde_json::json!({
+            "title": "News for {{ name }}",
+            "content": { "markdown": "Hello **{{ name }}** at {{ email }}" },
+        }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let issue_id = issue["id"].as_str().unwrap();
+
+    let preview: serde_json::Value = app
+        .admin_get(&format!(
+            "/admin/issues/{}/preview?subscriber_id={}",
+            issue_id, subscriber_id
+        ))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(preview["subject"], "News for Le Guin");
+    assert!(preview["html"]
+        .as_str()
+        .unwrap()
+        .contains("Hello <strong>Le Guin</strong> at ursula_le_guin@gmail.com"));
+    assert!(preview["text"]
+        .as_str()
+        .unwrap()
+        .contains("Hello Le Guin at ursula_le_guin@gmail.com"));
+
+    let invalid_seed = app
+        .admin_post(&format!("/admin/issues/{}/test-send", issue_id))
+        .json(&serde_json::json!({ "emails": ["not-an-email"] }))
+        .send()
+        .await?;
+    assert_eq!(400, invalid_seed.status().as_u16());
+
+    app.admin_post(&format!("/admin/issues/{}/test-send", issue_id))
+        .json(&serde_json::json!({ "emails": ["editor@earthsea.com", "proofreader@earthsea.com"] }))
+        .send()
+        .await?
+        .error_for_status()?;
+    let published = sqlx::query!("SELECT published_at FROM newsletter_issues")
+        .fetch_one(&app.pool)
+        .await?;
+    assert!(published.published_at.is_none());
+
+    app.admin_post(&format!("/admin/issues/{}/publish", issue_id))
+        .json(&serde_json::json!({}))
+        .send()
+        .await?
+        .error_for_status()?;
+    let published = sqlx::query!("SELECT published_at FROM newsletter_issues")
+        .fetch_one(&app.pool)
+        .await?;
+    assert!(published.published_at.is_some());
+    Ok(())
+}
+
+#[tokio::test]
+async fn subscriber_locale_is_captured_from_form_or_accept_language() -> Result<(), anyhow::Error> {
+    let app = spawn_app().await?;
+
+    let form = hashmap! {
+        "name" => "Le Guin",
+        "email" => "ursula_le_guin@gmail.com",
+        "locale" => "pt_br"
+    };
+    app.post_subscriptions(&form).await?.error_for_status()?;
+    reqwest::Client::new()
+        .post(format!("{}/subscriptions", app.base_address))
+        .header("Accept-Language", "en;q=0.5, de-AT;q=0.9")
+        .form(&hashmap! { "name" => "Ged", "email" => "ged@earthsea.com" })
+        .send()
+        .await?
+        .error_for_status()?;
+
+    let locales = sqlx::query!("SELECT email, locale FROM subscriptions ORDER BY email")
+        .fetch_all(&app.pool)
+        .await?;
+    assert_eq!(locales[0].email, "ged@earthsea.com");
+    assert_eq!(locales[0].locale.as_deref(), Some("de-AT"));
+    assert_eq!(locales[1].locale.as_deref(), Some("pt-BR"));
+
+    let invalid = hashmap! {
+        "name" => "Tenar",
+        "email" => "tenar@earthsea.com",
+        "locale" => "not a locale"
+    };
+    assert_eq!(
+        400,
+        app.post_subscriptions(&invalid).await?.status().as_u16()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn localized_template_is_stored_next_to_default() -> Result<(), anyhow::Error> {
+    let app = spawn_app().await?;
+
+    app.admin_put("/admin/templates/confirmation?locale=de")
+        .json(&serde_json::json!({
+            "kind": "Email",
+            "subject": "Willkommen!",
+            "html_body": "{% extends \"layout\" %}{% block content %}<a href=\"{{ confirmation_link }}\">Bestätigen</a>{% endblock %}",
+            "text_body": "{% extends \"layout\" %}{% block content %}Bestätigen: {{ confirmation_link }}{% endblock %}",
+        }))
+        .send()
+        .await?
+        .error_for_status()?;
+
+    let german: serde_json::Value = app
+        .admin_get("/admin/templates/confirmation?locale=de")
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let default: serde_json::Value = app
+        .admin_get("/admin/templates/confirmation")
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(german["subject"], "Willkommen!");
+    assert_eq!(default["locale"], "en");
+    assert_eq!(default["subject"], "Welcome!");
+
+    let form = hashmap! {
+        "name" => "Le Guin",
+        "email" => "ursula_le_guin@gmail.com",
+        "locale" => "de-AT"
+    };
+    app.post_subscriptions(&form).await?.error_for_status()?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn tracked_issue_records_opens_and_clicks() -> Result<(), anyhow::Error> {
+    let app = spawn_app().await?;
+    let subscriber_id = app
+        .create_confirmed_subscriber("Le Guin", "ursula_le_guin@gmail.com")
+        .await?;
+    let issue: serde_json::Value = app
+        .admin_post("/admin/issues")
+        .json(&serde_json::json!({
+            "title": "News",
+            "content": { "markdown": "Read [the post](https://example.com/post)" },
+            "tracking": true,
+        }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let issue_id = issue["id"].as_str().unwrap();
+    let preview = || async {
+        let preview: serde_json::Value = app
+            .admin_get(&format!(
+                "/admin/issues/{}/preview?subscriber_id={}",
+                issue_id, subscriber_id
+            ))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok::<_, anyhow::Error>(preview["html"].as_str().unwrap().to_owned())
+    };
+    let tracked_path = |html: &str, prefix: &str| {
+        let start = html.find(prefix).unwrap();
+        let end = start + html[start..].find('"').unwrap();
+        format!("{}{}", app.base_address, &html[start..end])
+    };
+
+    let html = preview().await?;
+    let click = tracked_path(&html, "/t/click/");
+    let open = tracked_path(&html, "/t/open/");
+    assert!(!html.contains("href=\"https://example.com/post\""));
+
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?;
+    let redirect = client.get(&click).send().await?;
+    assert_eq!(303, redirect.status().as_u16());
+    assert_eq!(redirect.headers()["location"], "https://example.com/post");
+    let forged = client
+        .get(click.replace("example.com", "evil.example.com"))
+        .send()
+        .await?;
+    assert_eq!(400, forged.status().as_u16());
+    let pixel = client.get(&open).send().await?.error_for_status()?;
+    assert_eq!(pixel.headers()["content-type"], "image/gif");
+
+    let stats: serde_json::Value = app
+        .admin_get(&format!("/admin/issues/{}/stats", issue_id))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(stats["opens"], 1);
+    assert_eq!(stats["unique_clicks"], 1);
+
+    let token = app.preference_token("ursula_le_guin@gmail.com").await?;
+    app.post_preferences(&[
+        ("token", &token),
+        ("name", "Le Guin"),
+        ("list", "default"),
+        ("tracking_opt_out", "on"),
+    ])
+    .await?
+    .error_for_status()?;
+    let html = preview().await?;
+    assert!(html.contains("https://example.com/post"));
+    assert!(!html.contains("/t/open/"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn email_events_mark_bounced_and_complained_subscribers() -> Result<(), anyhow::Error> {
+    let app = spawn_app().await?;
+    app.create_confirmed_subscriber("Le Guin", "ursula_le_guin@gmail.com")
+        .await?;
+    app.create_confirmed_subscriber("Ged", "ged@gmail.com")
+        .await?;
+    let client = reqwest::Client::new();
+    let url = format!("{}/webhooks/email-events", app.base_address);
+    let post_event = |record_type: &str, kind: &str, email: &str| {
+        client
+            .post(&url)
+            .header("X-Webhook-Token", "webhook_secret_value")
+            .json(&serde_json::json!({
+                "RecordType": record_type,
+                "Type": kind,
+                "Email": email,
+            }))
+            .send()
+    };
+    let statuses = || async {
+        let rows = sqlx::query!(
+            r#"
+        SELECT s.email, s.status, ls.status AS list_status FROM subscriptions s
+        JOIN list_subscriptions ls ON ls.subscriber_id = s.id
+        ORDER BY s.email
+        "#
+        )
+        .fetch_all(&app.pool)
+        .await?;
+        Ok::<_, anyhow::Error>(
+            rows.into_iter()
+                .map(|x| (x.email, x.status, x.list_status))
+                .collect::<Vec<_>>(),
+        )
+    };
+
+    let unauthorized = client
+        .post(&url)
+        .json(&serde_json::json!({
+            "RecordType": "SpamComplaint",
+            "Email": "ged@gmail.com",
+        }))
+        .send()
+        .await?;
+    assert_eq!(401, unauthorized.status().as_u16());
+    let forged = client
+        .post(&url)
+        .header("X-Webhook-Token", "webhook_secret_valuE")
+        .json(&serde_json::json!({ "RecordType": "SpamComplaint", "Email": "ged@gmail.com" }))
+        .send()
+        .await?;
+    assert_eq!(401, forged.status().as_u16());
+
+    for _ in 0..2 {
+        post_event("Bounce", "SoftBounce", "ursula_le_guin@gmail.com")
+            .await?
+            .error_for_status()?;
+    }
+    post_event("Delivery", "", "ursula_le_guin@gmail.com")
+        .await?
+        .error_for_status()?;
+    for _ in 0..2 {
+        post_event("Bounce", "SoftBounce", "ursula_le_guin@gmail.com")
+            .await?
+            .error_for_status()?;
+    }
+    post_event("SpamComplaint", "SpamComplaint", "ged@gmail.com")
+        .await?
+        .error_for_status()?;
+    assert_eq!(
+        statuses().await?[1].2,
+        ConfirmationStatus::Confirmed.as_ref()
+    );
+
+    post_event("Bounce", "SoftBounce", "Ursula_Le_Guin@gmail.com")
+        .await?
+        .error_for_status()?;
+    post_event("Bounce", "HardBounce", "unknown@gmail.com")
+        .await?
+        .error_for_status()?;
+
+    let complained = ConfirmationStatus::Complained.as_ref().to_owned();
+    let bounced = ConfirmationStatus::Bounced.as_ref().to_owned();
+    assert_eq!(
+        statuses().await?,
+        vec![
+            ("ged@gmail.com".into(), complained.clone(), complained),
+            ("ursula_le_guin@gmail.com".into(), bounced.clone(), bounced),
+        ]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn suppression_list_collects_unsubscribes_and_manual_entries() -> Result<(), anyhow::Error> {
+    let app = spawn_app().await?;
+    app.create_confirmed_subscriber("Le Guin", "Ursula_Le_Guin@gmail.com")
+        .await?;
+    let token = app.preference_token("Ursula_Le_Guin@gmail.com").await?;
+    app.post_preferences(&[("token", &token), ("unsubscribe", "all")])
+        .await?
+        .error_for_status()?;
+
+    let created = app
+        .admin_post("/admin/suppressions")
+        .json(&serde_json::json!({ "value": "Earthsea.com" }))
+        .send()
+        .await?;
+    assert_eq!(201, created.status().as_u16());
+    let duplicate = app
+        .admin_post("/admin/suppressions")
+        .json(&serde_json::json!({ "value": "earthsea.com" }))
+        .send()
+        .await?;
+    assert_eq!(400, duplicate.status().as_u16());
+    let invalid = app
+        .admin_post("/admin/suppressions")
+        .json(&serde_json::json!({ "value": "localhost" }))
+        .send()
+        .await?;
+    assert_eq!(400, invalid.status().as_u16());
+
+    let suppressions: Vec<serde_json::Value> = app
+        .admin_get("/admin/suppressions")
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let entries = suppressions
+        .iter()
+        .map(|x| {
+            (
+                x["value"].as_str().unwrap(),
+                x["domain"].as_bool().unwrap(),
+                x["reason"].as_str().unwrap(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        entries,
+        vec![
+            ("ursula_le_guin@gmail.com", false, "Unsubscribed"),
+            ("earthsea.com", true, "Manual"),
+        ]
+    );
+
+    let deleted = app
+        .admin_delete("/admin/suppressions/earthsea.com")
+        .send()
+        .await?;
+    assert_eq!(204, deleted.status().as_u16());
+    let missing = app
+        .admin_delete("/admin/suppressions/earthsea.com")
+        .send()
+        .await?;
+    assert_eq!(404, missing.status().as_u16());
+    Ok(())
+}
+
+#[tokio::test]
+async fn published_issue_records_deliveries_per_subscriber() -> Result<(), anyhow::Error> {
+    let app = spawn_app().await?;
+    app.create_confirmed_subscriber("Le Guin", "ursula_le_guin@gmail.com")
+        .await?;
+    app.create_confirmed_subscriber("Ged", "ged@gmail.com")
+        .await?;
+    app.create_confirmed_subscriber("Tenar", "tenar@atuan.com")
+        .await?;
+    app.admin_post("/admin/suppressions")
+        .json(&serde_json::json!({ "value": "atuan.com" }))
+        .send()
+        .await?
+        .error_for_status()?;
+    let issue: serde_json::Value = app
+        .admin_post("/admin/issues")
+        .json(&serde_json::json!({
+            "title": "News",
+            "content": { "markdown": "Hello {{ name }}" },
+        }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let issue_id = issue["id"].as_str().unwrap();
+    app.admin_post(&format!("/admin/issues/{}/publish", issue_id))
+        .json(&serde_json::json!({}))
+        .send()
+        .await?
+        .error_for_status()?;
+
+    reqwest::Client::new()
+        .post(format!("{}/webhooks/email-events", app.base_address))
+        .header("X-Webhook-Token", "webhook_secret_value")
+        .json(&serde_json::json!({
+            "RecordType": "Bounce",
+            "Type": "HardBounce",
+            "Email": "ged@gmail.com",
+        }))
+        .send()
+        .await?
+        .error_for_status()?;
+
+    let deliveries: Vec<serde_json::Value> = app
+        .admin_get(&format!("/admin/issues/{}/deliveries", issue_id))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let mut statuses = deliveries
+        .iter()
+        .map(|x| {
+            (
+                x["email"].as_str().unwrap(),
+                x["status"].as_str().unwrap(),
+                x["attempts"].as_i64().unwrap(),
+            )
+        })
+        .collect::<Vec<_>>();
+    statuses.sort();
+    assert_eq!(
+        statuses,
+        vec![
+            ("ged@gmail.com", "Bounced", 1),
+            ("tenar@atuan.com", "Suppressed", 1),
+            ("ursula_le_guin@gmail.com", "Sent", 1),
+        ]
+    );
+    let sent: Vec<serde_json::Value> = app
+        .admin_get(&format!(
+            "/admin/issues/{}/deliveries?status=sent",
+            issue_id
+        ))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(sent.len(), 1);
+    assert!(sent[0]["sent_at"].is_string());
+
+    let summary: serde_json::Value = app
+        .admin_get(&format!("/admin/issues/{}/deliveries/summary", issue_id))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(
+        summary,
+        serde_json::json!({
+            "queued": 0,
+            "sent": 1,
+            "failed": 0,
+            "bounced": 1,
+            "suppressed": 1,
+        })
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn issues_attach_uploaded_assets() -> Result<(), anyhow::Error> {
+    let app = spawn_app().await?;
+    app.create_confirmed_subscriber("Le Guin", "ursula_le_guin@gmail.com")
+        .await?;
+    let upload = |filename: &str, content_type: &str, content: &'static [u8]| {
+        app.admin_post(&format!("/admin/assets?filename={}", filename))
+            .header("Content-Type", content_type)
+            .body(content)
+            .send()
+    };
+    let logo: serde_json::Value = upload("logo.png", "image/png", b"\x89PNG\r\n\x1a\n")
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let map: serde_json::Value = upload("map.pdf", "application/pdf", b"%PDF-1.7")
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(logo["content_type"], "image/png");
+    assert_eq!(logo["size"], 8);
+    let invalid_upload = upload("../map.pdf", "application/pdf", b"%PDF-1.7").await?;
+    assert_eq!(400, invalid_upload.status().as_u16());
+
+    let create_issue = |markdown: String, attachments: Vec<&serde_json::Value>| {
+        app.admin_post("/admin/issues")
+            .json(&serde_json::json!({
+                "title": "Maps",
+                "content": { "markdown": markdown, "attachments": attachments },
+            }))
+            .send()
+    };
+    let issue: serde_json::Value = create_issue(
+        format!(
+            "![logo](cid:{})\n\nThe map is attached",
+            logo["id"].as_str().unwrap()
+        ),
+        vec![&map["id"]],
+    )
+    .await?
+    .error_for_status()?
+    .json()
+    .await?;
+    app.admin_post(&format!(
+        "/admin/issues/{}/publish",
+        issue["id"].as_str().unwrap()
+    ))
+    .json(&serde_json::json!({}))
+    .send()
+    .await?
+    .error_for_status()?;
+
+    let unknown_asset = create_issue(
+        "Hello".to_string(),
+        vec![&serde_json::json!(uuid::Uuid::new_v4())],
+    )
+    .await?;
+    let inline_document = create_issue(
+        format!("![map](cid:{})", map["id"].as_str().unwrap()),
+        vec![],
+    )
+    .await?;
+    let invalid_cid = create_issue("![logo](cid:logo)".to_string(), vec![]).await?;
+    assert_eq!(404, unknown_asset.status().as_u16());
+    assert_eq!(400, inline_document.status().as_u16());
+    assert_eq!(400, invalid_cid.status().as_u16());
+    Ok(())
+}
+
+#[tokio::test]
+async fn sends_fail_over_to_the_next_provider() -> Result<(), anyhow::Error> {
+    let primary = MockServer::start().await;
+    let secondary = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/email"))
+        .respond_with(ResponseTemplate::new(503))
+        .expect(1)
+        .mount(&primary)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&secondary)
+        .await;
+    let provider = |name: &str, priority: u32, server: &MockServer| EmailProviderConfig {
+        name: name.to_string(),
+        kind: EmailProviderKind::Http,
+        priority,
+        base_url: server.uri(),
+        authorization_token: "secret_value".to_string(),
+    };
+    let app = spawn_app_with(|config| {
+        config.email_client.circuit_breaker.failure_threshold = NonZeroU32::MIN;
+        config.email_client.providers = vec![
+            provider("secondary", 2, &secondary),
+            provider("primary", 1, &primary),
+        ];
+    })
+    .await?;
+
+    for (name, email) in [
+        ("Le Guin", "ursula_le_guin@gmail.com"),
+        ("Ged", "ged@gmail.com"),
+    ] {
+        app.post_subscriptions(&hashmap! { "name" => name, "email" => email })
+            .await?
+            .error_for_status()?;
+    }
+    eventually(|| async {
+        let requests = secondary.received_requests().await.unwrap_or_default();
+        Ok(requests.len() == 2)
+    })
+    .await?;
+
+    let health: Vec<serde_json::Value> = app
+        .admin_get("/admin/email-providers")
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(health[0]["name"], "primary");
+    assert_eq!(health[0]["state"], "Open");
+    assert_eq!(health[0]["consecutive_failures"], 1);
+    assert!(health[0]["last_error"].as_str().unwrap().contains("503"));
+    assert_eq!(health[1]["name"], "secondary");
+    assert_eq!(health[1]["state"], "Closed");
+    Ok(())
+}
+
+#[tokio::test]
+async fn confirmation_email_is_retried_from_the_outbox() -> Result<(), anyhow::Error> {
+    let provider = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/email"))
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&provider)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&provider)
+        .await;
+    let app = spawn_app_with(|config| {
+        config.email_client.outbox.poll_interval_seconds = 1;
+        config.email_client.providers = vec![EmailProviderConfig {
+            name: "postmark".to_string(),
+            kind: EmailProviderKind::Http,
+            priority: 1,
+            base_url: provider.uri(),
+            authorization_token: "secret_value".to_string(),
+        }];
+    })
+    .await?;
+
+    app.post_subscriptions(
+        &hashmap! { "name" => "Le Guin", "email" => "ursula_le_guin@gmail.com" },
+    )
+    .await?
+    .error_for_status()?;
+
+    let outbox_email = || async {
+        let email = sqlx::query!(
+            "SELECT attempts, last_error, sent_at FROM email_outbox WHERE recipient = $1",
+            "ursula_le_guin@gmail.com"
+        )
+        .fetch_one(&app.pool)
+        .await?;
+        Ok::<_, anyhow::Error>(email)
+    };
+    eventually(|| async { Ok(outbox_email().await?.sent_at.is_some()) }).await?;
+    let email = outbox_email().await?;
+    assert_eq!(email.attempts, 2);
+    assert_eq!(email.last_error, None);
+    Ok(())
+}
+
+#[tokio::test]
+async fn errors_are_problem_details() -> Result<(), anyhow::Error> {
+    let app = spawn_app().await?;
+
+    let invalid = app
+        .admin_post("/admin/suppressions")
+        .json(&serde_json::json!({ "value": "not a domain" }))
+        .send()
+        .await?;
+    let unauthorized = reqwest::Client::new()
+        .get(format!("{}/admin/suppressions", app.base_address))
+        .send()
+        .await?;
+
+    assert_eq!(400, invalid.status().as_u16());
+    assert_eq!(
+        invalid.headers()["content-type"],
+        "application/problem+json"
+    );
+    let request_id = invalid.headers()["x-request-id"].to_str()?.to_owned();
+    let problem: serde_json::Value = invalid.json().await?;
+    assert_eq!(problem["type"], "urn:zero2prod:problem:invalid_request");
+    assert_eq!(problem["title"], "Invalid request");
+    assert_eq!(problem["status"], 400);
+    assert_eq!(problem["code"], "invalid_request");
+    assert_eq!(problem["instance"], request_id.as_str());
+    assert!(problem["detail"].is_string());
+    assert_eq!(401, unauthorized.status().as_u16());
+    let problem: serde_json::Value = unauthorized.json().await?;
+    assert_eq!(problem["code"], "unauthorized");
+
+    let invalid_path = app
+        .admin_get("/admin/issues/not-a-uuid/preview")
+        .send()
+        .await?;
+    let missing_query = reqwest::Client::new()
+        .get(format!("{}/subscriptions/confirm", app.base_address))
+        .send()
+        .await?;
+    for response in [invalid_path, missing_query] {
+        assert_eq!(400, response.status().as_u16());
+        assert_eq!(
+            response.headers()["content-type"],
+            "application/problem+json"
+        );
+        let problem: serde_json::Value = response.json().await?;
+        assert_eq!(problem["code"], "invalid_request");
+        assert!(problem["instance"].is_string());
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn concurrent_subscriptions_for_one_email_both_succeed() -> Result<(), anyhow::Error> {
+    let app = spawn_app().await?;
+    let form = hashmap! { "name" => "Le Guin", "email" => "ursula_le_guin@gmail.com" };
+
+    let responses =
+        futures_util::future::join_all((0..4).map(|_| app.post_subscriptions(&form))).await;
+
+    for response in responses {
+        assert_eq!(200, response?.status().as_u16());
+    }
+    let count = sqlx::query_scalar!(r#"SELECT count(*) AS "count!" FROM subscriptions"#)
+        .fetch_one(&app.pool)
+        .await?;
+    assert_eq!(count, 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn publishing_an_issue_again_sends_once_per_subscriber() -> Result<(), anyhow::Error> {
+    let provider = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(batch_response(&[]))
+        .mount(&provider)
+        .await;
+    let app = spawn_app_with(|config| {
+        config.email_client.providers = vec![EmailProviderConfig {
+            name: "postmark".to_string(),
+            kind: EmailProviderKind::Http,
+            priority: 1,
+            base_url: provider.uri(),
+            authorization_token: "secret_value".to_string(),
+        }];
+    })
+    .await?;
+    app.create_confirmed_subscriber("Le Guin", "ursula_le_guin@gmail.com")
+        .await?;
+    app.create_confirmed_subscriber("Ged", "ged@gmail.com")
+        .await?;
+    let issue: serde_json::Value = app
+        .admin_post("/admin/issues")
+        .json(&serde_json::json!({
+            "title": "News",
+            "content": { "markdown": "Hello {{ name }}" },
+        }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let issue_id = issue["id"].as_str().unwrap();
+
+    for _ in 0..2 {
+        app.admin_post(&format!("/admin/issues/{}/publish", issue_id))
+            .json(&serde_json::json!({}))
+            .send()
+            .await?
+            .error_for_status()?;
+    }
+
+    let mut recipients = vec![];
+    for request in provider.received_requests().await.unwrap_or_default() {
+        if request.url.path() == "/email/batch" {
+            let emails: Vec<serde_json::Value> = request.body_json()?;
+            recipients.extend(emails.into_iter().map(|x| x["to"].clone()));
+        }
+    }
+    recipients.sort_by_key(|x| x.to_string());
+    assert_eq!(recipients, ["ged@gmail.com", "ursula_le_guin@gmail.com"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn smtp_providers_send_dkim_signed_mime_messages() -> Result<(), anyhow::Error> {
+    use rsa::pkcs8::{EncodePrivateKey, LineEnding};
+
+    let smtp = SmtpServer::start().await?;
+    let key = rsa::RsaPrivateKey::new(&mut rand::thread_rng(), 1024)?;
+    let key_path = std::env::temp_dir().join(format!("{}.pem", uuid::Uuid::now_v7()));
+    std::fs::write(&key_path, key.to_pkcs8_pem(LineEnding::LF)?.as_bytes())?;
+    let app = spawn_app_with(|config| {
+        config.email_client.providers = vec![EmailProviderConfig {
+            name: "relay".to_string(),
+            kind: EmailProviderKind::Smtp,
+            priority: 1,
+            base_url: smtp.url.clone(),
+            authorization_token: String::new(),
+        }];
+        config.email_client.dkim = Some(DkimConfig {
+            selector: "news".to_string(),
+            domain: "mail.ru".to_string(),
+            private_key_path: key_path.to_string_lossy().into_owned(),
+        });
+    })
+    .await?;
+
+    app.post_subscriptions(
+        &hashmap! { "name" => "Le Guin", "email" => "ursula_le_guin@gmail.com" },
+    )
+    .await?
+    .error_for_status()?;
+
+    eventually(|| async { Ok(smtp.messages().len() == 1) }).await?;
+    let message = &smtp.messages()[0];
+    assert!(message
+        .starts_with("DKIM-Signature: v=1; a=rsa-sha256; s=news; d=mail.ru; c=relaxed/relaxed;"));
+    assert!(message.contains("To: <ursula_le_guin@gmail.com>\r\n"));
+    assert!(message.contains("Content-Type: multipart/alternative"));
+    std::fs::remove_file(key_path)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn smtp_providers_send_attachments_and_inline_images() -> Result<(), anyhow::Error> {
+    let smtp = SmtpServer::start().await?;
+    let app = spawn_app_with(|config| {
+        config.email_client.providers = vec![EmailProviderConfig {
+            name: "relay".to_string(),
+            kind: EmailProviderKind::Smtp,
+            priority: 1,
+            base_url: smtp.url.clone(),
+            authorization_token: String::new(),
+        }];
+    })
+    .await?;
+    app.create_confirmed_subscriber("Le Guin", "ursula_le_guin@gmail.com")
+        .await?;
+    let upload = |filename: &str, content_type: &str, content: &'static [u8]| {
+        app.admin_post(&format!("/admin/assets?filename={}", filename))
+            .header("Content-Type", content_type)
+            .body(content)
+            .send()
+    };
+    let logo: serde_json::Value = upload("logo.png", "image/png", b"\x89PNG\r\n\x1a\n")
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let map: serde_json::Value = upload("map.pdf", "application/pdf", b"%PDF-1.7")
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let logo_id = logo["id"].as_str().unwrap();
+    let issue: serde_json::Value = app
+        .admin_post("/admin/issues")
+        .json(&serde_json::json!({
+            "title": "Maps",
+            "content": {
+                "markdown": format!("![logo](cid:{})\n\nThe map is attached", logo_id),
+                "attachments": [map["id"]],
+            },
+        }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    app.admin_post(&format!(
+        "/admin/issues/{}/publish",
+        issue["id"].as_str().unwrap()
+    ))
+    .json(&serde_json::json!({}))
+    .send()
+    .await?
+    .error_for_status()?;
+
+    let messages = smtp.messages();
+    let message = messages
+        .iter()
+        .find(|x| x.contains("Subject: Maps\r\n"))
+        .unwrap();
+    let related = message.find("Content-Type: multipart/related").unwrap();
+    let html = message.find("Content-Type: text/html").unwrap();
+    let image = message.find(&format!("Content-ID: <{}>", logo_id)).unwrap();
+    let attachment = message.find("filename=\"map.pdf\"").unwrap();
+    assert!(message.contains("Content-Type: multipart/mixed"));
+    assert!(related < html && html < image && image < attachment);
+    assert!(message.contains(&format!("src=3D\"cid:{}\"", logo_id)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn repeated_subscription_email_uses_the_stored_name() -> Result<(), anyhow::Error> {
+    let app = spawn_app().await?;
+    app.admin_post("/admin/lists")
+        .json(&serde_json::json!({ "slug": "rust-weekly", "name": "Rust weekly" }))
+        .send()
+        .await?
+        .error_for_status()?;
+    app.post_subscriptions(
+        &hashmap! { "name" => "Le Guin", "email" => "ursula_le_guin@gmail.com" },
+    )
+    .await?
+    .error_for_status()?;
+
+    let form = hashmap! {
+        "name" => "Visit evil.example",
+        "email" => "ursula_le_guin@gmail.com",
+        "list" => "rust-weekly",
+    };
+    app.post_subscriptions(&form).await?.error_for_status()?;
+
+    let emails = sqlx::query!(
+        "SELECT text_content FROM email_outbox WHERE recipient = $1 ORDER BY created_at",
+        "ursula_le_guin@gmail.com"
+    )
+    .fetch_all(&app.pool)
+    .await?;
+    assert_eq!(emails.len(), 2);
+    assert!(emails[1].text_content.contains("Le Guin"));
+    assert!(!emails[1].text_content.contains("evil.example"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn tag_targeting_requires_every_tag_like_the_listing() -> Result<(), anyhow::Error> {
+    let app = spawn_app().await?;
+    let both = app
+        .create_confirmed_subscriber("Hermann", "hermann@example.com")
+        .await?;
+    let beta = app
+        .create_confirmed_subscriber("Albert", "albert@example.com")
+        .await?;
+    for (id, tags) in [(&both, vec!["beta", "vip"]), (&beta, vec!["beta"])] {
+        app.admin_post(&format!("/admin/subscribers/{}/tags", id))
+            .json(&serde_json::json!({ "tags": tags }))
+            .send()
+            .await?
+            .error_for_status()?;
+    }
+
+    let listed: Vec<serde_json::Value> = app
+        .admin_get("/admin/subscribers?tags=beta,vip")
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let issue: serde_json::Value = app
+        .admin_post("/admin/issues")
+        .json(&serde_json::json!({
+            "title": "News",
+            "content": { "markdown": "Hello {{ name }}" },
+        }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let issue_id = issue["id"].as_str().unwrap();
+    app.admin_post(&format!("/admin/issues/{}/publish", issue_id))
+        .json(&serde_json::json!({ "include_tags": ["beta", "vip"] }))
+        .send()
+        .await?
+        .error_for_status()?;
+    let deliveries: Vec<serde_json::Value> = app
+        .admin_get(&format!("/admin/issues/{}/deliveries", issue_id))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0]["email"], "hermann@example.com");
+    assert_eq!(deliveries.len(), 1);
+    assert_eq!(deliveries[0]["email"], "hermann@example.com");
+    Ok(())
+}
+
+#[tokio::test]
+async fn issue_merge_fields_cant_inject_markup_or_scripts() -> Result<(), anyhow::Error> {
+    let app = spawn_app().await?;
+    let subscriber_id = app
+        .create_confirmed_subscriber("Le Guin", "ursula_le_guin@gmail.com")
+        .await?;
+    app.admin_patch(&format!("/admin/subscribers/{}/attributes", subscriber_id))
+        .json(&serde_json::json!({ "site": "javascript:alert(1)" }))
+        .send()
+        .await?
+        .error_for_status()?;
+    let create = |html: &str| {
+        app.admin_post("/admin/issues")
+            .json(&serde_json::json!({
+                "title": "News",
+                "content": { "html_content": html, "text_content": "News" },
+            }))
+            .send()
+    };
+
+    let script = create(r#"<p>{{ "<script>alert(1)</script>" | safe }}</p>"#).await?;
+    let literal_href = create(r#"<a href="{{ 'javascript:alert(1)' }}">site</a>"#).await?;
+    let issue: serde_json::Value = create(r#"<a href="{{ attributes.site }}">site</a>"#)
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let preview: serde_json::Value = app
+        .admin_get(&format!(
+            "/admin/issues/{}/preview?subscriber_id={}",
+            issue["id"].as_str().unwrap(),
+            subscriber_id
+        ))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    assert_eq!(400, script.status().as_u16());
+    assert_eq!(400, literal_href.status().as_u16());
+    assert!(!preview["html"].as_str().unwrap().contains("javascript:"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn rejected_batch_recipients_are_recorded_as_failed() -> Result<(), anyhow::Error> {
+    let provider = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/email/batch"))
+        .respond_with(batch_response(&["ged@gmail.com"]))
+        .mount(&provider)
+        .await;
+    let app = spawn_app_with(|config| {
+        config.email_client.providers = vec![EmailProviderConfig {
+            name: "postmark".to_string(),
+            kind: EmailProviderKind::Http,
+            priority: 1,
+            base_url: provider.uri(),
+            authorization_token: "secret_value".to_string(),
+        }];
+    })
+    .await?;
+    app.create_confirmed_subscriber("Le Guin", "ursula_le_guin@gmail.com")
+        .await?;
+    app.create_confirmed_subscriber("Ged", "ged@gmail.com")
+        .await?;
+    let issue: serde_json::Value = app
+        .admin_post("/admin/issues")
+        .json(&serde_json::json!({
+            "title": "News",
+            "content": { "markdown": "Hello {{ name }}" },
+        }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let issue_id = issue["id"].as_str().unwrap();
+
+    app.admin_post(&format!("/admin/issues/{}/publish", issue_id))
+        .json(&serde_json::json!({}))
+        .send()
+        .await?;
+
+    let deliveries: Vec<serde_json::Value> = app
+        .admin_get(&format!("/admin/issues/{}/deliveries", issue_id))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let mut statuses = deliveries
+        .iter()
+        .map(|x| (x["email"].as_str().unwrap(), x["status"].as_str().unwrap()))
+        .collect::<Vec<_>>();
+    statuses.sort();
+    assert_eq!(
+        statuses,
+        vec![
+            ("ged@gmail.com", "Failed"),
+            ("ursula_le_guin@gmail.com", "Sent")
+        ]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn missing_resources_are_not_found_problems() -> Result<(), anyhow::Error> {
+    let app = spawn_app().await?;
+    let missing_id = uuid::Uuid::new_v4();
+
+    let issue = app
+        .admin_get(&format!(
+            "/admin/issues/{0}/preview?subscriber_id={0}",
+            missing_id
+        ))
+        .send()
+        .await?;
+    let subscriber = app
+        .admin_patch(&format!("/admin/subscribers/{}/attributes", missing_id))
+        .json(&serde_json::json!({ "plan": "pro" }))
+        .send()
+        .await?;
+    let token = app.confirm("missing").await?;
+    let route = reqwest::Client::new()
+        .get(format!("{}/missing", app.base_address))
+        .send()
+        .await?;
+
+    for response in [issue, subscriber, token, route] {
+        assert_eq!(404, response.status().as_u16());
+        assert_eq!(
+            response.headers()["content-type"],
+            "application/problem+json"
+        );
+        let request_id = response.headers()["x-request-id"].to_str()?.to_owned();
+        let problem: serde_json::Value = response.json().await?;
+        assert_eq!(problem["code"], "not_found");
+        assert_eq!(problem["status"], 404);
+        assert_eq!(problem["instance"], request_id.as_str());
+    }
+    Ok(())
+}
@@ -1,18 +1,53 @@
+use anyhow::bail;
 use reqwest::Response;
 use sqlx::{Connection, Executor, PgConnection, PgPool};
 use std::collections::HashMap;
-use std::sync::Once;
+use std::future::Future;
+use std::sync::{Arc, Mutex, Once};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
 use tracing_subscriber::EnvFilter;
 use uuid::Uuid;
+use wiremock::{Request, ResponseTemplate};
 use zero2prod::app_config::{get_app_configuration, AppConfig};
+use zero2prod::domain::value_objects::PasswordHash;
 use zero2prod::startup::{build, get_database_pool};
 
 pub struct TestApp {
     pub base_address: String,
     pub pool: PgPool,
+    pub test_user: TestUser,
     client: reqwest::Client,
 }
 
+pub struct TestUser {
+    pub username: String,
+    pub password: String,
+}
+
+impl TestUser {
+    fn generate() -> Self {
+        Self {
+            username: Uuid::now_v7().to_string(),
+            password: Uuid::now_v7().to_string(),
+        }
+    }
+
+    async fn store(&self, pool: &PgPool) -> Result<(), anyhow::Error> {
+        let password_hash = PasswordHash::new_from_password(&self.password);
+        sqlx::query!(
+            "INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, $3)",
+            Uuid::now_v7(),
+            self.username,
+            password_hash.as_ref()
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+}
+
 impl TestApp {
     pub async fn post_subscriptions(
         &self,
@@ -23,9 +58,238 @@ impl TestApp {
 
         Ok(response)
     }
+
+    pub async fn confirmation_token(
+        &self,
+        email: &str,
+        list: &str,
+    ) -> Result<String, anyhow::Error> {
+        let token = sqlx::query!(
+            r#"
+        SELECT t.subscription_token FROM subscription_tokens t
+        JOIN subscriptions s ON s.id::text = t.subscriber_id
+        JOIN lists l ON l.id = t.list_id
+        WHERE s.email = $1 AND l.slug = $2
+        "#,
+            email,
+            list
+        )
+        .fetch_one(&self.pool)
+        .await?
+        .subscription_token;
+
+        Ok(token)
+    }
+
+    pub async fn preference_token(&self, email: &str) -> Result<String, anyhow::Error> {
+        let token = sqlx::query!(
+            r#"
+        SELECT t.preference_token FROM preference_tokens t
+        JOIN subscriptions s ON s.id = t.subscriber_id
+        WHERE s.email = $1
+        "#,
+            email
+        )
+        .fetch_one(&self.pool)
+        .await?
+        .preference_token;
+
+        Ok(token)
+    }
+
+    pub async fn post_preferences(
+        &self,
+        fields: &[(&str, &str)],
+    ) -> Result<Response, reqwest::Error> {
+        self.client
+            .post(format!("{}/subscriptions/preferences", self.base_address))
+            .form(fields)
+            .send()
+            .await
+    }
+
+    pub async fn post_email_change(
+        &self,
+        token: &str,
+        new_email: &str,
+    ) -> Result<Response, reqwest::Error> {
+        self.client
+            .post(format!("{}/subscriptions/email-change", self.base_address))
+            .form(&[("token", token), ("new_email", new_email)])
+            .send()
+            .await
+    }
+
+    pub fn admin_get(&self, path: &str) -> reqwest::RequestBuilder {
+        self.client
+            .get(format!("{}{}", self.base_address, path))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+    }
+
+    pub fn admin_post(&self, path: &str) -> reqwest::RequestBuilder {
+        self.client
+            .post(format!("{}{}", self.base_address, path))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+    }
+
+    pub fn admin_patch(&self, path: &str) -> reqwest::RequestBuilder {
+        self.client
+            .patch(format!("{}{}", self.base_address, path))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+    }
+
+    pub fn admin_put(&self, path: &str) -> reqwest::RequestBuilder {
+        self.client
+            .put(format!("{}{}", self.base_address, path))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+    }
+
+    pub fn admin_delete(&self, path: &str) -> reqwest::RequestBuilder {
+        self.client
+            .delete(format!("{}{}", self.base_address, path))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+    }
+
+    /// Subscribes to the default list and confirms, returning the subscriber id.
+    pub async fn create_confirmed_subscriber(
+        &self,
+        name: &str,
+        email: &str,
+    ) -> Result<Uuid, anyhow::Error> {
+        let form = HashMap::from([("name", name), ("email", email)]);
+        self.post_subscriptions(&form).await?.error_for_status()?;
+        let token = self.confirmation_token(email, "default").await?;
+        self.confirm(&token).await?.error_for_status()?;
+
+        let id = sqlx::query!("SELECT id FROM subscriptions WHERE email = $1", email)
+            .fetch_one(&self.pool)
+            .await?
+            .id;
+        Ok(id)
+    }
+
+    pub async fn confirm(&self, token: &str) -> Result<Response, reqwest::Error> {
+        self.client
+            .get(format!("{}/subscriptions/confirm", self.base_address))
+            .query(&[("token", token)])
+            .send()
+            .await
+    }
+}
+
+/// Polls `condition` until it holds, e.g. for emails sent by the outbox relay.
+pub async fn eventually<F, Fut>(mut condition: F) -> Result<(), anyhow::Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<bool, anyhow::Error>>,
+{
+    for _ in 0..100 {
+        if condition().await? {
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    bail!("Condition wasn't met in time")
+}
+
+/// Answers provider batch requests with one entry per email like Postmark, `rejected`
+/// recipients get an error code.
+pub fn batch_response(rejected: &'static [&'static str]) -> impl Fn(&Request) -> ResponseTemplate {
+    move |request| {
+        let emails: Vec<serde_json::Value> = request.body_json().unwrap_or_default();
+        let entries = emails
+            .iter()
+            .map(|email| match email["to"].as_str() {
+                Some(to) if rejected.contains(&to) => serde_json::json!({
+                    "ErrorCode": 406,
+                    "Message": "You tried to send to a recipient that has been marked as inactive.",
+                    "To": to,
+                }),
+                to => serde_json::json!({
+                    "ErrorCode": 0,
+                    "Message": "OK",
+                    "MessageID": Uuid::now_v7(),
+                    "To": to,
+                }),
+            })
+            .collect::<Vec<_>>();
+        ResponseTemplate::new(200).set_body_json(entries)
+    }
+}
+
+/// SMTP server accepting every email, for providers of the `smtp` kind.
+pub struct SmtpServer {
+    pub url: String,
+    messages: Arc<Mutex<Vec<String>>>,
+}
+
+impl SmtpServer {
+    pub async fn start() -> Result<Self, anyhow::Error> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("smtp://{}", listener.local_addr()?);
+        let messages = Arc::new(Mutex::new(vec![]));
+
+        let received = messages.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(Self::session(stream, received.clone()));
+            }
+        });
+        Ok(Self { url, messages })
+    }
+
+    /// Received messages in the order they arrived.
+    pub fn messages(&self) -> Vec<String> {
+        self.messages.lock().unwrap().clone()
+    }
+
+    async fn session(
+        stream: TcpStream,
+        messages: Arc<Mutex<Vec<String>>>,
+    ) -> Result<(), anyhow::Error> {
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        writer.write_all(b"220 localhost ESMTP\r\n").await?;
+
+        while let Some(line) = lines.next_line().await? {
+            let command = line.get(..4).unwrap_or_default().to_uppercase();
+            let reply: &[u8] = match command.as_str() {
+                "EHLO" | "HELO" => b"250 localhost\r\n",
+                "DATA" => {
+                    writer
+                        .write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n")
+                        .await?;
+                    let mut message = String::new();
+                    while let Some(line) = lines.next_line().await? {
+                        if line == "." {
+                            break;
+                        }
+                        message.push_str(line.strip_prefix('.').unwrap_or(&line));
+                        message.push_str("\r\n");
+                    }
+                    messages.lock().unwrap().push(message);
+                    b"250 OK\r\n"
+                }
+                "QUIT" => {
+                    writer.write_all(b"221 Bye\r\n").await?;
+                    return Ok(());
+                }
+                _ => b"250 OK\r\n",
+            };
+            writer.write_all(reply).await?;
+        }
+        Ok(())
+    }
 }
 
 pub async fn spawn_app() -> Result<TestApp, anyhow::Error> {
+    spawn_app_with(|_| {}).await
+}
+
+/// Spawns the app with test specific configuration changes.
+pub async fn spawn_app_with(
+    configure: impl FnOnce(&mut AppConfig),
+) -> Result<TestApp, anyhow::Error> {
     static INIT: Once = Once::new();
 
     INIT.call_once(|| {
@@ -33,7 +297,8 @@ pub async fn spawn_app() -> Result<TestApp, anyhow::Error> {
         tracing_subscriber::fmt().with_env_filter(filter).init()
     });
 
-    let configuration = build_test_app_config()?;
+    let mut configuration = build_test_app_config()?;
+    configure(&mut configuration);
     configure_database(&configuration).await?;
 
     let (listener, state) = build(configuration).await?;
@@ -41,12 +306,15 @@ pub async fn spawn_app() -> Result<TestApp, anyhow::Error> {
     let given_port = listener.local_addr()?.port();
 
     let pool = state.repository.inner().clone();
+    let test_user = TestUser::generate();
+    test_user.store(&pool).await?;
     _ = tokio::task::spawn(zero2prod::startup::run_until_stopped(state, listener));
 
     let base_address = format!("http://127.0.0.1:{}", given_port);
     let result = TestApp {
         base_address,
         pool,
+        test_user,
         client: reqwest::Client::new(),
     };
 